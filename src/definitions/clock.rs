@@ -0,0 +1,272 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the clock used for all show timing. The clock
+//! follows the local time by default, but may be accelerated (for example,
+//! to rehearse a show at four times the normal speed). In tests, the clock
+//! may be replaced with a virtual clock which follows the paused tokio time.
+
+// Import standard library features
+use std::sync::RwLock;
+use std::time::Duration;
+
+// Import Chrono features
+use chrono::{Local, NaiveDateTime};
+
+// Import tokio features
+use tokio::time::{sleep, Instant};
+
+// Define the longest real time to sleep before checking the clock again
+const MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// An internal enum to hold the source of the current time.
+///
+#[derive(Copy, Clone, Debug)]
+enum ClockSource {
+    /// A variant which follows the local time
+    Local,
+
+    /// A variant which follows the local time from the anchor, scaled by
+    /// the speed
+    Scaled {
+        anchor_time: NaiveDateTime, // the clock time at the anchor
+        anchor_instant: Instant,    // the real (or tokio) instant of the anchor
+        speed: f64,                 // the speed of the clock relative to real time
+    },
+}
+
+// Implement the clock source features
+impl ClockSource {
+    /// A method to return the current time from this source.
+    ///
+    fn now(&self) -> NaiveDateTime {
+        match self {
+            ClockSource::Local => Local::now().naive_local(),
+            ClockSource::Scaled {
+                anchor_time,
+                anchor_instant,
+                speed,
+            } => {
+                // Scale the time elapsed since the anchor
+                let elapsed = Instant::now().saturating_duration_since(*anchor_instant);
+                *anchor_time
+                    + chrono::Duration::from_std(elapsed.mul_f64(*speed))
+                        .unwrap_or_else(|_| chrono::Duration::zero())
+            }
+        }
+    }
+
+    /// A method to return a new source at the provided speed, anchored at
+    /// the current time of this source.
+    ///
+    fn with_speed(&self, speed: f64) -> ClockSource {
+        ClockSource::Scaled {
+            anchor_time: self.now(),
+            anchor_instant: Instant::now(),
+            speed,
+        }
+    }
+
+    /// A method to return the speed of this source.
+    ///
+    fn speed(&self) -> f64 {
+        match self {
+            ClockSource::Local => 1.0,
+            ClockSource::Scaled { speed, .. } => *speed,
+        }
+    }
+}
+
+// Define the clock for the whole program
+static CLOCK: RwLock<ClockSource> = RwLock::new(ClockSource::Local);
+
+// Define a virtual clock for a single test thread
+#[cfg(test)]
+thread_local! {
+    static TEST_CLOCK: std::cell::Cell<Option<ClockSource>> = const { std::cell::Cell::new(None) };
+}
+
+/// A struct to access the program clock. All show timing (the queue, the
+/// dmx fades, and the logs) should use this clock rather than reading the
+/// local time directly.
+///
+pub struct Clock;
+
+// Implement the clock features
+impl Clock {
+    /// An internal function to return the current clock source.
+    ///
+    fn source() -> ClockSource {
+        // Use the test clock, if set
+        #[cfg(test)]
+        if let Some(source) = TEST_CLOCK.with(|clock| clock.get()) {
+            return source;
+        }
+
+        // Otherwise, use the program clock
+        match CLOCK.read() {
+            Ok(source) => *source,
+            _ => ClockSource::Local, // inelegant failure handling
+        }
+    }
+
+    /// A function to return the current time of the clock.
+    ///
+    pub fn now() -> NaiveDateTime {
+        Clock::source().now()
+    }
+
+    /// A function to return the clock time elapsed since the provided time,
+    /// or zero if the time is in the future.
+    ///
+    pub fn elapsed(since: NaiveDateTime) -> Duration {
+        Clock::now()
+            .signed_duration_since(since)
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    /// A function to return the current speed of the clock relative to real
+    /// time.
+    ///
+    pub fn speed() -> f64 {
+        Clock::source().speed()
+    }
+
+    /// A function to change the speed of the clock relative to real time.
+    /// A speed of one returns the clock to the local time (so any time gained
+    /// while accelerated is dropped). A virtual test clock instead continues
+    /// from the current clock time.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the speed is not a positive
+    /// number.
+    ///
+    pub fn set_speed(speed: f64) -> Result<(), String> {
+        // Check that the speed is valid
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(format!("Invalid Clock Speed: {}", speed));
+        }
+
+        // Use the test clock, if set
+        #[cfg(test)]
+        if let Some(source) = TEST_CLOCK.with(|clock| clock.get()) {
+            TEST_CLOCK.with(|clock| clock.set(Some(source.with_speed(speed))));
+            return Ok(());
+        }
+
+        // Replace the program clock (returning to the local time at normal speed)
+        if let Ok(mut clock) = CLOCK.write() {
+            *clock = match speed == 1.0 {
+                true => ClockSource::Local,
+                false => clock.with_speed(speed),
+            };
+        }
+        Ok(())
+    }
+
+    /// A function to sleep for the provided clock duration. If the speed of
+    /// the clock changes while sleeping, the remaining time is adjusted
+    /// within one second (of real time).
+    ///
+    pub async fn sleep(duration: Duration) {
+        // Calculate the clock time to wake up (or sleep forever if out of range)
+        let end = match chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| Clock::now().checked_add_signed(duration))
+        {
+            Some(end) => end,
+            None => return std::future::pending().await,
+        };
+
+        // Sleep until the end time has passed
+        loop {
+            // Calculate the remaining clock time
+            let remaining = match (end - Clock::now()).to_std() {
+                Ok(remaining) if remaining > Duration::from_secs(0) => remaining,
+                _ => break,
+            };
+
+            // Sleep for the equivalent real time (checking the clock at least every second)
+            sleep(remaining.div_f64(Clock::speed()).min(MAX_SLEEP)).await;
+        }
+    }
+
+    /// A function to replace the clock for the current test thread with a
+    /// virtual clock at the provided speed. The virtual clock follows the
+    /// tokio time, so it can be paused and advanced manually (or
+    /// automatically) by the test runtime.
+    ///
+    #[cfg(test)]
+    pub fn set_virtual(speed: f64) {
+        TEST_CLOCK.with(|clock| {
+            clock.set(Some(ClockSource::Scaled {
+                anchor_time: Local::now().naive_local(),
+                anchor_instant: Instant::now(),
+                speed,
+            }))
+        });
+    }
+}
+
+// Tests of the clock module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test the virtual clock with the paused tokio time
+    #[tokio::test(start_paused = true)]
+    async fn virtual_clock() {
+        // Replace the clock with a virtual clock at four times the speed
+        Clock::set_virtual(4.0);
+        let start = Clock::now();
+
+        // Advance the tokio time by fifteen minutes
+        tokio::time::advance(Duration::from_secs(15 * 60)).await;
+        assert_eq!(Clock::elapsed(start), Duration::from_secs(60 * 60));
+
+        // Sleep for an hour of clock time (automatically advanced)
+        let start = Clock::now();
+        let real_start = Instant::now();
+        Clock::sleep(Duration::from_secs(60 * 60)).await;
+        assert!(Clock::elapsed(start) >= Duration::from_secs(60 * 60));
+        assert!(real_start.elapsed() <= Duration::from_secs(15 * 60 + 1));
+    }
+
+    // Test that the virtual clock keeps the accelerated time at normal speed
+    #[tokio::test(start_paused = true)]
+    async fn change_speed() {
+        // Replace the clock with a virtual clock and accelerate it
+        Clock::set_virtual(1.0);
+        Clock::set_speed(4.0).unwrap();
+        let start = Clock::now();
+
+        // Advance the tokio time by one minute and return to normal speed
+        tokio::time::advance(Duration::from_secs(60)).await;
+        let accelerated = Clock::now();
+        assert_eq!(accelerated - start, chrono::Duration::minutes(4));
+        Clock::set_speed(1.0).unwrap();
+        assert!(Clock::now() >= accelerated);
+        assert_eq!(Clock::speed(), 1.0);
+
+        // Check that the clock continues at normal speed from that time
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(Clock::now() - accelerated, chrono::Duration::minutes(1));
+        assert!(Clock::set_speed(0.0).is_err());
+    }
+}
@@ -49,6 +49,48 @@ impl fmt::Display for Identifier {
     }
 }
 
+/// An enum to specify where the backup of the game state is stored.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupStorage {
+    /// A variant to store the backup on a Redis server
+    Redis {
+        location: String, // the location of the Redis server
+    },
+
+    /// A variant to store the backup in a local, append-only file
+    File {
+        path: PathBuf, // the location of the backup file
+    },
+
+    /// A variant to keep the backup in memory (the backup is lost if the
+    /// program stops, so this is only useful for testing)
+    Memory,
+}
+
+/// An enum to specify the role of this instance in a failover pair.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailoverRole {
+    /// A variant for the instance which drives the system connections and
+    /// writes the backup
+    Primary,
+
+    /// A variant for the instance which mirrors the backup of the primary and
+    /// takes over the system connections if the primary stops
+    Secondary,
+}
+
+/// A structure to specify hot-standby failover between two instances which
+/// share the same backup storage (and the same identifier and prefix).
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failover {
+    pub role: FailoverRole, // the role of this instance
+    pub heartbeat: Duration, // the time between heartbeats of the primary (and checks by the secondary)
+    pub timeout: Duration, // the time without a heartbeat before the secondary takes over
+}
+
 /// An enum to specify the type of system connection.
 ///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -76,12 +118,33 @@ pub enum ConnectionType {
         recv_path: PathBuf, // the location to connect the ZMQ receiver
     },
 
-    /// A variant to connect with a DMX serial port. This connection type only allows
-    /// messages to be the sent.
+    /// A variant to connect with a DMX serial port. The serial port is
+    /// universe 1. This connection type only allows messages to be the sent.
     DmxSerial {
         path: PathBuf,              // the location of the serial port
         all_stop_dmx: Vec<DmxFade>, // a vector of dmx fades for all stop
-        dmx_map: DmxMap,            // the map of event ids to dmx fades
+        dmx_map: DmxMap,            // the map of event ids to dmx fades and looks
+    },
+
+    /// A variant to send DMX over the network as Art-Net (ArtDmx) packets.
+    /// Channels continue across the listed universes in order. This
+    /// connection type only allows messages to be sent.
+    ArtNet {
+        target: String,             // the address of the node or the broadcast address (e.g. "2.255.255.255")
+        universes: Vec<u16>,        // the Art-Net port addresses to send, in order
+        all_stop_dmx: Vec<DmxFade>, // a vector of dmx fades for all stop
+        dmx_map: DmxMap,            // the map of event ids to dmx fades and looks
+    },
+
+    /// A variant to send DMX over the network as sACN (E1.31) packets.
+    /// Channels continue across the listed universes in order. This
+    /// connection type only allows messages to be sent.
+    Sacn {
+        target: Option<String>,     // the unicast address of the receiver (multicast if None)
+        source_name: String,        // the source name reported to the receivers
+        universes: Vec<u16>,        // the sACN universes to send, in order
+        all_stop_dmx: Vec<DmxFade>, // a vector of dmx fades for all stop
+        dmx_map: DmxMap,            // the map of event ids to dmx fades and looks
     },
 
     /// A variant to play media on the local screen. This connection type only allows
@@ -93,6 +156,60 @@ pub enum ConnectionType {
         window_map: WindowMap,            // the map of window numbers to window properties
         apollo_params: ApolloParams,      // the parameters for Apollo media player, if specified
     },
+
+    /// A variant to send and receive Open Sound Control messages over UDP.
+    /// Received messages are echoed to the other connections, but not back
+    /// to this connection.
+    Osc {
+        bind_address: String, // the local address to receive messages (e.g. "0.0.0.0:9000")
+        send_address: String, // the remote address to send messages (e.g. "192.168.1.10:53000")
+        address_map: OscMap,  // the map of event ids to OSC addresses
+    },
+
+    /// A variant to publish and subscribe to topics on an MQTT broker.
+    /// Received messages are echoed to the other connections, but not back
+    /// to this connection.
+    Mqtt {
+        broker: String,       // the url of the broker (e.g. "mqtt://localhost:1883")
+        client_id: String,    // the client id for this connection
+        topic_prefix: String, // the prefix for every topic (e.g. "minerva/")
+        qos: u8,              // the quality of service (0 or 1)
+        topic_map: MqttMap,   // the map of event ids to topics and payloads
+    },
+
+    /// A variant to exchange newline-delimited events over TCP, either as a
+    /// client of a remote server or as a server for remote clients. Events
+    /// received by the server are relayed to the other clients.
+    Tcp {
+        address: String,    // the address to connect to or listen on (e.g. "0.0.0.0:7000")
+        is_server: bool,    // if true, listen for clients rather than connecting to a server
+        format: LineFormat, // the format of each line
+    },
+
+    /// A variant to exchange newline-delimited events over UDP.
+    Udp {
+        bind_address: String, // the local address to receive events (e.g. "0.0.0.0:7000")
+        send_address: String, // the remote address to send events (e.g. "192.168.1.255:7000")
+        format: LineFormat,   // the format of each line
+    },
+
+    /// A variant to send HTTP requests (webhooks) when events are broadcast.
+    /// Failed requests are retried, doubling the delay after each attempt (up
+    /// to one minute). Each request is sent and retried separately.
+    /// This connection type only allows messages to be sent.
+    Webhook {
+        request_map: WebhookMap, // the map of event ids to requests
+        retries: u32,            // the number of times to retry a failed request
+        backoff: Duration,       // the delay before the first retry
+    },
+
+    /// A variant to exchange MIDI messages with a raw MIDI device (e.g.
+    /// "/dev/snd/midiC1D0"). Either the input or the output may be omitted.
+    Midi {
+        input: Option<String>,  // the path of the device to read messages from
+        output: Option<String>, // the path of the device to write messages to
+        midi_map: MidiMap,      // the map of event ids to MIDI messages
+    },
 }
 
 /// A type to contain any number of connection types
@@ -104,18 +221,200 @@ pub type ConnectionSet = Vec<ConnectionType>;
 /// # Note
 ///
 /// Assumes the channels are one-indexed (the DMX standard) rather than
-/// zero-indexed.
+/// zero-indexed. If no universe is specified, channels above 512 continue
+/// into the following universe of the connection.
 ///
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DmxFade {
+    pub universe: Option<u16>,      // the universe of the channel (the first universe of the connection if None)
     pub channel: u32,               // the dmx channel to fade
     pub value: u8,                  // the final value at the end of the fade
     pub duration: Option<Duration>, // the duration of the fade (None if instantaneous)
+    pub curve: Option<FadeCurve>,   // the shape of the fade (linear if None)
 }
 
-/// A type to store a hashmap of event ids and DMX fades
+/// An enum to specify the shape of a DMX fade over its duration
 ///
-pub type DmxMap = FnvHashMap<ItemId, DmxFade>;
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FadeCurve {
+    /// A variant which changes at a constant rate
+    Linear,
+
+    /// A variant which starts slowly and finishes quickly
+    EaseIn,
+
+    /// A variant which starts quickly and finishes slowly
+    EaseOut,
+
+    /// A variant which starts and finishes slowly
+    EaseInOut,
+
+    /// A variant which changes exponentially, to appear smooth at low levels
+    Exponential,
+
+    /// A variant which holds the starting value until the end of the fade
+    SnapAtEnd,
+}
+
+// Implement key features of the fade curve
+impl FadeCurve {
+    /// A method to convert the elapsed portion of the fade (from zero to one)
+    /// into the portion of the change in value (from zero to one)
+    ///
+    pub fn apply(&self, progress: f64) -> f64 {
+        // Limit the progress to the valid range
+        let progress = progress.clamp(0.0, 1.0);
+
+        // Calculate the portion for each curve
+        match self {
+            FadeCurve::Linear => progress,
+            FadeCurve::EaseIn => progress * progress,
+            FadeCurve::EaseOut => 1.0 - (1.0 - progress) * (1.0 - progress),
+            FadeCurve::EaseInOut => {
+                if progress < 0.5 {
+                    2.0 * progress * progress
+                } else {
+                    1.0 - 2.0 * (1.0 - progress) * (1.0 - progress)
+                }
+            }
+            FadeCurve::Exponential => (2_f64.powf(10.0 * progress) - 1.0) / 1023.0,
+            FadeCurve::SnapAtEnd => {
+                if progress < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// A struct to define a lighting look: several DMX fades which are started
+/// together by a single event
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmxLook {
+    pub fades: Vec<DmxFade>,         // the fades which compose the look
+    pub crossfade: Option<Duration>, // if specified, channels of the previous look which are not in this look fade to zero over this duration
+}
+
+/// An enum to hold either a single DMX fade or a complete look. Single fades
+/// are written exactly as before, so existing configurations remain valid.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DmxCue {
+    /// A variant for a single fade of one channel
+    Fade(DmxFade),
+
+    /// A variant for a look across many channels
+    Look(DmxLook),
+}
+
+/// A type to store a hashmap of event ids and DMX cues
+///
+pub type DmxMap = FnvHashMap<ItemId, DmxCue>;
+
+/// A type to store a hashmap of event ids and OSC addresses. Incoming
+/// address patterns are matched against these addresses.
+///
+pub type OscMap = FnvHashMap<ItemId, String>;
+
+/// A struct to define the MQTT topic of an event and how the payload
+/// corresponds to the event data
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MqttTopic {
+    pub topic: String,        // the topic of the event, after the topic prefix
+    pub payload: MqttPayload, // the format of the payload
+}
+
+/// An enum to specify how an MQTT payload is converted to and from the
+/// event data
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MqttPayload {
+    /// A variant where the payload is empty and the data is zero
+    Empty,
+
+    /// A variant where the payload is data1 and data2, separated by a space
+    Numbers,
+
+    /// A variant where the payload is a JSON object with the data in the
+    /// named fields (the data is zero for a field that is not specified)
+    Json {
+        data1: Option<String>,
+        data2: Option<String>,
+    },
+}
+
+/// A type to store a hashmap of event ids and MQTT topics
+///
+pub type MqttMap = FnvHashMap<ItemId, MqttTopic>;
+
+/// An enum to specify the format of each line for the TCP and UDP
+/// connections
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineFormat {
+    /// A variant for lines of text with the fields separated by spaces
+    /// (e.g. "12 0 5")
+    Text,
+
+    /// A variant for lines of JSON (e.g. {"id": 12, "data1": 0, "data2": 5})
+    Json,
+}
+
+/// A struct to define an HTTP request for an event. The url, header values,
+/// and body may contain placeholders for the event, scene, and statuses.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebhookRequest {
+    pub method: String,                      // the method of the request (e.g. "POST")
+    pub url: String,                         // the url of the request
+    pub headers: FnvHashMap<String, String>, // the headers of the request
+    pub body: Option<serde_json::Value>,     // the template of the JSON body, if any
+}
+
+/// A type to store a hashmap of event ids and webhook requests
+///
+pub type WebhookMap = FnvHashMap<ItemId, WebhookRequest>;
+
+/// An enum to define a MIDI message for an event. Channels are numbered from
+/// 1 to 16.
+///
+/// # Note
+///
+/// If the velocity or value is omitted, incoming messages match any value and
+/// the value is passed as data1. Outgoing messages use data1 as the value
+/// instead (or 127 if data1 is out of range).
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MidiMessage {
+    /// A variant for a note on message
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: Option<u8>,
+    },
+
+    /// A variant for a note off message
+    NoteOff { channel: u8, note: u8 },
+
+    /// A variant for a control change message
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: Option<u8>,
+    },
+
+    /// A variant for a program change message
+    ProgramChange { channel: u8, program: u8 },
+}
+
+/// A type to store a hashmap of event ids and MIDI messages
+///
+pub type MidiMap = FnvHashMap<ItemId, MidiMessage>;
 
 /// A struct to define a single media track to play
 ///
@@ -128,7 +427,7 @@ pub type DmxMap = FnvHashMap<ItemId, DmxFade>;
 /// media when this media completes. This takes priority over the channel loop
 /// media field.
 ///
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MediaCue {
     pub uri: String,                // the location of the video or audio file to play
     pub channel: u32, // the channel of the video or audio. New media sent to the same channel will replace the old media, starting instantly
@@ -164,6 +463,17 @@ impl MediaCue {
 ///
 pub type MediaMap = FnvHashMap<ItemId, MediaCue>;
 
+/// A struct to hold the current state of the outputs of the system
+/// connections, so that the outputs can be restored after a restart.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputState {
+    #[serde(default)]
+    pub dmx_levels: Vec<DmxFade>, // the final level of each dmx channel which has been set
+    #[serde(default)]
+    pub media_cues: Vec<MediaCue>, // the most recent media cue of each media channel
+}
+
 /// A struct to hold the dimensions of a video Frame
 ///
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -24,11 +24,27 @@ use crate::definitions::*;
 use std::time::Duration;
 
 // Import Chrono features
-use chrono::{NaiveDateTime, Local};
+use chrono::NaiveDateTime;
 
 // Import FNV HashMap
 use fnv::FnvHashMap;
 
+/// An enum to specify how a cued event is handled when an event with the
+/// same id is already in the queue.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// A variant to keep the existing event and add the new one (the default)
+    #[default]
+    KeepBoth,
+
+    /// A variant to cancel the existing event and add the new one
+    Replace,
+
+    /// A variant to keep the existing event and ignore the new one
+    Ignore,
+}
+
 /// A small struct that holds and event id and the corresponding delay until the
 /// event should be triggered. This delay is an Option<delay> to allow the
 /// possibility for events to trigger immediately.
@@ -37,6 +53,10 @@ use fnv::FnvHashMap;
 pub struct EventDelay {
     delay: Option<Duration>, // delay between now and the time for the event
     event_id: ItemId,        // id of the event to launch
+    #[serde(default)]
+    priority: u8, // the priority of the event (higher priorities trigger first at the same time)
+    #[serde(default)]
+    conflict: ConflictPolicy, // the handling of an event with the same id already in the queue
 }
 
 // Implement the event delay functions
@@ -45,7 +65,29 @@ impl EventDelay {
     /// ItemId.
     ///
     pub fn new(delay: Option<Duration>, event_id: ItemId) -> EventDelay {
-        EventDelay { delay, event_id }
+        EventDelay {
+            delay,
+            event_id,
+            priority: 0,
+            conflict: ConflictPolicy::default(),
+        }
+    }
+
+    /// A function to return a new EventDelay with the provided priority and
+    /// conflict policy.
+    ///
+    pub fn with_policy(
+        delay: Option<Duration>,
+        event_id: ItemId,
+        priority: u8,
+        conflict: ConflictPolicy,
+    ) -> EventDelay {
+        EventDelay {
+            delay,
+            event_id,
+            priority,
+            conflict,
+        }
     }
 
     /// A method to return a copy of the event id
@@ -60,6 +102,28 @@ impl EventDelay {
     pub fn delay(&self) -> Option<Duration> {
         self.delay.clone()
     }
+
+    /// A method to return the priority of the event.
+    ///
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// A method to return the conflict policy of the event.
+    ///
+    pub fn conflict(&self) -> ConflictPolicy {
+        self.conflict
+    }
+}
+
+/// A struct to describe the remaining repetitions of a repeating event in
+/// the queue.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Repetition {
+    pub interval: Duration,              // the time between each occurrence
+    pub count: Option<u32>,              // the number of occurrences after this one, if limited
+    pub end_time: Option<NaiveDateTime>, // the time after which there are no more occurrences
 }
 
 /// A struct to allow easier manipulation of queued events.
@@ -69,6 +133,12 @@ pub struct ComingEvent {
     pub start_time: NaiveDateTime,   // the start time of the event
     pub delay: Duration,        // delay between the start time and the trigger time for the event
     pub event_id: ItemId,       // id of the event to launch
+    pub paused: Option<Duration>, // the remaining time, frozen while the queue or event is paused
+    pub is_held: bool,          // a flag to indicate that the event is held individually
+    pub repeat: Option<Repetition>, // the repetitions of the event, if it repeats
+    pub schedule: Option<ScheduleRule>, // the schedule of the event, if it is scheduled
+    pub priority: u8,           // the priority of the event among events at the same time
+    pub conflict: ConflictPolicy, // the handling of an event with the same id, applied when loaded
 }
 
 // Implement the Coming Event features
@@ -77,13 +147,136 @@ impl ComingEvent {
     /// ItemId.
     ///
     pub fn new(delay: Duration, event_id: ItemId) -> ComingEvent {
+        ComingEvent::starting_at(Clock::now(), delay, event_id)
+    }
+
+    /// A function to return a new ComingEvent which starts at the provided
+    /// time. Events started at the same time share the same start time, so
+    /// the priority of the events is applied among them.
+    ///
+    pub fn starting_at(start_time: NaiveDateTime, delay: Duration, event_id: ItemId) -> ComingEvent {
         ComingEvent {
-            start_time: Local::now().naive_local(),
+            start_time,
             delay,
             event_id,
+            paused: None,
+            is_held: false,
+            repeat: None,
+            schedule: None,
+            priority: 0,
+            conflict: ConflictPolicy::KeepBoth,
         }
     }
 
+    /// A function to return a new scheduled ComingEvent for the next time
+    /// that matches the schedule rule after the provided time. Returns None
+    /// if the event should not be triggered again.
+    ///
+    pub fn scheduled(
+        event_id: ItemId,
+        rule: ScheduleRule,
+        after: NaiveDateTime,
+    ) -> Option<ComingEvent> {
+        // Find the next time for the event
+        let next_time = rule.next_after(after)?;
+
+        // Calculate the delay from now (triggering immediately if already passed)
+        let now = Clock::now();
+        let delay = (next_time - now).to_std().unwrap_or_default();
+        Some(ComingEvent {
+            start_time: now,
+            delay,
+            event_id,
+            paused: None,
+            is_held: false,
+            repeat: None,
+            schedule: Some(rule),
+            priority: 0,
+            conflict: ConflictPolicy::KeepBoth,
+        })
+    }
+
+    /// A function to return a new repeating ComingEvent. The first
+    /// occurrence is one interval from now. Returns None if there are no
+    /// occurrences.
+    ///
+    pub fn repeating(
+        event_id: ItemId,
+        interval: Duration,
+        count: Option<u32>,
+        end_after: Option<Duration>,
+    ) -> Option<ComingEvent> {
+        // Calculate the end time, if specified
+        let now = Clock::now();
+        let end_time = match end_after {
+            Some(duration) => Some(now + chrono::Duration::from_std(duration).ok()?),
+            None => None,
+        };
+
+        // Create a stand-in for the previous occurrence and find the first one
+        let mut previous = ComingEvent::new(Duration::from_secs(0), event_id);
+        previous.repeat = Some(Repetition {
+            interval,
+            count,
+            end_time,
+        });
+        previous.repeat_after(now)
+    }
+
+    /// A method to return the next occurrence of a repeating event, one
+    /// interval after the provided time. Returns None if the event does not
+    /// repeat or there are no more occurrences.
+    ///
+    pub fn repeat_after(&self, time: NaiveDateTime) -> Option<ComingEvent> {
+        // Check for remaining occurrences
+        let repeat = self.repeat?;
+        let count = match repeat.count {
+            Some(0) => return None,
+            Some(count) => Some(count - 1),
+            None => None,
+        };
+
+        // Check that the next occurrence is before the end time
+        let next_time = time + chrono::Duration::from_std(repeat.interval).ok()?;
+        if let Some(end_time) = repeat.end_time {
+            if next_time > end_time {
+                return None;
+            }
+        }
+
+        // Return the next occurrence
+        Some(ComingEvent {
+            start_time: time,
+            delay: repeat.interval,
+            event_id: self.event_id,
+            paused: None,
+            is_held: false,
+            repeat: Some(Repetition { count, ..repeat }),
+            schedule: None,
+            priority: self.priority,
+            conflict: ConflictPolicy::KeepBoth,
+        })
+    }
+
+    /// A method to return the next occurrence of a repeating or scheduled
+    /// event after the provided time. Returns None if there are no more
+    /// occurrences.
+    ///
+    pub fn next_occurrence(&self, time: NaiveDateTime) -> Option<ComingEvent> {
+        match self.schedule {
+            Some(rule) => ComingEvent::scheduled(self.event_id, rule, time),
+            None => self.repeat_after(time),
+        }
+    }
+
+    /// A method to return the time when the event is scheduled to trigger.
+    ///
+    pub fn trigger_time(&self) -> NaiveDateTime {
+        // Default to the start time if the delay is too large
+        self.start_time
+            + chrono::Duration::from_std(self.delay).unwrap_or_else(|_| chrono::Duration::zero())
+    }
+
     /// A method to return a copy of the event id.
     ///
     pub fn id(&self) -> ItemId {
@@ -94,8 +287,13 @@ impl ComingEvent {
     /// triggers. Returns None if the event should already have occured.
     ///
     pub fn remaining(&self) -> Option<Duration> {
+        // Return the frozen time if the event is paused
+        if let Some(remaining) = self.paused {
+            return Some(remaining);
+        }
+
         // Calculate the time since the event was queued
-        let elapsed = Local::now().naive_local().signed_duration_since(self.start_time);
+        let elapsed = Clock::now().signed_duration_since(self.start_time);
         
         // Compare the durations, or default to playing the event immediately
         match elapsed.to_std().ok() {
@@ -125,6 +323,10 @@ pub struct UpcomingEvent {
     pub event: ItemPair,            // id and description of the event to launch
     pub start_time: NaiveDateTime,  // the original start time of the event
     pub delay: Duration,            // delay between now and the time for the event
+    pub paused: Option<Duration>,   // the remaining time, frozen while the queue or event is paused
+    pub is_held: bool,              // a flag to indicate that the event is held individually
+    pub repeat: Option<Repetition>, // the repetitions of the event, if it repeats
+    pub schedule: Option<ScheduleRule>, // the schedule of the event, if it is scheduled
 }
 
 /// An enum with the types of data available to be saved and sent
@@ -149,6 +351,11 @@ pub enum DataType {
 
     /// A variant for a string collected from the user
     UserString,
+
+    /// A variant for the current value of a variable status
+    VariableValue {
+        status_id: ItemId, // the variable of interest
+    },
 }
 
 /// An enum with various action options for each event.
@@ -170,9 +377,25 @@ pub enum EventAction {
         new_state: ItemId,
     },
 
+    /// A variant used to change the value of the target variable status.
+    ModifyVariable {
+        status_id: ItemId,
+        operation: VariableOperation,
+    },
+
     /// A variant indicating a complete change in scene.
     NewScene { new_scene: ItemId },
 
+    /// A variant that adds a repeating event to the queue. The event is
+    /// triggered every interval (starting one interval from now) until the
+    /// count or the end time is reached, or the event is cancelled.
+    RepeatEvent {
+        event: ItemId,
+        interval: Duration,
+        count: Option<u32>,
+        end_after: Option<Duration>,
+    },
+
     /// A variant which contains a vector of data to save in the current game
     /// logging file.
     SaveData { data: DataType },
@@ -187,6 +410,14 @@ pub enum EventAction {
     /// A variant which contains a type of data to include with the event
     /// when broadcast to the system
     SendData { data: DataType },
+
+    /// A variant which evaluates a condition over the current statuses and
+    /// cues the true event if it holds, or the false event otherwise.
+    ConditionalEvent {
+        condition: StatusCondition,
+        true_event: Option<ItemId>,
+        false_event: Option<ItemId>,
+    },
 }
 
 /// An web-safe (JSON readable) enum with various action options for each event.
@@ -208,9 +439,25 @@ pub enum WebEventAction {
         new_state: ItemId,
     },
 
+    /// A variant used to change the value of the target variable status.
+    ModifyVariable {
+        status_id: ItemId,
+        operation: VariableOperation,
+    },
+
     /// A variant indicating a complete change in scene.
     NewScene { new_scene: ItemId },
 
+    /// A variant that adds a repeating event to the queue. The event is
+    /// triggered every interval (starting one interval from now) until the
+    /// count or the end time is reached, or the event is cancelled.
+    RepeatEvent {
+        event: ItemId,
+        interval: Duration,
+        count: Option<u32>,
+        end_after: Option<Duration>,
+    },
+
     /// A variant which contains a vector of data to save in the current game
     /// logging file.
     SaveData { data: DataType },
@@ -225,6 +472,14 @@ pub enum WebEventAction {
     /// A variant which contains a type of data to include with the event
     /// when broadcast to the system
     SendData { data: DataType },
+
+    /// A variant which evaluates a condition over the current statuses and
+    /// cues the true event if it holds, or the false event otherwise.
+    ConditionalEvent {
+        condition: StatusCondition,
+        true_event: Option<ItemId>,
+        false_event: Option<ItemId>,
+    },
 }
 
 // Implement conversions to and from WebEventAction
@@ -247,9 +502,20 @@ impl From<EventAction> for WebEventAction {
             EventAction::CancelEvent { event } => WebEventAction::CancelEvent { event },
             EventAction::CueEvent { event } => WebEventAction::CueEvent { event },
             EventAction::ModifyStatus { status_id, new_state } => WebEventAction::ModifyStatus { status_id, new_state },
+            EventAction::ModifyVariable { status_id, operation } => WebEventAction::ModifyVariable { status_id, operation },
             EventAction::NewScene { new_scene } => WebEventAction::NewScene { new_scene },
+            EventAction::RepeatEvent { event, interval, count, end_after } => WebEventAction::RepeatEvent { event, interval, count, end_after },
             EventAction::SaveData { data } => WebEventAction::SaveData { data },
             EventAction::SendData { data } => WebEventAction::SendData { data },
+            EventAction::ConditionalEvent {
+                condition,
+                true_event,
+                false_event,
+            } => WebEventAction::ConditionalEvent {
+                condition,
+                true_event,
+                false_event,
+            },
         }
     }
 }
@@ -272,9 +538,20 @@ impl From<WebEventAction> for EventAction {
             WebEventAction::CancelEvent { event } => EventAction::CancelEvent { event },
             WebEventAction::CueEvent { event } => EventAction::CueEvent { event },
             WebEventAction::ModifyStatus { status_id, new_state } => EventAction::ModifyStatus { status_id, new_state },
+            WebEventAction::ModifyVariable { status_id, operation } => EventAction::ModifyVariable { status_id, operation },
             WebEventAction::NewScene { new_scene } => EventAction::NewScene { new_scene },
+            WebEventAction::RepeatEvent { event, interval, count, end_after } => EventAction::RepeatEvent { event, interval, count, end_after },
             WebEventAction::SaveData { data } => EventAction::SaveData { data },
             WebEventAction::SendData { data } => EventAction::SendData { data },
+            WebEventAction::ConditionalEvent {
+                condition,
+                true_event,
+                false_event,
+            } => EventAction::ConditionalEvent {
+                condition,
+                true_event,
+                false_event,
+            },
         }
     }
 }
@@ -316,5 +593,6 @@ impl From<WebEvent> for Event {
 
 // Reexport the event action type variants
 pub use self::EventAction::{
-    CancelEvent, CueEvent, ModifyStatus, NewScene, SaveData, SelectEvent, SendData,
+    CancelEvent, ConditionalEvent, CueEvent, ModifyStatus, ModifyVariable, NewScene, RepeatEvent,
+    SaveData, SelectEvent, SendData,
 };
@@ -21,6 +21,12 @@
 // Import crate definitions
 use crate::definitions::*;
 
+// Import standard library features
+use std::time::Duration;
+
+// Import Chrono features
+use chrono::NaiveDateTime;
+
 // Import Tokio and warp features
 use tokio::sync::mpsc;
 use warp::ws::Message;
@@ -55,6 +61,14 @@ pub enum InterfaceUpdate {
     #[serde(rename_all = "camelCase")]
     Notify { message: String },
 
+    /// A variant to ask the operator whether to restore the saved game from
+    /// before the program was restarted.
+    #[serde(rename_all = "camelCase")]
+    RestorePrompt {
+        saved: NaiveDateTime, // the time when the game was saved
+        scene: ItemPair,      // the current scene of the saved game
+    },
+
     /// A variant to update the available scenes and full status in the main
     /// program window.
     #[serde(rename_all = "camelCase")]
@@ -75,17 +89,30 @@ pub enum InterfaceUpdate {
     /// A variant to update the state of a partiular status.
     #[serde(rename_all = "camelCase")]
     UpdateStatus {
-        status_id: ItemPair, // the group to update
-        new_state: ItemPair, // the new state of the group
+        status_id: ItemPair,   // the group to update
+        new_state: ItemPair,   // the new state of the group
+        value: Option<i64>,    // the new value of the group, if it is a variable
+    },
+
+    /// A variant to update the remaining time of a timer status.
+    #[serde(rename_all = "camelCase")]
+    UpdateTimer {
+        status_id: ItemPair, // the timer to update
+        remaining: Duration, // the remaining time of the timer
+        is_running: bool,    // a flag to indicate that the timer is running
     },
 
     /// A variant indicating that the system notifications should be updated.
     #[serde(rename_all = "camelCase")]
     UpdateNotifications { notifications: Vec<Notification> },
 
-    /// A variant indicating that the event timeline should be updated.
+    /// A variant indicating that the event timeline should be updated, and
+    /// whether the timeline is paused.
     #[serde(rename_all = "camelCase")]
-    UpdateTimeline { events: Vec<UpcomingEvent> },
+    UpdateTimeline {
+        events: Vec<UpcomingEvent>,
+        is_paused: bool,
+    },
 }
 
 // Implement from<InterfaceUpdate> for Message)
@@ -41,13 +41,18 @@ pub enum InternalUpdate {
     BroadcastEvent(ItemId, Option<u32>),
 
     /// A variant that notifies the system of a change in the coming events
-    ComingEvents(Vec<ComingEvent>),
+    /// and whether the queue is paused
+    ComingEvents(Vec<ComingEvent>, bool),
 
     /// A variant that solicites a string of data from the user to send to the
     /// system. The string will be sent as a series of events with the same
     /// item id. TODO Make this more generic for other user input
     GetUserString(ItemId),
 
+    /// A variant that notifies the system of a change in the state of the
+    /// outputs of the system connections
+    OutputState(OutputState),
+
     /// A variant that processes a new event with the given item id. If the
     /// check_scene flag is not set, the system will not check if the event is
     /// listed in the current scene. If broadcast is set to true, the event
@@ -100,9 +105,9 @@ impl InternalSend {
 
     /// A method to send new coming events to the system
     ///
-    pub async fn send_coming_events(&self, coming_events: Vec<ComingEvent>) {
+    pub async fn send_coming_events(&self, coming_events: Vec<ComingEvent>, is_paused: bool) {
         self.internal_send
-            .send(InternalUpdate::ComingEvents(coming_events))
+            .send(InternalUpdate::ComingEvents(coming_events, is_paused))
             .await
             .unwrap_or(());
     }
@@ -187,7 +192,7 @@ pub enum LogUpdate {
     Save(String), // the data to save, formatted as a string
 
     /// A variant that notifies the rest of the system of the new state of the status
-    Status(ItemId, ItemId), // first field is the status id, second is the new state
+    Status(ItemId, ItemId, Option<i64>), // the status id, the new state, and the new value (for variables)
 
     /// A variant which can send any other type of update to the system.
     Update(String),
@@ -213,9 +218,12 @@ impl fmt::Display for LogUpdate {
             &LogUpdate::Save(ref data) => write!(f, "Got Data: {:?}", data),
 
             // If there is a status change, copy it
-            &LogUpdate::Status(ref status_id, ref state) => {
+            &LogUpdate::Status(ref status_id, ref state, None) => {
                 write!(f, "Status: {} Now {}", status_id, state)
             }
+            &LogUpdate::Status(ref status_id, ref state, Some(ref value)) => {
+                write!(f, "Status: {} Now {} ({})", status_id, state, value)
+            }
 
             // If there is a system update, simply write the string
             &LogUpdate::Update(ref update) => write!(f, "Update: {}", update),
@@ -226,6 +234,82 @@ impl fmt::Display for LogUpdate {
     }
 }
 
+// Implement key features of the log update
+impl LogUpdate {
+    /// A method to return the type of the log update.
+    ///
+    pub fn log_type(&self) -> LogType {
+        match self {
+            LogUpdate::Broadcast(..) => LogType::Broadcast,
+            LogUpdate::Current(..) => LogType::Current,
+            LogUpdate::Error(..) => LogType::Error,
+            LogUpdate::Save(..) => LogType::Save,
+            LogUpdate::Status(..) => LogType::Status,
+            LogUpdate::Update(..) => LogType::Update,
+            LogUpdate::Warning(..) => LogType::Warning,
+        }
+    }
+}
+
+/// An enum to specify the type of an entry in the structured game log (one
+/// type for each variant of the log update).
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogType {
+    Broadcast,
+    Current,
+    Error,
+    Save,
+    Status,
+    Update,
+    Warning,
+}
+
+/// A structure to hold the id and description of an item in the structured
+/// game log.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogItem {
+    pub id: u32,             // the id of the item
+    pub description: String, // the description of the item
+}
+
+// Implement conversion from an item pair
+impl From<ItemPair> for LogItem {
+    fn from(item_pair: ItemPair) -> Self {
+        LogItem {
+            id: item_pair.id(),
+            description: item_pair.description,
+        }
+    }
+}
+
+/// A structure to hold a single entry of the structured game log. Each entry
+/// is written to the log as one line of JSON, and only the fields which apply
+/// to the type of the entry are included.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub time: NaiveDateTime, // the time of the entry
+    #[serde(rename = "type")]
+    pub log_type: LogType, // the type of the entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>, // the message of an error, warning, or update, or the saved data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<LogItem>, // the event of the entry, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<LogItem>, // the status which changed, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<LogItem>, // the new state of the status, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<i64>, // the new value of the status, if it is a variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene: Option<LogItem>, // the current scene, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<u32>, // the data broadcast with the event, if any
+}
+
 /// A macro that allows the user to quickly and easily send status updates over
 /// the update line to the rest of the system.
 ///
@@ -312,7 +396,16 @@ macro_rules! log {
         use crate::definitions::LogUpdate;
 
         // Send an update to the mpsc line
-        $line.send_update(LogUpdate::Status($group_id, $status)).await;
+        $line.send_update(LogUpdate::Status($group_id, $status, None)).await;
+    });
+
+    // Take a mpsc line and status type of event update with a new value
+    (status $line:expr => $group_id:expr, $status:expr, $value:expr) => ({
+        // Import necessary features
+        use crate::definitions::LogUpdate;
+
+        // Send an update to the mpsc line
+        $line.send_update(LogUpdate::Status($group_id, $status, Some($value))).await;
     });
 
     // Take a mpsc line and save type of event update
@@ -23,6 +23,7 @@
 mod test;
 
 // Define submodules
+mod clock;
 mod connections;
 #[macro_use]
 mod event;
@@ -32,11 +33,13 @@ mod item;
 #[macro_use]
 mod log;
 mod scene;
+mod schedule;
 mod status;
 mod style;
 mod system;
 
 // Reexport all the definitions from the submodules
+pub use self::clock::*;
 pub use self::connections::*;
 pub use self::event::*;
 pub use self::index::*;
@@ -44,6 +47,7 @@ pub use self::interface::*;
 pub use self::item::*;
 pub use self::log::*;
 pub use self::scene::*;
+pub use self::schedule::*;
 pub use self::status::*;
 pub use self::style::*;
 pub use self::system::*;
@@ -0,0 +1,364 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the scheduled event definitions, which trigger
+//! events at absolute local times or according to cron-like rules.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::convert::TryFrom;
+use std::fmt;
+
+// Import Chrono features
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+// Define the number of days to search for the next match of a cron rule
+const SEARCH_DAYS: i64 = 366 * 8; // long enough to find any leap day
+
+// Define the names for months and days of the week (as used in cron rules)
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A struct to hold a cron-like rule. The rule follows the usual five
+/// fields (minute, hour, day of the month, month, day of the week), each of
+/// which is a comma-separated list of values, ranges (a-b), or wildcards (*),
+/// optionally with a step (*/15 or 1-10/2). Months and days of the week may
+/// also be specified by their three letter names (jan, fri).
+///
+/// As with cron, if both the day of the month and the day of the week are
+/// restricted, a day matches if either field matches.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronRule {
+    minutes: u64,                // a bit mask of the valid minutes (0-59)
+    hours: u64,                  // a bit mask of the valid hours (0-23)
+    days: u64,                   // a bit mask of the valid days of the month (1-31)
+    months: u64,                 // a bit mask of the valid months (1-12)
+    weekdays: u64,               // a bit mask of the valid days of the week (0-6, Sunday is 0)
+    is_day_restricted: bool,     // a flag to indicate that the day of the month was specified
+    is_weekday_restricted: bool, // a flag to indicate that the day of the week was specified
+}
+
+// Implement the cron rule features
+impl CronRule {
+    /// A function to parse one field of a cron rule into a bit mask. Also
+    /// returns whether the field was restricted (i.e. not a wildcard).
+    ///
+    fn parse_field(
+        field: &str,
+        min: u32,
+        max: u32,
+        names: &[&str],
+    ) -> Result<(u64, bool), String> {
+        // Parse each element in the list
+        let mut mask = 0;
+        for element in field.split(',') {
+            // Separate the step, if specified
+            let (range, step) = match element.split_once('/') {
+                Some((range, step)) => match step.parse::<u32>() {
+                    Ok(step) if step > 0 => (range, step),
+                    _ => return Err(format!("Invalid step: {}", element)),
+                },
+                None => (element, 1),
+            };
+
+            // Parse the start and end of the range
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else {
+                match range.split_once('-') {
+                    Some((start, end)) => (
+                        CronRule::parse_value(start, min, names)?,
+                        CronRule::parse_value(end, min, names)?,
+                    ),
+                    // A single value with a step continues to the maximum
+                    None => {
+                        let start = CronRule::parse_value(range, min, names)?;
+                        (start, if element.contains('/') { max } else { start })
+                    }
+                }
+            };
+
+            // Check that the range is valid
+            if start < min || end > max || start > end {
+                return Err(format!("Value out of range: {}", element));
+            }
+
+            // Add each value in the range to the mask
+            for value in (start..=end).step_by(step as usize) {
+                mask |= 1 << value;
+            }
+        }
+
+        // Return the mask and whether it was restricted
+        Ok((mask, !field.starts_with('*')))
+    }
+
+    /// A function to parse a single value or name of a cron field.
+    ///
+    fn parse_value(value: &str, min: u32, names: &[&str]) -> Result<u32, String> {
+        // Try to parse the value as a number
+        if let Ok(number) = value.parse::<u32>() {
+            return Ok(number);
+        }
+
+        // Otherwise, try to match the value with a name
+        let lowercase = value.to_lowercase();
+        match names.iter().position(|name| *name == lowercase) {
+            Some(index) => Ok(index as u32 + min),
+            None => Err(format!("Invalid value: {}", value)),
+        }
+    }
+
+    /// A function to compose a bit mask back into a cron field, collapsing
+    /// consecutive values into ranges.
+    ///
+    fn compose_field(mask: u64, min: u32, max: u32, is_restricted: bool) -> String {
+        // Use a wildcard if the field is not restricted
+        if !is_restricted {
+            return "*".to_string();
+        }
+
+        // Collect the ranges of consecutive values
+        let mut elements = Vec::new();
+        let mut value = min;
+        while value <= max {
+            // Skip values that are not in the mask
+            if mask & (1 << value) == 0 {
+                value += 1;
+                continue;
+            }
+
+            // Find the end of this range
+            let start = value;
+            while value < max && mask & (1 << (value + 1)) != 0 {
+                value += 1;
+            }
+
+            // Add the value or range
+            if start == value {
+                elements.push(format!("{}", start));
+            } else {
+                elements.push(format!("{}-{}", start, value));
+            }
+            value += 1;
+        }
+        elements.join(",")
+    }
+
+    /// A method to check if the rule matches the provided day.
+    ///
+    fn matches_day(&self, day: &NaiveDate) -> bool {
+        // Check the month first
+        if self.months & (1 << day.month()) == 0 {
+            return false;
+        }
+
+        // Check the day of the month and the day of the week
+        let is_day = self.days & (1 << day.day()) != 0;
+        let is_weekday = self.weekdays & (1 << day.weekday().num_days_from_sunday()) != 0;
+
+        // If both are restricted, either may match (as with cron)
+        if self.is_day_restricted && self.is_weekday_restricted {
+            is_day || is_weekday
+        } else {
+            is_day && is_weekday
+        }
+    }
+
+    /// A method to return the next time that matches the rule, strictly after
+    /// the provided time. Returns None if no match could be found (for example,
+    /// for the 31st of February).
+    ///
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        // Start at the beginning of the next minute
+        let start = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        // Look through each day for the first match
+        for offset in 0..SEARCH_DAYS {
+            // Skip days that don't match
+            let day = start.date() + Duration::days(offset);
+            if !self.matches_day(&day) {
+                continue;
+            }
+
+            // Look through each hour and minute of the day
+            let (first_hour, first_minute) = if offset == 0 {
+                (start.hour(), start.minute())
+            } else {
+                (0, 0)
+            };
+            for hour in first_hour..24 {
+                // Skip hours that don't match
+                if self.hours & (1 << hour) == 0 {
+                    continue;
+                }
+
+                // Find the first matching minute
+                let from_minute = if hour == first_hour { first_minute } else { 0 };
+                let mut minutes = from_minute..60;
+                if let Some(minute) = minutes.find(|minute| self.minutes & (1 << minute) != 0) {
+                    return Some(day.and_hms(hour, minute, 0));
+                }
+            }
+        }
+
+        // Otherwise, there is no match
+        None
+    }
+}
+
+// Implement parsing of the cron rule from a string
+impl TryFrom<String> for CronRule {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        // Separate the five fields
+        let fields: Vec<&str> = rule.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("Cron rule must have five fields: {}", rule));
+        }
+
+        // Parse each field
+        let (minutes, _) = CronRule::parse_field(fields[0], 0, 59, &[])?;
+        let (hours, _) = CronRule::parse_field(fields[1], 0, 23, &[])?;
+        let (days, is_day_restricted) = CronRule::parse_field(fields[2], 1, 31, &[])?;
+        let (months, _) = CronRule::parse_field(fields[3], 1, 12, &MONTH_NAMES)?;
+        let (mut weekdays, is_weekday_restricted) =
+            CronRule::parse_field(fields[4], 0, 7, &WEEKDAY_NAMES)?;
+
+        // Treat day seven as Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        // Return the completed rule
+        Ok(CronRule {
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            is_day_restricted,
+            is_weekday_restricted,
+        })
+    }
+}
+
+// Implement composing the cron rule into a string
+impl From<CronRule> for String {
+    fn from(rule: CronRule) -> Self {
+        rule.to_string()
+    }
+}
+
+// Implement display for the cron rule
+impl fmt::Display for CronRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            CronRule::compose_field(self.minutes, 0, 59, self.minutes != (1 << 60) - 1),
+            CronRule::compose_field(self.hours, 0, 23, self.hours != (1 << 24) - 1),
+            CronRule::compose_field(self.days, 1, 31, self.is_day_restricted),
+            CronRule::compose_field(self.months, 1, 12, self.months != ((1 << 13) - 1) & !1),
+            CronRule::compose_field(self.weekdays, 0, 6, self.is_weekday_restricted),
+        )
+    }
+}
+
+/// An enum to describe when a scheduled event should be triggered.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ScheduleRule {
+    /// A variant to trigger the event once, at the provided local time
+    At { time: NaiveDateTime },
+
+    /// A variant to trigger the event each time the cron rule matches the
+    /// local time
+    Cron { expression: CronRule },
+}
+
+// Implement the schedule rule features
+impl ScheduleRule {
+    /// A method to return the next time that the event should be triggered,
+    /// strictly after the provided time. Returns None if the event should not
+    /// be triggered again.
+    ///
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            ScheduleRule::At { time: at } if *at > time => Some(*at),
+            ScheduleRule::At { .. } => None,
+            ScheduleRule::Cron { expression } => expression.next_after(time),
+        }
+    }
+}
+
+/// A struct to define an event which is triggered according to a schedule.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ScheduledEvent {
+    pub event: ItemId,      // the event to trigger
+    pub rule: ScheduleRule, // the rule that defines when to trigger the event
+    #[serde(default)]
+    pub catch_up: bool,     // a flag to trigger an occurrence missed while the program was stopped
+}
+
+// Tests of the schedule module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test parsing the cron rules and finding the next match
+    #[test]
+    fn cron_rules() {
+        // Parse a rule for every Friday at 21:55
+        let rule = CronRule::try_from("55 21 * * Fri".to_string()).unwrap();
+        assert_eq!(rule.to_string(), "55 21 * * 5");
+
+        // Check the next match from a Wednesday (2021-06-02) and from the match itself
+        let wednesday = NaiveDate::from_ymd(2021, 6, 2).and_hms(12, 0, 0);
+        let friday = NaiveDate::from_ymd(2021, 6, 4).and_hms(21, 55, 0);
+        assert_eq!(rule.next_after(wednesday), Some(friday));
+        assert_eq!(
+            rule.next_after(friday),
+            Some(NaiveDate::from_ymd(2021, 6, 11).and_hms(21, 55, 0))
+        );
+
+        // Parse a rule with steps and ranges
+        let rule = CronRule::try_from("*/15 9-17 1,15 * 7".to_string()).unwrap();
+        assert_eq!(rule.to_string(), "0,15,30,45 9-17 1,15 * 0");
+        assert_eq!(
+            rule.next_after(wednesday),
+            Some(NaiveDate::from_ymd(2021, 6, 6).and_hms(9, 0, 0)) // Sunday (either day matches)
+        );
+
+        // Check that an impossible rule returns nothing
+        let rule = CronRule::try_from("0 0 31 feb *".to_string()).unwrap();
+        assert_eq!(rule.next_after(wednesday), None);
+
+        // Check that invalid rules fail
+        assert!(CronRule::try_from("60 * * * *".to_string()).is_err());
+        assert!(CronRule::try_from("* * * *".to_string()).is_err());
+        assert!(CronRule::try_from("*/0 * * * *".to_string()).is_err());
+    }
+}
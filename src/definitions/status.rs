@@ -21,6 +21,12 @@
 // Import crate definitions
 use crate::definitions::*;
 
+// Import standard library features
+use std::time::Duration;
+
+// Import Chrono features
+use chrono::NaiveDateTime;
+
 // Import FNV HashMap
 use fnv::FnvHashMap;
 
@@ -28,6 +34,10 @@ use fnv::FnvHashMap;
 ///
 pub type StatusMap = FnvHashMap<ItemId, Status>; // a hash map of status id and status pairs
 
+/// A type to store a hashmap of status ids and saved statuses
+///
+pub type SavedStatusMap = FnvHashMap<ItemId, SavedStatus>; // a hash map of status id and saved status pairs
+
 /// A type to store a hashmap of status ids and status descriptions
 ///
 /// # FIXME
@@ -49,6 +59,8 @@ pub enum Status {
     MultiState {
         current: ItemId,        // the current state
         allowed: Vec<ItemId>,   // the allowed states
+        #[serde(default)]
+        state_events: Vec<StateEvents>, // the events to trigger when entering or leaving particular states
         no_change_silent: bool, // if true, events are only broadcast when the state changes
     },
 
@@ -61,12 +73,49 @@ pub enum Status {
         reset: ItemId,          // the state to reset the status to its default value
         count: u32,             // the current count of the status
         default_count: u32,     // the starting value of the status count
+        #[serde(default)]
+        state_events: Vec<StateEvents>, // the events to trigger when entering or leaving particular states
         no_change_silent: bool, // if true, events are only broadcast when the state changes
     },
+
+    /// The Timer variant
+    ///
+    Timer {
+        current: ItemId,     // the current state
+        start: ItemId,       // the state to start (or resume) the timer
+        stop: ItemId,        // the state to stop the timer
+        reset: ItemId,       // the state to stop the timer and restore the full duration
+        add_time: ItemId,    // the state to add the increment to the remaining time
+        duration: Duration,  // the full duration of the timer
+        increment: Duration, // the time added to the timer by the add time state
+        remaining: Duration, // the remaining time when the timer was last started or stopped
+        #[serde(skip)]
+        started: Option<NaiveDateTime>, // the clock time when the timer was started, if running
+        #[serde(default)]
+        thresholds: Vec<TimerThreshold>, // the events to trigger at particular remaining times
+        #[serde(default)]
+        state_events: Vec<StateEvents>, // the events to trigger when entering or leaving particular states
+        no_change_silent: bool, // if true, events are only broadcast when the state changes
+    },
+
+    /// The Variable variant
+    ///
+    Variable {
+        current: ItemId,        // the state (and event) triggered when the value changes
+        value: i64,             // the current value of the variable
+        default_value: i64,     // the starting value of the variable
+        min: Option<i64>,       // the minimum value of the variable, if limited
+        max: Option<i64>,       // the maximum value of the variable, if limited
+        #[serde(default)]
+        thresholds: Vec<VariableThreshold>, // the events to trigger when the value reaches particular values
+        #[serde(default)]
+        state_events: Vec<StateEvents>, // the events to trigger when entering or leaving particular states
+        no_change_silent: bool, // if true, events are only broadcast when the value changes
+    },
 }
 
 // Reexport the status variants
-use self::Status::{CountedState, MultiState};
+use self::Status::{CountedState, MultiState, Timer, Variable};
 
 // Implement key features for Status
 impl Status {
@@ -76,6 +125,254 @@ impl Status {
         match self {
             &MultiState { ref current, .. } => current.clone(),
             &CountedState { ref current, .. } => current.clone(),
+            &Timer { ref current, .. } => *current,
+            &Variable { ref current, .. } => *current,
+        }
+    }
+
+    /// A method to return the current count of the status, if the status
+    /// is a counted state
+    ///
+    pub fn count(&self) -> Option<u32> {
+        match self {
+            CountedState { count, .. } => Some(*count),
+            _ => None,
+        }
+    }
+
+    /// A method to return the current value of the status, if the status is
+    /// a variable
+    ///
+    pub fn value(&self) -> Option<i64> {
+        match self {
+            Variable { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// A method to modify the value of the status, if the status is a
+    /// variable. The new value is limited to the minimum and maximum of the
+    /// variable. Returns the previous and the new value if the value changed
+    /// (or if the variable is not no_change_silent).
+    ///
+    pub fn modify_value(&mut self, operation: VariableOperation) -> Option<(i64, i64)> {
+        match self {
+            &mut Variable {
+                ref mut value,
+                ref default_value,
+                ref min,
+                ref max,
+                ref no_change_silent,
+                ..
+            } => {
+                // Calculate the new value
+                let previous = *value;
+                let mut new_value = match operation {
+                    VariableOperation::Set { value } => value,
+                    VariableOperation::Add { value } => previous.saturating_add(value),
+                    VariableOperation::Subtract { value } => previous.saturating_sub(value),
+                    VariableOperation::Multiply { value } => previous.saturating_mul(value),
+                    VariableOperation::Reset => *default_value,
+                };
+
+                // Limit the new value
+                if let Some(min) = min {
+                    new_value = new_value.max(*min);
+                }
+                if let Some(max) = max {
+                    new_value = new_value.min(*max);
+                }
+
+                // If no_change_silent and the value is the same
+                if *no_change_silent & (new_value == previous) {
+                    return None; // Indicate no change
+                }
+
+                // Update the value
+                *value = new_value;
+                Some((previous, new_value))
+            }
+
+            // Ignore other statuses
+            _ => None,
+        }
+    }
+
+    /// A method to return the threshold events reached when the value of a
+    /// variable changes from the previous value to the new value, in the
+    /// order they were reached. A threshold is reached when the value moves
+    /// onto or past it (in either direction).
+    ///
+    pub fn reached(&self, previous: i64, new_value: i64) -> Vec<ItemId> {
+        match self {
+            Variable { thresholds, .. } => {
+                // Find the thresholds between the two values
+                let mut reached: Vec<VariableThreshold> = thresholds
+                    .iter()
+                    .filter(|threshold| {
+                        ((previous < threshold.value) & (threshold.value <= new_value))
+                            | ((previous > threshold.value) & (threshold.value >= new_value))
+                    })
+                    .cloned()
+                    .collect();
+
+                // Sort the thresholds in the direction of the change
+                reached.sort_by_key(|threshold| threshold.value);
+                if new_value < previous {
+                    reached.reverse();
+                }
+                reached.iter().map(|threshold| threshold.event).collect()
+            }
+
+            // Ignore other statuses
+            _ => Vec::new(),
+        }
+    }
+
+    /// A method to return all the threshold events of the status, if the
+    /// status is a timer or a variable
+    ///
+    pub fn threshold_events(&self) -> Vec<ItemId> {
+        match self {
+            Timer { thresholds, .. } => thresholds.iter().map(|threshold| threshold.event).collect(),
+            Variable { thresholds, .. } => thresholds.iter().map(|threshold| threshold.event).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// A method to return the events to trigger when entering or leaving
+    /// particular states of the status
+    ///
+    pub fn state_events(&self) -> &[StateEvents] {
+        match self {
+            MultiState { state_events, .. }
+            | CountedState { state_events, .. }
+            | Timer { state_events, .. }
+            | Variable { state_events, .. } => state_events,
+        }
+    }
+
+    /// A method to return the events triggered when the current state of the
+    /// status changes from the previous state to the new state: the on_exit
+    /// events of the previous state followed by the on_enter events of the
+    /// new state. Returns no events if the state did not change.
+    ///
+    pub fn transition_events(&self, previous: ItemId, new_state: ItemId) -> Vec<ItemId> {
+        // Ignore the transition if the state did not change
+        let mut events = Vec::new();
+        if previous == new_state {
+            return events;
+        }
+
+        // Collect the exit events, then the enter events
+        for state_events in self.state_events() {
+            if state_events.state == previous {
+                events.extend(state_events.on_exit.iter().cloned());
+            }
+        }
+        for state_events in self.state_events() {
+            if state_events.state == new_state {
+                events.extend(state_events.on_enter.iter().cloned());
+            }
+        }
+        events
+    }
+
+    /// A method to return the current remaining time of the status, if the
+    /// status is a timer
+    ///
+    pub fn remaining(&self) -> Option<Duration> {
+        match self {
+            &Timer {
+                ref remaining,
+                ref started,
+                ..
+            } => match started {
+                // Subtract the time since the timer was started
+                Some(start_time) => Some(remaining.saturating_sub(Clock::elapsed(*start_time))),
+                None => Some(*remaining),
+            },
+            _ => None,
+        }
+    }
+
+    /// A method to indicate if the status is a timer which is running
+    ///
+    pub fn is_running(&self) -> bool {
+        match self {
+            &Timer { ref started, .. } => started.is_some(),
+            _ => false,
+        }
+    }
+
+    /// A method to return the thresholds of the status, if the status is a
+    /// timer
+    ///
+    pub fn thresholds(&self) -> Vec<TimerThreshold> {
+        match self {
+            &Timer { ref thresholds, .. } => thresholds.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// A method to stop the status if it is a running timer which has run
+    /// out of time. Returns the new state if the timer was stopped.
+    ///
+    pub fn expire(&mut self) -> Option<ItemId> {
+        // Check that the timer is running and has run out of time
+        let stop = match self {
+            &mut Timer { ref stop, .. } => *stop,
+            _ => return None,
+        };
+        if !self.is_running() || self.remaining() != Some(Duration::from_secs(0)) {
+            return None;
+        }
+
+        // Stop the timer
+        self.update(stop)
+    }
+
+    /// A method to save the complete current state of the status (the
+    /// remaining time of a running timer is frozen at the current time)
+    ///
+    pub fn save(&self) -> SavedStatus {
+        SavedStatus {
+            current: self.current(),
+            count: match self {
+                CountedState { count, .. } => Some(*count),
+                _ => None,
+            },
+            value: self.value(),
+            remaining: self.remaining(),
+            is_running: self.is_running(),
+        }
+    }
+
+    /// A method to restore the complete state of the status from a saved
+    /// state. A timer which was running continues from the saved remaining
+    /// time.
+    ///
+    pub fn restore(&mut self, saved: &SavedStatus) {
+        match self {
+            MultiState { current, .. } => *current = saved.current,
+            CountedState { current, count, .. } => {
+                *current = saved.current;
+                *count = saved.count.unwrap_or(*count);
+            }
+            Timer {
+                current,
+                remaining,
+                started,
+                ..
+            } => {
+                *current = saved.current;
+                *remaining = saved.remaining.unwrap_or(*remaining);
+                *started = Some(Clock::now()).filter(|_| saved.is_running);
+            }
+            Variable { current, value, .. } => {
+                *current = saved.current;
+                *value = saved.value.unwrap_or(*value);
+            }
         }
     }
 
@@ -97,6 +394,14 @@ impl Status {
                 allowed.push(reset.clone());
                 allowed
             }
+            &Timer {
+                ref start,
+                ref stop,
+                ref reset,
+                ref add_time,
+                ..
+            } => vec![*start, *stop, *reset, *add_time],
+            &Variable { ref current, .. } => vec![*current],
         }
     }
 
@@ -121,6 +426,24 @@ impl Status {
                 // Check if the new state is valid
                 (*new_state == *trigger) | (*new_state == *anti_trigger) | (*new_state == *reset)
             }
+
+            // The timer variant
+            &Timer {
+                ref start,
+                ref stop,
+                ref reset,
+                ref add_time,
+                ..
+            } => {
+                // Check if the new state is valid
+                (*new_state == *start)
+                    | (*new_state == *stop)
+                    | (*new_state == *reset)
+                    | (*new_state == *add_time)
+            }
+
+            // The variable variant
+            &Variable { ref current, .. } => *new_state == *current,
         }
     }
 
@@ -136,6 +459,7 @@ impl Status {
                 ref mut current,
                 ref allowed,
                 ref no_change_silent,
+                ..
             } => {
                 // Check that the new state is valid
                 if allowed.is_empty() | allowed.contains(&new_state) {
@@ -163,6 +487,7 @@ impl Status {
                 ref trigger,
                 ref anti_trigger,
                 ref no_change_silent,
+                ..
             } => {
                 // Reset the count and state
                 if new_state == *reset {
@@ -216,6 +541,308 @@ impl Status {
                     None
                 }
             }
+
+            // The timer variant
+            &mut Timer {
+                ref mut current,
+                ref start,
+                ref stop,
+                ref reset,
+                ref add_time,
+                ref duration,
+                ref increment,
+                ref mut remaining,
+                ref mut started,
+                ref no_change_silent,
+                ..
+            } => {
+                // Start the timer (unless already running)
+                if new_state == *start {
+                    if started.is_none() {
+                        *started = Some(Clock::now());
+
+                    // If no_change_silent and the timer is already running
+                    } else if *no_change_silent {
+                        return None; // Indicate no change
+                    }
+
+                    // Update the current state
+                    *current = *start;
+                    Some(*current)
+
+                // Stop the timer, keeping the remaining time
+                } else if new_state == *stop {
+                    if let Some(start_time) = started.take() {
+                        *remaining = remaining.saturating_sub(Clock::elapsed(start_time));
+
+                    // If no_change_silent and the timer is already stopped
+                    } else if *no_change_silent & (*current == *stop) {
+                        return None; // Indicate no change
+                    }
+
+                    // Update the current state
+                    *current = *stop;
+                    Some(*current)
+
+                // Stop the timer and restore the full duration
+                } else if new_state == *reset {
+                    // If no_change_silent and the timer is already reset
+                    if *no_change_silent
+                        & (*current == *reset)
+                        & started.is_none()
+                        & (*remaining == *duration)
+                    {
+                        return None; // Indicate no change
+                    }
+
+                    // Reset the timer and the current state
+                    *started = None;
+                    *remaining = *duration;
+                    *current = *reset;
+                    Some(*current)
+
+                // Add time to the timer (without changing the current state)
+                } else if new_state == *add_time {
+                    *remaining += *increment;
+                    Some(*add_time)
+
+                // Otherwise report failure
+                } else {
+                    None
+                }
+            }
+
+            // The variable variant (the value is changed with modify_value)
+            &mut Variable {
+                ref current,
+                ref no_change_silent,
+                ..
+            } => {
+                // Check that the new state is valid and not silent
+                if (new_state != *current) | *no_change_silent {
+                    return None;
+                }
+                Some(*current)
+            }
+        }
+    }
+}
+
+/// An enum to specify an operation to change the value of a variable.
+///
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum VariableOperation {
+    /// A variant to set the variable to the value
+    Set { value: i64 },
+
+    /// A variant to add the value to the variable
+    Add { value: i64 },
+
+    /// A variant to subtract the value from the variable
+    Subtract { value: i64 },
+
+    /// A variant to multiply the variable by the value
+    Multiply { value: i64 },
+
+    /// A variant to reset the variable to its default value
+    Reset,
+}
+
+/// A struct to define the events which are triggered when a status enters
+/// or leaves a particular state.
+///
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct StateEvents {
+    pub state: ItemId, // the state of interest
+    #[serde(default)]
+    pub on_enter: Vec<ItemId>, // the events to trigger when the status enters the state
+    #[serde(default)]
+    pub on_exit: Vec<ItemId>, // the events to trigger when the status leaves the state
+}
+
+/// A struct to define an event which is triggered when the value of a
+/// variable reaches the threshold.
+///
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct VariableThreshold {
+    pub value: i64,    // the value when the event is triggered
+    pub event: ItemId, // the event to trigger
+}
+
+/// A struct to define an event which is triggered when the remaining time of
+/// a timer reaches the threshold.
+///
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct TimerThreshold {
+    pub remaining: Duration, // the remaining time when the event is triggered
+    pub event: ItemId,       // the event to trigger
+}
+
+/// A struct to hold the complete current state of a status, so that the
+/// status can be restored after a restart.
+///
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct SavedStatus {
+    pub current: ItemId, // the current state
+    #[serde(default)]
+    pub count: Option<u32>, // the current count, if the status is a counted state
+    #[serde(default)]
+    pub value: Option<i64>, // the current value, if the status is a variable
+    #[serde(default)]
+    pub remaining: Option<Duration>, // the remaining time, if the status is a timer
+    #[serde(default)]
+    pub is_running: bool, // a flag to indicate that the timer was running
+}
+
+/// A helper function to return the remaining time as whole seconds, rounded
+/// up (so that a timer only shows zero once it has run out).
+///
+pub fn whole_seconds(remaining: Duration) -> u32 {
+    let seconds = remaining.as_secs() + (remaining.subsec_nanos() > 0) as u64;
+    seconds.min(u32::MAX as u64) as u32
+}
+
+/// An enum to specify how the count of a status should be compared to
+/// a fixed value.
+///
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum Comparison {
+    /// The count is equal to the value
+    Equal,
+
+    /// The count is not equal to the value
+    NotEqual,
+
+    /// The count is less than the value
+    Less,
+
+    /// The count is less than or equal to the value
+    LessOrEqual,
+
+    /// The count is greater than the value
+    Greater,
+
+    /// The count is greater than or equal to the value
+    GreaterOrEqual,
+}
+
+// Implement key features for Comparison
+impl Comparison {
+    /// A method to compare the count (or the remaining seconds or the value)
+    /// with the provided value
+    ///
+    pub fn compare<T: PartialOrd>(&self, count: T, value: T) -> bool {
+        match self {
+            Comparison::Equal => count == value,
+            Comparison::NotEqual => count != value,
+            Comparison::Less => count < value,
+            Comparison::LessOrEqual => count <= value,
+            Comparison::Greater => count > value,
+            Comparison::GreaterOrEqual => count >= value,
+        }
+    }
+}
+
+/// An enum to hold a boolean expression over one or more statuses. The
+/// expression is evaluated against the current state of the status map.
+///
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum StatusCondition {
+    /// A variant which is true only if all the conditions are true
+    And { conditions: Vec<StatusCondition> },
+
+    /// A variant which is true if any of the conditions are true
+    Or { conditions: Vec<StatusCondition> },
+
+    /// A variant which inverts the result of the condition
+    Not { condition: Box<StatusCondition> },
+
+    /// A variant which is true if the status is in the indicated state
+    IsState { status_id: ItemId, state: ItemId },
+
+    /// A variant which compares the count of a counted state to a value
+    CountCompare {
+        status_id: ItemId,
+        comparison: Comparison,
+        value: u32,
+    },
+
+    /// A variant which compares the remaining whole seconds of a timer to a
+    /// number of seconds
+    TimerCompare {
+        status_id: ItemId,
+        comparison: Comparison,
+        seconds: u32,
+    },
+
+    /// A variant which compares the value of a variable to a value
+    ValueCompare {
+        status_id: ItemId,
+        comparison: Comparison,
+        value: i64,
+    },
+}
+
+// Implement key features for StatusCondition
+impl StatusCondition {
+    /// A method to evaluate the condition against the provided status map.
+    /// Statuses which are missing (or are not counted states, for a count
+    /// comparison, timers, for a timer comparison, or variables, for a value
+    /// comparison) evaluate as false.
+    ///
+    pub fn evaluate(&self, status_map: &StatusMap) -> bool {
+        match self {
+            // Check that every condition is true
+            &StatusCondition::And { ref conditions } => conditions
+                .iter()
+                .all(|condition| condition.evaluate(status_map)),
+
+            // Check that any condition is true
+            &StatusCondition::Or { ref conditions } => conditions
+                .iter()
+                .any(|condition| condition.evaluate(status_map)),
+
+            // Invert the condition
+            &StatusCondition::Not { ref condition } => !condition.evaluate(status_map),
+
+            // Compare the current state of the status
+            &StatusCondition::IsState {
+                ref status_id,
+                ref state,
+            } => match status_map.get(status_id) {
+                Some(status) => status.current() == *state,
+                None => false,
+            },
+
+            // Compare the count of the status
+            &StatusCondition::CountCompare {
+                ref status_id,
+                ref comparison,
+                ref value,
+            } => match status_map.get(status_id).and_then(|status| status.count()) {
+                Some(count) => comparison.compare(count, *value),
+                None => false,
+            },
+
+            // Compare the remaining time of the timer
+            StatusCondition::TimerCompare {
+                status_id,
+                comparison,
+                seconds,
+            } => match status_map.get(status_id).and_then(|status| status.remaining()) {
+                Some(remaining) => comparison.compare(whole_seconds(remaining), *seconds),
+                None => false,
+            },
+
+            // Compare the value of the variable
+            StatusCondition::ValueCompare {
+                status_id,
+                comparison,
+                value,
+            } => match status_map.get(status_id).and_then(|status| status.value()) {
+                Some(current) => comparison.compare(current, *value),
+                None => false,
+            },
         }
     }
 }
@@ -263,6 +890,7 @@ mod tests {
         let mut status = MultiState {
             current: id1,
             allowed: valid_states.clone(),
+            state_events: Vec::new(),
             no_change_silent: false,
         };
 
@@ -297,6 +925,7 @@ mod tests {
             reset: id3,
             count: 1,
             default_count: 1,
+            state_events: Vec::new(),
             no_change_silent: false,
         };
 
@@ -322,5 +951,300 @@ mod tests {
         // Check changing the state to an invalid option
         assert_eq!(None, status.update(id4));
         assert_eq!(id2, status.current());
+
+        // Check saving and restoring the count
+        assert_eq!(Some(id2), status.update(id2));
+        let saved = status.save();
+        assert_eq!(Some(2), saved.count);
+        assert_eq!(Some(id2), status.update(id3));
+        status.restore(&saved);
+        assert_eq!(Some(2), status.count());
+    }
+
+    // Test creation and modification of a Timer status
+    #[tokio::test(start_paused = true)]
+    async fn timer() {
+        // Import libraries for testing
+        use std::time::Duration;
+        use tokio::time::advance;
+
+        // Create a new timer on the virtual clock
+        Clock::set_virtual(1.0);
+        let id1 = ItemId::new_unchecked(10);
+        let id2 = ItemId::new_unchecked(11);
+        let id3 = ItemId::new_unchecked(12);
+        let id4 = ItemId::new_unchecked(13);
+        let id5 = ItemId::new_unchecked(14);
+        let mut status = Timer {
+            current: id3,
+            start: id1,
+            stop: id2,
+            reset: id3,
+            add_time: id4,
+            duration: Duration::from_secs(60),
+            increment: Duration::from_secs(30),
+            remaining: Duration::from_secs(60),
+            started: None,
+            thresholds: Vec::new(),
+            state_events: Vec::new(),
+            no_change_silent: true,
+        };
+
+        // Check the allowed states and the stopped timer
+        assert_eq!(vec![id1, id2, id3, id4], status.allowed());
+        advance(Duration::from_secs(5)).await;
+        assert_eq!(Some(Duration::from_secs(60)), status.remaining());
+
+        // Check starting the timer
+        assert_eq!(Some(id1), status.update(id1));
+        assert_eq!(None, status.update(id1));
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(Some(Duration::from_secs(50)), status.remaining());
+        assert_eq!(None, status.count());
+
+        // Check adding time while running
+        assert_eq!(Some(id4), status.update(id4));
+        assert_eq!(id1, status.current());
+        assert_eq!(Some(Duration::from_secs(80)), status.remaining());
+
+        // Check stopping the timer
+        assert_eq!(Some(id2), status.update(id2));
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(Some(Duration::from_secs(80)), status.remaining());
+        assert!(!status.is_running());
+
+        // Check that the timer runs out and stops
+        assert_eq!(Some(id1), status.update(id1));
+        assert_eq!(None, status.expire());
+        advance(Duration::from_secs(90)).await;
+        assert_eq!(Some(Duration::from_secs(0)), status.remaining());
+        assert_eq!(Some(id2), status.expire());
+        assert_eq!(id2, status.current());
+
+        // Check resetting the timer and an invalid option
+        assert_eq!(Some(id3), status.update(id3));
+        assert_eq!(Some(Duration::from_secs(60)), status.remaining());
+
+        // Check saving a running timer and restoring it later
+        assert_eq!(Some(id1), status.update(id1));
+        advance(Duration::from_secs(15)).await;
+        let saved = status.save();
+        assert_eq!(Some(Duration::from_secs(45)), saved.remaining);
+        assert!(saved.is_running);
+        assert_eq!(Some(id3), status.update(id3));
+        advance(Duration::from_secs(30)).await;
+        status.restore(&saved);
+        assert_eq!(id1, status.current());
+        assert_eq!(saved, status.save());
+        advance(Duration::from_secs(5)).await;
+        assert_eq!(Some(Duration::from_secs(40)), status.remaining());
+        assert_eq!(Some(id3), status.update(id3));
+        assert_eq!(None, status.update(id3));
+        assert_eq!(None, status.update(id5));
+    }
+
+    // Test creation and modification of a Variable status
+    #[test]
+    fn variable() {
+        // Create a new variable with thresholds
+        let id1 = ItemId::new_unchecked(10);
+        let id2 = ItemId::new_unchecked(11);
+        let id3 = ItemId::new_unchecked(12);
+        let id4 = ItemId::new_unchecked(13);
+        let mut status = Variable {
+            current: id1,
+            value: 0,
+            default_value: 5,
+            min: Some(0),
+            max: Some(100),
+            thresholds: vec![
+                VariableThreshold { value: 10, event: id2 },
+                VariableThreshold { value: 50, event: id3 },
+            ],
+            state_events: Vec::new(),
+            no_change_silent: true,
+        };
+
+        // Check the allowed states and the value
+        assert_eq!(vec![id1], status.allowed());
+        assert_eq!(Some(0), status.value());
+        assert_eq!(None, status.count());
+
+        // Check the arithmetic operations
+        assert_eq!(Some((0, 8)), status.modify_value(VariableOperation::Add { value: 8 }));
+        assert_eq!(Some((8, 24)), status.modify_value(VariableOperation::Multiply { value: 3 }));
+        assert_eq!(Some((24, 20)), status.modify_value(VariableOperation::Subtract { value: 4 }));
+        assert_eq!(Some((20, 5)), status.modify_value(VariableOperation::Reset));
+        assert_eq!(Some((5, 60)), status.modify_value(VariableOperation::Set { value: 60 }));
+
+        // Check clamping to the minimum and maximum
+        assert_eq!(Some((60, 100)), status.modify_value(VariableOperation::Add { value: 70 }));
+        assert_eq!(None, status.modify_value(VariableOperation::Add { value: 1 }));
+        assert_eq!(Some((100, 0)), status.modify_value(VariableOperation::Set { value: -3 }));
+        assert_eq!(Some(0), status.value());
+
+        // Check the thresholds reached in each direction
+        assert_eq!(vec![id2, id3], status.reached(0, 60));
+        assert_eq!(vec![id3, id2], status.reached(60, 10));
+        assert_eq!(Vec::<ItemId>::new(), status.reached(10, 10));
+        assert_eq!(Vec::<ItemId>::new(), status.reached(11, 49));
+
+        // Check that other statuses have no value
+        let mut other = MultiState {
+            current: id1,
+            allowed: vec![id1, id4],
+            state_events: Vec::new(),
+            no_change_silent: false,
+        };
+        assert_eq!(None, other.value());
+        assert_eq!(None, other.modify_value(VariableOperation::Reset));
+    }
+
+    // Test evaluation of a status condition
+    #[test]
+    fn status_condition() {
+        // Import libraries for testing
+        use std::time::Duration;
+
+        // Create a multistate, a countedstate, a timer, and a variable
+        let id1 = ItemId::new_unchecked(10);
+        let id2 = ItemId::new_unchecked(11);
+        let id3 = ItemId::new_unchecked(12);
+        let multi_id = ItemId::new_unchecked(20);
+        let counted_id = ItemId::new_unchecked(21);
+        let missing_id = ItemId::new_unchecked(22);
+        let mut status_map = StatusMap::default();
+        status_map.insert(
+            multi_id,
+            MultiState {
+                current: id1,
+                allowed: vec![id1, id2],
+                state_events: Vec::new(),
+                no_change_silent: false,
+            },
+        );
+        status_map.insert(
+            counted_id,
+            CountedState {
+                current: id2,
+                trigger: id1,
+                anti_trigger: id2,
+                reset: id3,
+                count: 2,
+                default_count: 2,
+                state_events: Vec::new(),
+                no_change_silent: false,
+            },
+        );
+
+        let timer_id = ItemId::new_unchecked(23);
+        status_map.insert(
+            timer_id,
+            Timer {
+                current: id3,
+                start: id1,
+                stop: id2,
+                reset: id3,
+                add_time: id3,
+                duration: Duration::from_secs(60),
+                increment: Duration::from_secs(30),
+                remaining: Duration::from_secs(45),
+                started: None,
+                thresholds: Vec::new(),
+                state_events: Vec::new(),
+                no_change_silent: false,
+            },
+        );
+        let variable_id = ItemId::new_unchecked(24);
+        status_map.insert(
+            variable_id,
+            Variable {
+                current: id1,
+                value: -10,
+                default_value: 0,
+                min: None,
+                max: None,
+                thresholds: Vec::new(),
+                state_events: Vec::new(),
+                no_change_silent: false,
+            },
+        );
+
+        // Check the state comparisons
+        let is_first = StatusCondition::IsState {
+            status_id: multi_id,
+            state: id1,
+        };
+        assert!(is_first.evaluate(&status_map));
+        assert!(!StatusCondition::IsState {
+            status_id: missing_id,
+            state: id1
+        }
+        .evaluate(&status_map));
+
+        // Check the count comparisons
+        let count_above = StatusCondition::CountCompare {
+            status_id: counted_id,
+            comparison: Comparison::Greater,
+            value: 1,
+        };
+        assert!(count_above.evaluate(&status_map));
+        assert!(!StatusCondition::CountCompare {
+            status_id: multi_id,
+            comparison: Comparison::Equal,
+            value: 0
+        }
+        .evaluate(&status_map));
+
+        // Check the timer comparisons
+        assert!(StatusCondition::TimerCompare {
+            status_id: timer_id,
+            comparison: Comparison::LessOrEqual,
+            seconds: 45,
+        }
+        .evaluate(&status_map));
+        assert!(!StatusCondition::TimerCompare {
+            status_id: counted_id,
+            comparison: Comparison::GreaterOrEqual,
+            seconds: 0,
+        }
+        .evaluate(&status_map));
+
+        // Check the value comparisons (including a negative value)
+        assert!(StatusCondition::ValueCompare {
+            status_id: variable_id,
+            comparison: Comparison::Less,
+            value: -5,
+        }
+        .evaluate(&status_map));
+        assert!(!StatusCondition::CountCompare {
+            status_id: variable_id,
+            comparison: Comparison::GreaterOrEqual,
+            value: 0,
+        }
+        .evaluate(&status_map));
+        assert!(!StatusCondition::ValueCompare {
+            status_id: counted_id,
+            comparison: Comparison::GreaterOrEqual,
+            value: 0,
+        }
+        .evaluate(&status_map));
+
+        // Check the combined expressions
+        let not_above = StatusCondition::Not {
+            condition: Box::new(count_above.clone()),
+        };
+        assert!(StatusCondition::And {
+            conditions: vec![is_first.clone(), count_above.clone()]
+        }
+        .evaluate(&status_map));
+        assert!(!StatusCondition::And {
+            conditions: vec![is_first.clone(), not_above.clone()]
+        }
+        .evaluate(&status_map));
+        assert!(StatusCondition::Or {
+            conditions: vec![is_first, not_above]
+        }
+        .evaluate(&status_map));
     }
 }
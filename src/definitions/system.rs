@@ -125,6 +125,26 @@ pub enum Modification {
     },
 }
 
+/// An enum to specify the change to a single event in the queue
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventAdjustment {
+    /// A variant to change the delay, relative to the original start time
+    Delay(Duration),
+
+    /// A variant to remove the event from the queue
+    Cancel,
+
+    /// A variant to hold the event, freezing the remaining time
+    Hold,
+
+    /// A variant to release a held event with the remaining time
+    Release,
+
+    /// A variant to remove the event from the queue and trigger it now
+    FireNow,
+}
+
 /// An enum to specify the type of detail request
 ///
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -261,6 +281,10 @@ pub enum UserRequest {
     /// A variant to trigger all the queued events to clear
     ClearQueue,
 
+    /// A variant to change the speed of the program clock (for example, to
+    /// rehearse the show at four times the normal speed)
+    ClockSpeed { speed: f64 },
+
     /// A special variant to close the program and unload all the data.
     Close,
 
@@ -285,20 +309,30 @@ pub enum UserRequest {
     /// A variant to modify the underlying configuration
     Edit { modifications: Vec<Modification> },
 
+    /// A variant to enable or disable the scheduled events
+    EnableSchedule { is_enabled: bool },
+
+    /// A variant to end the current game and clear its backup
+    EndGame,
+
     /// A variant that provides a new error log file for the system interface.
     ErrorLog { filepath: PathBuf },
 
     /// A variant to change the remaining delay for an existing event in the
-    /// queue.
+    /// queue, or to cancel, hold, release, or trigger the event.
     EventChange {
         event_id: ItemId,
         start_time: NaiveDateTime, // the start time of the event, for unambiguous identification
-        new_delay: Option<Duration>, // new delay relative to the original start time, or None to cancel the event
+        change: EventAdjustment,   // the change to the event
     },
 
     /// A variant that provides a new game log file for the system interface.
     GameLog { filepath: PathBuf },
 
+    /// A variant to pause the queued events, freezing the remaining time of
+    /// each event until the queue is resumed
+    PauseQueue,
+
     /// A variant that processes a new event with the given item id. If the
     /// check_scene flag is not set, the system will not check if the event is
     /// listed in the current scene. If broadcast is set to true, the event
@@ -309,9 +343,32 @@ pub enum UserRequest {
         broadcast: bool,
     },
 
+    /// A variant to query the history in the structured game log. Returns
+    /// the entries between the start and end times (if specified) with one of
+    /// the log types (or any type, if none are specified), skipping the
+    /// offset and returning at most the limit.
+    QueryLog {
+        start: Option<NaiveDateTime>,
+        end: Option<NaiveDateTime>,
+        log_types: Vec<LogType>,
+        offset: usize,
+        limit: Option<usize>,
+    },
+
+    /// A variant to redo the most recently undone status or scene change. If
+    /// fire_events is true, the events for the restored state are triggered.
+    Redo { fire_events: bool },
+
     /// A variant that triggers a redraw of the user interface window FIXME can likely be removed
     Redraw,
 
+    /// A variant to restore the saved game from before the program was
+    /// restarted, or to discard it if restore is false.
+    RestoreGame { restore: bool },
+
+    /// A variant to resume the queued events with their remaining time
+    ResumeQueue,
+
     /// A variant that provides a new configuration file to save the current
     /// configuration.
     SaveConfig { filepath: PathBuf },
@@ -321,6 +378,11 @@ pub enum UserRequest {
 
     /// A variant to change the state of the indicated status.
     StatusChange { status: ItemId, state: ItemId },
+
+    /// A variant to undo the most recent status or scene change by the
+    /// operator. If fire_events is true, the events for the previous state
+    /// are triggered.
+    Undo { fire_events: bool },
 }
 
 /// A type to cover all web replies
@@ -356,6 +418,13 @@ pub enum WebReply {
         items: Vec<ItemId>, // the list of all items, if found
     },
 
+    // A variant that contains entries from the structured game log
+    #[serde(rename_all = "camelCase")]
+    Log {
+        is_valid: bool,         // a flag to indicate the result of the request
+        entries: Vec<LogEntry>, // the entries which matched the query
+    },
+
     // A variant for replies with no specific content
     #[serde(rename_all = "camelCase")]
     Generic {
@@ -417,6 +486,7 @@ impl WebReply {
             &WebReply::Event { ref is_valid, .. } => is_valid.clone(),
             &WebReply::Item { ref is_valid, .. } => is_valid.clone(),
             &WebReply::Items { ref is_valid, .. } => is_valid.clone(),
+            &WebReply::Log { ref is_valid, .. } => is_valid.clone(),
             &WebReply::Generic { ref is_valid, .. } => is_valid.clone(),
             &WebReply::Path { ref is_valid, .. } => is_valid.clone(),
             &WebReply::Scene { ref is_valid, .. } => is_valid.clone(),
@@ -51,6 +51,7 @@ use tracing_subscriber;
 
 // Define program constants
 const USER_STYLE_SHEET: &str = "/tmp/userStyles.css";
+const TRIGGER_SETTINGS: &str = "trigger.yaml";
 
 /// The Minerva structure to contain the program launching and overall
 /// communication code.
@@ -0,0 +1,1002 @@
+// Copyright (c) 2019-21 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the connection to a backup storage backend (a Redis
+//! server, a local file, or memory) to maintain a backup of the system state.
+//! This handler syncs the system status, current scene, and queue to the
+//! storage, along with a versioned snapshot of the complete game state. Every
+//! key is namespaced by the key prefix and the instance identifier (so that
+//! several instances can share one storage), and every value may expire after
+//! a time-to-live. The backup is kept until the game is explicitly ended. This
+//! module does nothing if no backup storage is specified.
+//!
+//! WARNING: This module assumes no authorized systems/operators are compromised.
+
+// Define private submodules
+mod storage;
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use self::storage::{open_storage, Storage};
+
+// Import standard library features
+use std::time::Duration;
+
+// Import Chrono features
+use chrono::NaiveDateTime;
+
+// Import the failure features
+use failure::Error;
+
+// Import YAML processing library
+use serde_yaml;
+
+// Define module constants
+const SNAPSHOT_VERSION: u32 = 1; // the current version of the game snapshot
+
+/// An internal structure to store queued events
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct QueuedEvent {
+    pub remaining: Duration, // the remaining time before the event is triggered
+    pub event_id: ItemId,    // id of the event to launch
+    #[serde(default)]
+    pub is_held: bool, // a flag to indicate that the event is held
+    #[serde(default)]
+    pub repeat: Option<Repetition>, // the repetitions of the event, if it repeats
+    #[serde(default)]
+    pub due: Option<NaiveDateTime>, // the time a scheduled event is due, if it is scheduled
+    #[serde(default)]
+    pub priority: u8, // the priority of the event among events at the same time
+}
+
+/// A structure to hold a complete snapshot of the game state. The snapshot is
+/// written to the backup storage as a single value so that it is always
+/// complete.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub version: u32,                  // the version of the snapshot
+    pub saved: NaiveDateTime,          // the clock time when the snapshot was saved
+    pub current_scene: Option<ItemId>, // the current scene, once the game has started
+    #[serde(default)]
+    pub statuses: SavedStatusMap,      // the complete state of every status
+    #[serde(default)]
+    pub queue: Vec<QueuedEvent>,       // the events in the queue
+    #[serde(default)]
+    pub is_paused: bool,               // a flag to indicate that the queue is paused
+    #[serde(default)]
+    pub output: OutputState,           // the state of the dmx and media outputs
+}
+
+// Implement key features of the game snapshot
+impl GameSnapshot {
+    /// A function to create a new, empty game snapshot
+    ///
+    pub fn new() -> GameSnapshot {
+        GameSnapshot {
+            version: SNAPSHOT_VERSION,
+            saved: Clock::now(),
+            current_scene: None,
+            statuses: SavedStatusMap::default(),
+            queue: Vec::new(),
+            is_paused: false,
+            output: OutputState::default(),
+        }
+    }
+
+    /// A method to return the clock time since the snapshot was saved.
+    ///
+    pub fn age(&self) -> Duration {
+        Clock::elapsed(self.saved)
+    }
+
+    /// A method to reduce the remaining time of the queued events (unless
+    /// the queue is paused or the event is held) by the provided time.
+    ///
+    pub fn advance_queue(&mut self, elapsed: Duration) {
+        if !self.is_paused {
+            for event in self.queue.iter_mut().filter(|event| !event.is_held) {
+                event.remaining = event.remaining.saturating_sub(elapsed);
+            }
+        }
+    }
+
+    /// A method to reduce the remaining time of the running timers by the
+    /// provided time.
+    ///
+    pub fn advance_timers(&mut self, elapsed: Duration) {
+        for saved in self.statuses.values_mut().filter(|saved| saved.is_running) {
+            saved.remaining = saved
+                .remaining
+                .map(|remaining| remaining.saturating_sub(elapsed));
+        }
+    }
+}
+
+/// A structure which holds a reference to the backup storage (if it exists)
+/// and syncronizes local data to and from the storage.
+///
+/// # Notes
+///
+/// When created, the status handler will attempt to open the requested
+/// backup storage. If the status handler cannot open the storage, the status
+/// handler will raise an error and return none.
+///
+/// The backup is not removed when the handler is dropped (so that it survives
+/// a crash or panic). Call clear_backup() when the game has ended.
+///
+pub struct BackupHandler {
+    namespace: String, // the prefix and identifier which begin every key of this instance
+    storage: Option<Box<dyn Storage>>, // the backup storage, if it exists
+    internal_send: InternalSend, // the update line for posting any warnings
+    snapshot: GameSnapshot, // the current snapshot of the game state
+    queue_time: NaiveDateTime, // the clock time when the queue in the snapshot was last updated
+    status_time: NaiveDateTime, // the clock time when the statuses in the snapshot were last updated
+    is_held: bool, // a flag to keep the previous snapshot until the operator decides whether to restore it
+    is_standby: bool, // a flag to indicate that another instance writes the backup
+}
+
+// Implement key features for the status handler
+impl BackupHandler {
+    /// A function to create and return a new backup handler. Every key is
+    /// namespaced with the provided prefix and identifier, and every value
+    /// expires after the time-to-live (if specified).
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to open the backup
+    /// storage provided.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line and returning
+    /// None.
+    ///
+    pub async fn new(
+        internal_send: InternalSend,
+        identifier: Identifier,
+        backup_storage: Option<BackupStorage>,
+        prefix: &str,
+        ttl: Option<Duration>,
+    ) -> Result<BackupHandler, Error> {
+        // If a backup storage was specified, try to open the storage
+        let storage = match backup_storage {
+            Some(settings) => match open_storage(&settings, ttl).await {
+                Ok(storage) => Some(storage),
+
+                // Indicate that there was a failure to open the storage
+                Err(error) => {
+                    log!(err &internal_send => "Unable To Connect To Backup Storage: {:?} ({}).", settings, error);
+                    return Err(format_err!(
+                        "Unable To Connect To Backup Storage: {:?}.",
+                        settings
+                    ));
+                }
+            },
+
+            // Otherwise, continue without a storage
+            None => None,
+        };
+
+        // Return the new backup handler
+        Ok(BackupHandler {
+            namespace: format!("{}:{}:", prefix, identifier),
+            storage,
+            internal_send,
+            snapshot: GameSnapshot::new(),
+            queue_time: Clock::now(),
+            status_time: Clock::now(),
+            is_held: false,
+            is_standby: false,
+        })
+    }
+
+    /// A method to backup the current scene of the system
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to write to the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    pub async fn backup_current_scene(&mut self, current_scene: &ItemId) {
+        // Do nothing while another instance writes the backup
+        if self.is_standby {
+            return;
+        }
+
+        // If the storage exists
+        if let Some(storage) = self.storage.as_mut() {
+            // Try to copy the current scene to the storage
+            let result = storage.set(
+                &format!("{}current", self.namespace),
+                &format!("{}", current_scene.id()),
+            ).await;
+
+            // Unpack the result from the operation
+            if let Err(..) = result {
+                // Warn that it wasn't possible to update the current scene
+                log!(err self.internal_send => "Unable To Backup Current Scene Onto Backup Server.");
+            }
+        }
+
+        // Update the game snapshot
+        self.snapshot.current_scene = Some(*current_scene);
+        self.write_snapshot().await;
+    }
+
+    /// A method to backup a status state on the backup server based on the
+    /// provided status id and new state (and the value, for a variable).
+    ///
+    /// # Note
+    ///
+    /// As the backup handler does not hold a copy of the status map, this
+    /// method does not verify the validity of the new state in any way.
+    /// It is expected that the calling module will perform this check.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to write to the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    pub async fn backup_status(
+        &mut self,
+        status_id: &ItemId,
+        new_state: &ItemId,
+        value: Option<i64>,
+    ) {
+        // Do nothing while another instance writes the backup
+        if self.is_standby {
+            return;
+        }
+
+        // If the storage exists
+        if let Some(storage) = self.storage.as_mut() {
+            // Try to copy the state to the storage
+            let mut result = storage.set(
+                &format!("{}{}", self.namespace, status_id),
+                &format!("{}", new_state.id()),
+            ).await;
+
+            // Try to copy the value of a variable to the storage
+            if let (Ok(..), Some(value)) = (&result, value) {
+                result = storage.set(
+                    &format!("{}{}:value", self.namespace, status_id),
+                    &format!("{}", value),
+                ).await;
+            }
+
+            // Warn that the particular status was not set
+            if let Err(..) = result {
+                log!(warn &self.internal_send => "Unable To Backup Status Onto Backup Server: {}.", status_id);
+            }
+        }
+    }
+
+    /// A method to backup the event queue on the backup server based on the
+    /// provided coming events and whether the queue is paused
+    ///
+    /// # Note
+    ///
+    /// As the backup handler does not hold a copy of the configuration, this
+    /// method does not verify the validity of the event queue in any way.
+    /// It is expected that the calling module will perform this check.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to write to the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    pub async fn backup_events(&mut self, coming_events: Vec<ComingEvent>, is_paused: bool) {
+        // Do nothing while another instance writes the backup
+        if self.is_standby {
+            return;
+        }
+
+        // Covert the coming events to queued events
+        let mut queued_events = Vec::new();
+        for event in coming_events {
+            // Convert each event to a queued event
+            if let Some(remaining) = event.remaining() {
+                queued_events.push(QueuedEvent {
+                    remaining,
+                    event_id: event.id(),
+                    is_held: event.is_held,
+                    repeat: event.repeat,
+                    due: event.schedule.map(|_| event.trigger_time()),
+                    priority: event.priority,
+                });
+            }
+        }
+
+        // If the storage exists
+        if let Some(storage) = self.storage.as_mut() {
+            // Try to serialize the coming events
+            let event_string = match serde_yaml::to_string(&queued_events) {
+                Ok(string) => string,
+                Err(error) => {
+                    log!(err &self.internal_send => "Unable To Parse Coming Events: {}", error);
+                    return;
+                }
+            };
+
+            // Try to copy the event to the storage
+            let result = storage.set(&format!("{}queue", self.namespace), &event_string).await;
+
+            // Try to copy the paused state to the storage
+            let paused_result = storage.set(
+                &format!("{}paused", self.namespace),
+                &format!("{}", is_paused),
+            ).await;
+
+            // Warn that the event queue was not set
+            if result.is_err() || paused_result.is_err() {
+                log!(warn &self.internal_send => "Unable To Backup Events Onto Backup Server.");
+            }
+        }
+
+        // Update the game snapshot
+        self.snapshot.queue = queued_events;
+        self.snapshot.is_paused = is_paused;
+        self.queue_time = Clock::now();
+        self.write_snapshot().await;
+    }
+
+    /// A method to backup the complete state of every status in the game
+    /// snapshot.
+    ///
+    pub async fn backup_statuses(&mut self, statuses: SavedStatusMap) {
+        self.snapshot.statuses = statuses;
+        self.status_time = Clock::now();
+        self.write_snapshot().await;
+    }
+
+    /// A method to backup the state of the dmx and media outputs in the game
+    /// snapshot.
+    ///
+    pub async fn backup_output(&mut self, output: OutputState) {
+        self.snapshot.output = output;
+        self.write_snapshot().await;
+    }
+
+    /// An internal method to write the game snapshot to the backup storage.
+    /// The remaining times of the queue and the timers are recalculated for
+    /// the time of writing. The snapshot is not written until the game has
+    /// started or while the previous snapshot is held.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to write to the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    async fn write_snapshot(&mut self) {
+        // If the storage exists and the snapshot should be written
+        let storage = match self.storage.as_mut() {
+            Some(storage)
+                if !self.is_held && !self.is_standby && self.snapshot.current_scene.is_some() =>
+            {
+                storage
+            }
+            _ => return,
+        };
+
+        // Update the remaining times to the time of writing
+        let mut snapshot = self.snapshot.clone();
+        snapshot.advance_queue(Clock::elapsed(self.queue_time));
+        snapshot.advance_timers(Clock::elapsed(self.status_time));
+        snapshot.saved = Clock::now();
+
+        // Try to serialize the snapshot
+        let snapshot_string = match serde_yaml::to_string(&snapshot) {
+            Ok(string) => string,
+            Err(error) => {
+                log!(err &self.internal_send => "Unable To Parse Game Snapshot: {}", error);
+                return;
+            }
+        };
+
+        // Try to copy the snapshot to the storage (as a single value)
+        let result = storage.set(&format!("{}snapshot", self.namespace), &snapshot_string).await;
+        if result.is_err() {
+            log!(warn &self.internal_send => "Unable To Backup Game Snapshot Onto Backup Server.");
+        }
+    }
+
+    /// A method to reload the previous game snapshot from the backup server,
+    /// if it exists. The previous snapshot is held in the backup storage
+    /// until it is released.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise a warning if the snapshot is unreadable or
+    /// was saved by a newer version of the program.
+    ///
+    pub async fn reload_snapshot(&mut self) -> Option<GameSnapshot> {
+        // Try to read the snapshot
+        let snapshot = self.read_snapshot().await?;
+
+        // Hold the snapshot and return it
+        self.is_held = true;
+        Some(snapshot)
+    }
+
+    /// A method to read the current game snapshot from the backup server, if
+    /// it exists, without holding it (used to mirror another instance).
+    ///
+    /// # Errors
+    ///
+    /// This function will raise a warning if the snapshot is unreadable or
+    /// was saved by a newer version of the program.
+    ///
+    pub async fn read_snapshot(&mut self) -> Option<GameSnapshot> {
+        // If the storage exists, try to read the snapshot
+        let storage = self.storage.as_mut()?;
+        let result = storage.get(&format!("{}snapshot", self.namespace)).await;
+        let snapshot_string = result.ok()??;
+
+        // Try to parse the snapshot
+        let snapshot: GameSnapshot = match serde_yaml::from_str(&snapshot_string) {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                log!(warn &self.internal_send => "Unable To Read Game Snapshot: {}", error);
+                return None;
+            }
+        };
+
+        // Check the version and that the game had started
+        if snapshot.version > SNAPSHOT_VERSION {
+            log!(warn &self.internal_send => "Game Snapshot Version Not Supported: {}.", snapshot.version);
+            return None;
+        }
+        snapshot.current_scene?;
+        Some(snapshot)
+    }
+
+    /// A method to release the previous game snapshot (after it was restored
+    /// or discarded), replacing it with the current snapshot.
+    ///
+    pub async fn release_snapshot(&mut self) {
+        self.is_held = false;
+        self.write_snapshot().await;
+    }
+
+    /// A method to change whether another instance writes the backup. While
+    /// in standby, nothing is written to the backup storage.
+    ///
+    pub fn set_standby(&mut self, is_standby: bool) {
+        self.is_standby = is_standby;
+    }
+
+    /// A method to write a new heartbeat to the backup storage, to show that
+    /// this instance is still running.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to write to the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    pub async fn backup_heartbeat(&mut self) {
+        // If the storage exists and this instance writes the backup
+        if let (Some(storage), false) = (self.storage.as_mut(), self.is_standby) {
+            // Try to write the current time as the heartbeat
+            let result = storage
+                .set(
+                    &format!("{}heartbeat", self.namespace),
+                    &format!("{}", Clock::now()),
+                )
+                .await;
+            if result.is_err() {
+                log!(warn &self.internal_send => "Unable To Write Heartbeat To Backup Server.");
+            }
+        }
+    }
+
+    /// A method to read the latest heartbeat from the backup storage, if it
+    /// exists.
+    ///
+    pub async fn reload_heartbeat(&mut self) -> Option<String> {
+        // If the storage exists, try to read the heartbeat
+        let storage = self.storage.as_mut()?;
+        storage
+            .get(&format!("{}heartbeat", self.namespace))
+            .await
+            .ok()?
+    }
+
+    /// A method to clear the complete backup of this instance (when the game
+    /// has ended). The game snapshot is not written again until the next
+    /// scene begins.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to clear the backup
+    /// storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    pub async fn clear_backup(&mut self) {
+        // Do nothing while another instance writes the backup
+        if self.is_standby {
+            return;
+        }
+
+        // Reset the game snapshot
+        self.snapshot = GameSnapshot::new();
+        self.is_held = false;
+
+        // If the storage exists, try to delete every key of this instance
+        if let Some(storage) = self.storage.as_mut() {
+            if let Err(error) = storage.clear(&self.namespace).await {
+                log!(err &self.internal_send => "Unable To Clear Backup Storage: {}.", error);
+            }
+        }
+    }
+
+    /// A method to backup whether the schedule is enabled.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to write to the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line.
+    ///
+    pub async fn backup_schedule(&mut self, is_enabled: bool) {
+        // Do nothing while another instance writes the backup
+        if self.is_standby {
+            return;
+        }
+
+        // If the storage exists
+        if let Some(storage) = self.storage.as_mut() {
+            // Try to copy the schedule setting to the storage
+            let result = storage.set(
+                &format!("{}schedule", self.namespace),
+                &format!("{}", is_enabled),
+            ).await;
+
+            // Warn that the schedule setting was not set
+            if result.is_err() {
+                log!(warn &self.internal_send => "Unable To Backup Schedule Onto Backup Server.");
+            }
+        }
+    }
+
+    /// A method to reload whether the schedule is enabled from the backup
+    /// server. Returns None if there is no backup of the setting.
+    ///
+    pub async fn reload_schedule(&mut self) -> Option<bool> {
+        // If the storage exists, try to read the schedule setting
+        let storage = self.storage.as_mut()?;
+        let result = storage.get(&format!("{}schedule", self.namespace)).await;
+        result.ok()??.parse().ok()
+    }
+
+    /// A method to reload the values of any variables from the backup server.
+    /// Returns the status ids and values which were found in the backup.
+    ///
+    pub async fn reload_values(&mut self, status_ids: Vec<ItemId>) -> Vec<(ItemId, i64)> {
+        // If the storage exists
+        let mut value_pairs = Vec::new();
+        if let Some(storage) = self.storage.as_mut() {
+            // Try to read an existing value for each status
+            for status_id in status_ids {
+                let result = storage.get(&format!("{}{}:value", self.namespace, status_id)).await;
+
+                // If a value was found, add it to the value pairs
+                if let Ok(Some(value_str)) = result {
+                    if let Ok(value) = value_str.parse::<i64>() {
+                        value_pairs.push((status_id, value));
+                    }
+                }
+            }
+        }
+        value_pairs
+    }
+
+    /// A function to reload an existing backup from the backup server. If the
+    /// data exists, this function returns the existing backup data.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to read from the
+    /// backup storage.
+    ///
+    /// Like all BackupHandler functions and methods, this function will fail
+    /// gracefully by notifying of any errors on the update line and returning
+    /// None.
+    ///
+    pub async fn reload_backup(
+        &mut self,
+        mut status_ids: Vec<ItemId>,
+    ) -> Option<(ItemId, Vec<(ItemId, ItemId)>, Vec<QueuedEvent>, bool)> {
+        // If the storage exists
+        if let Some(storage) = self.storage.as_mut() {
+            // Check to see if there is an existing scene
+            let result = storage.get(&format!("{}current", self.namespace)).await;
+
+            // If the current scene exists
+            if let Ok(Some(current_str)) = result {
+                // Try to read the exising event queue
+                let mut queued_events: Vec<QueuedEvent> = Vec::new();
+                let result = storage.get(&format!("{}queue", self.namespace)).await;
+
+                // If something was received
+                if let Ok(Some(queue_string)) = result {
+                    // Try to parse the queue
+                    if let Ok(events) = serde_yaml::from_str(queue_string.as_str()) {
+                        queued_events = events;
+                    }
+                }
+
+                // Check whether the queue was paused
+                let result = storage.get(&format!("{}paused", self.namespace)).await;
+                let is_paused = match result {
+                    Ok(Some(paused_str)) => paused_str.parse().unwrap_or(false),
+                    _ => false,
+                };
+
+                // Compile a list of valid status pairs
+                let mut status_pairs: Vec<(ItemId, ItemId)> = Vec::new();
+                for status_id in status_ids.drain(..) {
+                    // Try to read an existing status from the backup
+                    let result = storage.get(&format!("{}{}", self.namespace, status_id)).await;
+
+                    // If something was received
+                    if let Ok(Some(state_str)) = result {
+                        // Try to parse the current state id
+                        if let Ok(state_id) = state_str.parse::<u32>() {
+                            // Try to compose the id into an item
+                            if let Some(new_state) = ItemId::new(state_id) {
+                                // Add the status id and new state to the status pairs
+                                status_pairs.push((status_id, new_state));
+                            }
+                        }
+                    }
+                }
+
+                // Try to parse the current scene id
+                if let Ok(current_id) = current_str.parse::<u32>() {
+                    // Try to compose the id into an item
+                    if let Some(current_scene) = ItemId::new(current_id) {
+                        // Return the current scene, status pairs, and queue
+                        return Some((current_scene, status_pairs, queued_events, is_paused));
+                    }
+                }
+            }
+        }
+
+        // Silently return nothing if the storage does not exist or there was not a current scene
+        None
+    }
+}
+
+// Tests of the status module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // FIXME Define tests of this module
+    #[tokio::test]
+    async fn backup_game() {
+        // Import libraries for testing
+        use crate::definitions::{Identifier, InternalSend, InternalUpdate};
+
+        // Create a channel for receiving messages from the backup handler
+        let (internal_send, mut rx) = InternalSend::new();
+
+        // Create the backup handler
+        let mut backup_handler = BackupHandler::new(
+            internal_send,
+            Identifier { id: None },
+            Some(BackupStorage::Redis {
+                location: "redis://127.0.0.1:6379".to_string(),
+            }),
+            "minerva",
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Make sure there is no existing backup
+        if let Some(_) = backup_handler.reload_backup(Vec::new()).await {
+            panic!("Backup already existed before beginning of the test.");
+        }
+
+        // Create the current scene and status pairs
+        let current_scene = ItemId::new_unchecked(10);
+        let status1 = ItemId::new_unchecked(11);
+        let state1 = ItemId::new_unchecked(12);
+        let status2 = ItemId::new_unchecked(13);
+        let state2 = ItemId::new_unchecked(14);
+
+        // Backup the current scene, statuses (unable to easily test coming events)
+        backup_handler.backup_current_scene(&current_scene).await;
+        backup_handler.backup_status(&status1, &state1, None).await;
+        backup_handler.backup_status(&status2, &state2, None).await;
+
+        // Reload the backup
+        if let Some((reload_scene, statuses, _, _)) =
+            backup_handler.reload_backup(vec![status1, status2]).await
+        {
+            assert_eq!(current_scene, reload_scene);
+            assert_eq!(vec!((status1, state1), (status2, state2)), statuses);
+
+        // If the backup doesn't exist, throw the error
+        } else {
+            panic!("Backup was not reloaded.");
+        }
+
+        // End the game and make sure the backup was cleared
+        backup_handler.clear_backup().await;
+        assert!(backup_handler.reload_backup(Vec::new()).await.is_none());
+
+        // Make sure no messages were received (wait at most half a second)
+        let empty: Vec<InternalUpdate> = Vec::new();
+        test_vec!(=rx, empty);
+    }
+
+    // Test the backup with the in-memory storage
+    #[tokio::test]
+    async fn backup_in_memory() {
+        // Import libraries for testing
+        use crate::definitions::{Identifier, InternalSend};
+
+        // Create the backup handler
+        let (internal_send, _rx) = InternalSend::new();
+        let mut backup_handler = BackupHandler::new(
+            internal_send,
+            Identifier { id: Some(1) },
+            Some(BackupStorage::Memory),
+            "minerva",
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Make sure there is no existing backup
+        assert!(backup_handler.reload_backup(Vec::new()).await.is_none());
+        assert_eq!(None, backup_handler.reload_schedule().await);
+
+        // Create the current scene and status pairs
+        let current_scene = ItemId::new_unchecked(10);
+        let status1 = ItemId::new_unchecked(11);
+        let state1 = ItemId::new_unchecked(12);
+        let variable = ItemId::new_unchecked(13);
+        let state2 = ItemId::new_unchecked(14);
+
+        // Backup the current scene, statuses, queue, and schedule setting
+        backup_handler.backup_current_scene(&current_scene).await;
+        backup_handler.backup_status(&status1, &state1, None).await;
+        backup_handler.backup_status(&variable, &state2, Some(-5)).await;
+        backup_handler.backup_events(Vec::new(), true).await;
+        backup_handler.backup_schedule(false).await;
+
+        // Reload the backup
+        let (reload_scene, statuses, events, is_paused) = backup_handler
+            .reload_backup(vec![status1, variable]).await
+            .unwrap();
+        assert_eq!(current_scene, reload_scene);
+        assert_eq!(vec!((status1, state1), (variable, state2)), statuses);
+        assert!(events.is_empty());
+        assert!(is_paused);
+        assert_eq!(
+            vec!((variable, -5)),
+            backup_handler.reload_values(vec![status1, variable]).await
+        );
+        assert_eq!(Some(false), backup_handler.reload_schedule().await);
+
+        // End the game and make sure the backup was cleared
+        backup_handler.clear_backup().await;
+        assert!(backup_handler.reload_backup(Vec::new()).await.is_none());
+        assert!(backup_handler.reload_values(vec![variable]).await.is_empty());
+        assert_eq!(None, backup_handler.reload_schedule().await);
+    }
+
+    // Test saving, holding, and releasing the game snapshot
+    #[tokio::test]
+    async fn game_snapshot() {
+        // Import libraries for testing
+        use crate::definitions::{Identifier, InternalSend};
+
+        // Create the backup handler
+        let (internal_send, _rx) = InternalSend::new();
+        let mut backup_handler = BackupHandler::new(
+            internal_send,
+            Identifier { id: Some(2) },
+            Some(BackupStorage::Memory),
+            "minerva",
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Create the game state
+        let scene1 = ItemId::new_unchecked(10);
+        let scene2 = ItemId::new_unchecked(11);
+        let status = ItemId::new_unchecked(12);
+        let mut statuses = SavedStatusMap::default();
+        statuses.insert(
+            status,
+            SavedStatus {
+                current: ItemId::new_unchecked(13),
+                count: Some(3),
+                value: None,
+                remaining: None,
+                is_running: false,
+            },
+        );
+        let output = OutputState {
+            dmx_levels: vec![DmxFade {
+                universe: Some(1),
+                channel: 5,
+                value: 127,
+                duration: None,
+                curve: None,
+            }],
+            media_cues: vec![MediaCue {
+                uri: "file:///media/intro.mp4".to_string(),
+                channel: 1,
+                loop_media: None,
+            }],
+        };
+
+        // Check that nothing is saved until the game has started
+        backup_handler.backup_statuses(statuses.clone()).await;
+        backup_handler.backup_output(output.clone()).await;
+        backup_handler.backup_events(Vec::new(), true).await;
+        assert_eq!(None, backup_handler.reload_snapshot().await);
+
+        // Start the game and reload the snapshot
+        backup_handler.backup_current_scene(&scene1).await;
+        let snapshot = backup_handler.reload_snapshot().await.unwrap();
+        assert_eq!(SNAPSHOT_VERSION, snapshot.version);
+        assert_eq!(Some(scene1), snapshot.current_scene);
+        assert_eq!(statuses, snapshot.statuses);
+        assert!(snapshot.queue.is_empty());
+        assert!(snapshot.is_paused);
+        assert_eq!(output, snapshot.output);
+
+        // Check that the snapshot is held until it is released
+        backup_handler.backup_current_scene(&scene2).await;
+        let held = backup_handler.reload_snapshot().await.unwrap();
+        assert_eq!(Some(scene1), held.current_scene);
+        backup_handler.release_snapshot().await;
+        let released = backup_handler.reload_snapshot().await.unwrap();
+        assert_eq!(Some(scene2), released.current_scene);
+
+        // Check that the snapshot is cleared when the game ends
+        backup_handler.clear_backup().await;
+        assert_eq!(None, backup_handler.reload_snapshot().await);
+        backup_handler.backup_statuses(statuses).await;
+        assert_eq!(None, backup_handler.reload_snapshot().await);
+    }
+
+    // Test that nothing is written in standby and that the snapshot can be mirrored
+    #[tokio::test]
+    async fn standby_mirror() {
+        // Import libraries for testing
+        use crate::definitions::{Identifier, InternalSend};
+
+        // Create the backup handler
+        let (internal_send, _rx) = InternalSend::new();
+        let mut backup_handler = BackupHandler::new(
+            internal_send,
+            Identifier { id: Some(3) },
+            Some(BackupStorage::Memory),
+            "minerva",
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Check that nothing is written in standby
+        let scene1 = ItemId::new_unchecked(10);
+        let scene2 = ItemId::new_unchecked(11);
+        backup_handler.set_standby(true);
+        backup_handler.backup_current_scene(&scene1).await;
+        backup_handler.backup_heartbeat().await;
+        assert_eq!(None, backup_handler.read_snapshot().await);
+        assert_eq!(None, backup_handler.reload_heartbeat().await);
+
+        // Check that the heartbeat and snapshot are written once active
+        backup_handler.set_standby(false);
+        backup_handler.backup_current_scene(&scene1).await;
+        backup_handler.backup_heartbeat().await;
+        assert!(backup_handler.reload_heartbeat().await.is_some());
+        let mirror = backup_handler.read_snapshot().await.unwrap();
+        assert_eq!(Some(scene1), mirror.current_scene);
+
+        // Check that reading the snapshot does not hold it
+        backup_handler.backup_current_scene(&scene2).await;
+        let mirror = backup_handler.read_snapshot().await.unwrap();
+        assert_eq!(Some(scene2), mirror.current_scene);
+    }
+
+    // Test that the remaining times are current when the snapshot is written
+    #[tokio::test(start_paused = true)]
+    async fn snapshot_remaining() {
+        // Import libraries for testing
+        use crate::definitions::{Identifier, InternalSend};
+        use tokio::time::advance;
+
+        // Create the backup handler on the virtual clock
+        Clock::set_virtual(1.0);
+        let (internal_send, _rx) = InternalSend::new();
+        let mut backup_handler = BackupHandler::new(
+            internal_send,
+            Identifier { id: Some(4) },
+            Some(BackupStorage::Memory),
+            "minerva",
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Backup a queued event and a running timer
+        let scene = ItemId::new_unchecked(10);
+        let event = ItemId::new_unchecked(11);
+        let timer = ItemId::new_unchecked(12);
+        let mut statuses = SavedStatusMap::default();
+        statuses.insert(
+            timer,
+            SavedStatus {
+                current: ItemId::new_unchecked(13),
+                count: None,
+                value: None,
+                remaining: Some(Duration::from_secs(90)),
+                is_running: true,
+            },
+        );
+        backup_handler.backup_current_scene(&scene).await;
+        backup_handler
+            .backup_events(vec![ComingEvent::new(Duration::from_secs(60), event)], false)
+            .await;
+        backup_handler.backup_statuses(statuses).await;
+
+        // Write the snapshot again later and check the remaining times
+        advance(Duration::from_secs(20)).await;
+        backup_handler.backup_output(OutputState::default()).await;
+        let snapshot = backup_handler.read_snapshot().await.unwrap();
+        assert_eq!(Duration::from_secs(40), snapshot.queue[0].remaining);
+        assert_eq!(
+            Some(Duration::from_secs(70)),
+            snapshot.statuses[&timer].remaining
+        );
+
+        // Check the age of the snapshot
+        advance(Duration::from_secs(5)).await;
+        assert_eq!(Duration::from_secs(5), snapshot.age());
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module defines the storage backends for the backup handler. Each
+//! backend stores string values by string keys: on a Redis server, in a local
+//! append-only file, or in memory. If a time-to-live is specified, each value
+//! expires that long after it was last set.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Import Chrono features
+use chrono::{Local, NaiveDateTime};
+
+// Import the failure features
+use failure::Error;
+
+// Import the async trait features
+use async_trait::async_trait;
+
+// Imprt redis client library
+use redis::AsyncCommands;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Define module constants
+const COMPACT_LIMIT: usize = 1000; // the number of entries to append before the file is compacted
+
+/// A trait for the storage backends of the backup handler
+///
+#[async_trait]
+pub trait Storage: Send {
+    /// A method to read the value of the key, if it exists and has not expired
+    ///
+    async fn get(&mut self, key: &str) -> Result<Option<String>, Error>;
+
+    /// A method to set the value of the key
+    ///
+    async fn set(&mut self, key: &str, value: &str) -> Result<(), Error>;
+
+    /// A method to delete the key, if it exists
+    ///
+    async fn delete(&mut self, key: &str) -> Result<(), Error>;
+
+    /// A method to delete every key which begins with the provided prefix
+    ///
+    async fn clear(&mut self, prefix: &str) -> Result<(), Error>;
+}
+
+/// A function to open the storage backend described by the provided settings,
+/// with the provided time-to-live for each value (if specified).
+///
+/// # Errors
+///
+/// This function will raise an error if it is unable to connect to the Redis
+/// server or to open the backup file.
+///
+pub async fn open_storage(
+    settings: &BackupStorage,
+    ttl: Option<Duration>,
+) -> Result<Box<dyn Storage>, Error> {
+    match settings {
+        BackupStorage::Redis { location } => {
+            Ok(Box::new(RedisStorage::new(location, ttl).await?))
+        }
+        BackupStorage::File { path } => Ok(Box::new(FileStorage::new(path, ttl)?)),
+        BackupStorage::Memory => Ok(Box::new(MemoryStorage::new(ttl))),
+    }
+}
+
+/// A helper function to calculate when a value set now will expire, if it
+/// expires. Like Redis, the expiration follows the local time rather than
+/// the show clock.
+///
+fn expires(ttl: Option<Duration>) -> Option<NaiveDateTime> {
+    ttl.and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+        .map(|ttl| Local::now().naive_local() + ttl)
+}
+
+/// A helper function to check whether a value with the provided expiration
+/// is still current
+///
+fn is_current(expires: &Option<NaiveDateTime>) -> bool {
+    match expires {
+        Some(expires) => *expires > Local::now().naive_local(),
+        None => true,
+    }
+}
+
+/// A storage backend which keeps the backup on a Redis server
+///
+pub struct RedisStorage {
+    connection: redis::aio::Connection, // the async connection to the Redis server
+    ttl: Option<usize>,                 // the time-to-live of each value in seconds, if specified
+}
+
+// Implement key features of the Redis storage
+impl RedisStorage {
+    /// A function to connect to the Redis server at the provided location
+    ///
+    pub async fn new(location: &str, ttl: Option<Duration>) -> Result<RedisStorage, Error> {
+        let client = redis::Client::open(location)?;
+        Ok(RedisStorage {
+            connection: client.get_async_connection().await?,
+            ttl: ttl.map(|ttl| std::cmp::max(ttl.as_secs() as usize, 1)),
+        })
+    }
+}
+
+// Implement the storage trait for the Redis storage
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn get(&mut self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self.connection.get(key).await?)
+    }
+
+    async fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        // Let Redis expire the value, if a time-to-live is specified
+        match self.ttl {
+            Some(seconds) => Ok(self.connection.set_ex(key, value, seconds).await?),
+            None => Ok(self.connection.set(key, value).await?),
+        }
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<(), Error> {
+        Ok(self.connection.del(key).await?)
+    }
+
+    async fn clear(&mut self, prefix: &str) -> Result<(), Error> {
+        // Collect the matching keys (without the wildcard characters of the prefix)
+        let pattern = format!("{}*", prefix.replace('*', "\\*").replace('?', "\\?"));
+        let mut keys: Vec<String> = Vec::new();
+        {
+            let mut iter = self.connection.scan_match::<_, String>(pattern).await?;
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+        }
+
+        // Delete the keys, if there are any
+        if !keys.is_empty() {
+            let _: () = self.connection.del(keys).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A structure to hold a single entry of the append-only file. An entry
+/// without a value deletes the key.
+///
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    key: String,           // the key of the entry
+    value: Option<String>, // the new value of the key, if it was not deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<NaiveDateTime>, // the time the value expires, if it expires
+}
+
+/// A storage backend which keeps the backup in a local, append-only file.
+/// Every change is appended to the file as it happens, and the file is
+/// compacted when it is opened and after every COMPACT_LIMIT changes.
+///
+pub struct FileStorage {
+    path: PathBuf,                      // the location of the backup file
+    file: File,                         // the backup file, open for appending
+    values: FnvHashMap<String, (String, Option<NaiveDateTime>)>, // the current values in the file and when they expire
+    appended: usize,                    // the number of entries appended since the file was compacted
+    ttl: Option<Duration>,              // the time-to-live of each value, if specified
+}
+
+// Implement key features of the file storage
+impl FileStorage {
+    /// A function to open (or create) the backup file at the provided path.
+    /// Any existing entries are reloaded (dropping any expired values) and the
+    /// file is compacted.
+    ///
+    pub fn new(path: &Path, ttl: Option<Duration>) -> Result<FileStorage, Error> {
+        // Replay any existing entries (ignoring a partially written last line)
+        let mut values = FnvHashMap::default();
+        if let Ok(file) = File::open(path) {
+            for line in BufReader::new(file).lines() {
+                if let Ok(entry) = serde_json::from_str::<FileEntry>(&line?) {
+                    match entry.value {
+                        Some(value) => values.insert(entry.key, (value, entry.expires)),
+                        None => values.remove(&entry.key),
+                    };
+                }
+            }
+        }
+        values.retain(|_, (_, expires)| is_current(expires));
+
+        // Compact the file and open it for appending
+        let file = FileStorage::compact_file(path, &values)?;
+        Ok(FileStorage {
+            path: PathBuf::from(path),
+            file,
+            values,
+            appended: 0,
+            ttl,
+        })
+    }
+
+    /// A helper function to write the current values to a new file, replace
+    /// the old file, and return the new file open for appending
+    ///
+    fn compact_file(
+        path: &Path,
+        values: &FnvHashMap<String, (String, Option<NaiveDateTime>)>,
+    ) -> Result<File, Error> {
+        // Write the current values to a new file and replace the old file
+        let mut compact_path = PathBuf::from(path);
+        compact_path.set_extension("compact");
+        {
+            let mut compact = File::create(&compact_path)?;
+            for (key, (value, expires)) in values.iter() {
+                FileStorage::write_entry(&mut compact, key, Some(value), *expires)?;
+            }
+            compact.sync_all()?;
+        }
+        std::fs::rename(&compact_path, path)?;
+
+        // Open the file for appending
+        Ok(OpenOptions::new().append(true).open(path)?)
+    }
+
+    /// A helper method to note a new entry in the file, compacting the file
+    /// when it has grown too long
+    ///
+    fn note_entry(&mut self) -> Result<(), Error> {
+        self.appended += 1;
+        if self.appended >= COMPACT_LIMIT {
+            self.file = FileStorage::compact_file(&self.path, &self.values)?;
+            self.appended = 0;
+        }
+        Ok(())
+    }
+
+    /// A helper function to append an entry to the file
+    ///
+    fn write_entry(
+        file: &mut File,
+        key: &str,
+        value: Option<&str>,
+        expires: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        let entry = FileEntry {
+            key: key.to_string(),
+            value: value.map(|value| value.to_string()),
+            expires,
+        };
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+// Implement the storage trait for the file storage
+#[async_trait]
+impl Storage for FileStorage {
+    async fn get(&mut self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self
+            .values
+            .get(key)
+            .filter(|(_, expires)| is_current(expires))
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        // Append the change to the file before updating the values
+        let expires = expires(self.ttl);
+        FileStorage::write_entry(&mut self.file, key, Some(value), expires)?;
+        self.file.sync_data()?;
+        self.values
+            .insert(key.to_string(), (value.to_string(), expires));
+        self.note_entry()
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<(), Error> {
+        // Only record the deletion if the key exists
+        if self.values.remove(key).is_some() {
+            FileStorage::write_entry(&mut self.file, key, None, None)?;
+            self.file.sync_data()?;
+            self.note_entry()?;
+        }
+        Ok(())
+    }
+
+    async fn clear(&mut self, prefix: &str) -> Result<(), Error> {
+        // Delete each of the matching keys
+        let keys: Vec<String> = self
+            .values
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        for key in keys {
+            self.delete(&key).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A storage backend which keeps the backup in memory
+///
+pub struct MemoryStorage {
+    values: FnvHashMap<String, (String, Option<NaiveDateTime>)>, // the current values and when they expire
+    ttl: Option<Duration>, // the time-to-live of each value, if specified
+}
+
+// Implement key features of the memory storage
+impl MemoryStorage {
+    /// A function to create a new, empty memory storage
+    ///
+    pub fn new(ttl: Option<Duration>) -> MemoryStorage {
+        MemoryStorage {
+            values: FnvHashMap::default(),
+            ttl,
+        }
+    }
+}
+
+// Implement the storage trait for the memory storage
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn get(&mut self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self
+            .values
+            .get(key)
+            .filter(|(_, expires)| is_current(expires))
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        self.values
+            .insert(key.to_string(), (value.to_string(), expires(self.ttl)));
+        Ok(())
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<(), Error> {
+        self.values.remove(key);
+        Ok(())
+    }
+
+    async fn clear(&mut self, prefix: &str) -> Result<(), Error> {
+        self.values.retain(|key, _| !key.starts_with(prefix));
+        Ok(())
+    }
+}
+
+// Tests of the storage module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the file storage survives being reopened
+    #[tokio::test]
+    async fn reopen_file() {
+        // Create a new backup file
+        let path = std::env::temp_dir().join(format!("minerva_backup_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut storage = FileStorage::new(&path, None).unwrap();
+
+        // Set, replace, and delete some values
+        storage.set("0:current", "10").await.unwrap();
+        storage.set("0:11", "12").await.unwrap();
+        storage.set("0:11", "13").await.unwrap();
+        storage.set("0:14", "15").await.unwrap();
+        storage.delete("0:14").await.unwrap();
+        assert_eq!(Some("13".to_string()), storage.get("0:11").await.unwrap());
+
+        // Simulate a crash with a partially written line
+        writeln!(storage.file, "{{\"key\": \"0:cur").unwrap();
+        drop(storage);
+
+        // Reopen the file and check the values
+        let mut storage = FileStorage::new(&path, None).unwrap();
+        assert_eq!(Some("10".to_string()), storage.get("0:current").await.unwrap());
+        assert_eq!(Some("13".to_string()), storage.get("0:11").await.unwrap());
+        assert_eq!(None, storage.get("0:14").await.unwrap());
+
+        // Check that the file was compacted
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(2, contents.lines().count());
+
+        // Check that the file is compacted as it grows
+        for count in 0..COMPACT_LIMIT {
+            storage.set("0:snapshot", &count.to_string()).await.unwrap();
+        }
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(3, contents.lines().count());
+        drop(storage);
+        let mut storage = FileStorage::new(&path, None).unwrap();
+        assert_eq!(
+            Some((COMPACT_LIMIT - 1).to_string()),
+            storage.get("0:snapshot").await.unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Test that values expire and that keys are cleared by prefix
+    #[tokio::test]
+    async fn expire_and_clear() {
+        // Create a memory storage with a short time-to-live
+        let mut storage = MemoryStorage::new(Some(Duration::from_millis(50)));
+        storage.set("minerva:0:current", "10").await.unwrap();
+        storage.set("minerva:1:current", "11").await.unwrap();
+        storage.set("other:0:current", "12").await.unwrap();
+
+        // Clear one instance and check that the others remain
+        storage.clear("minerva:0:").await.unwrap();
+        assert_eq!(None, storage.get("minerva:0:current").await.unwrap());
+        assert_eq!(
+            Some("11".to_string()),
+            storage.get("minerva:1:current").await.unwrap()
+        );
+        assert_eq!(
+            Some("12".to_string()),
+            storage.get("other:0:current").await.unwrap()
+        );
+
+        // Wait for the values to expire
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(None, storage.get("minerva:1:current").await.unwrap());
+        assert_eq!(None, storage.get("other:0:current").await.unwrap());
+    }
+}
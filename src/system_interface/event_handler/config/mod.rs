@@ -26,10 +26,11 @@ use crate::definitions::*;
 mod status;
 
 // Import the relevant structures into the correct namespace
-use self::status::StatusHandler;
+use self::status::{StatusHandler, TimerChanges};
 
 // Import standard library features
 use std::path::PathBuf;
+use std::time::Duration;
 
 // Import tokio features
 use tokio::fs::File;
@@ -46,6 +47,9 @@ use fnv::FnvHashMap;
 // Import YAML processing library
 use serde_yaml;
 
+// Define module constants
+const DEFAULT_BACKUP_PREFIX: &str = "minerva"; // the default prefix for every backup key
+
 /// A struct to define the elements of a background process
 ///
 #[derive(Clone, Serialize, Deserialize)]
@@ -168,6 +172,14 @@ struct YamlConfig {
     version: String,        // a version tag to warn the user of incompatible versions
     identifier: Identifier, // unique identifier for the controller instance, if specified
     server_location: Option<String>, // the location of the backup server, if specified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup_storage: Option<BackupStorage>, // the backup storage (replaces the server location), if specified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup_prefix: Option<String>, // the prefix for every backup key, if specified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup_ttl: Option<Duration>, // the time before an untouched backup expires, if specified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failover: Option<Failover>, // the failover settings for this instance, if specified
     system_connection: ConnectionSet, // the type of connection(s) to the underlying system
     background_process: Option<BackgroundProcess>, // an option background process to run
     default_scene: Option<ItemId>, // the starting scene for the configuration
@@ -176,6 +188,7 @@ struct YamlConfig {
     status_map: StatusMap,  // hash map of the default game status
     event_set: FnvHashMap<ItemPair, Option<Event>>, // hash map of all the item pairs and events
     user_styles: StyleMap, // A string representing arbitrary css for styling the user and edit interfaces
+    schedule: Option<Vec<ScheduledEvent>>, // the events to trigger at scheduled times, if specified
 } // Private struct to allow deserialization of the configuration
 
 /// A structure to hold the whole configuration for current instantiation of the
@@ -187,11 +200,16 @@ pub struct Config {
     identifier: Identifier, // unique identifier for the controller instance
     system_connection: ConnectionSet, // the type of connection(s) to the underlying system
     server_location: Option<String>, // the location of the backup server, if specified
+    backup_storage: Option<BackupStorage>, // the backup storage, if specified
+    backup_prefix: Option<String>, // the prefix for every backup key, if specified
+    backup_ttl: Option<Duration>, // the time before an untouched backup expires, if specified
+    failover: Option<Failover>, // the failover settings for this instance, if specified
     background_thread: Option<BackgroundThread>, // a copy of the background process info
     current_scene: ItemId,  // identifier for the current scene
     all_scenes: FnvHashMap<ItemId, Scene>, // hash map of all availble scenes
     status_handler: StatusHandler, // status handler for the current game status
     events: FnvHashMap<ItemId, Event>, // hash map of all the events
+    schedule: Vec<ScheduledEvent>, // the events to trigger at scheduled times
     index_access: IndexAccess, // access point to the item index
     style_access: StyleAccess, // access point to the style sheet
     internal_send: InternalSend, // line to provide updates to the higher-level system
@@ -290,6 +308,25 @@ impl Config {
         )
         .await;
 
+        // Verify that failover uses a Redis server (the only storage shared between instances)
+        let mut failover = yaml_config.failover;
+        let is_shared = matches!(
+            (&yaml_config.backup_storage, &yaml_config.server_location),
+            (Some(BackupStorage::Redis { .. }), _) | (None, Some(_))
+        );
+        if failover.is_some() && !is_shared {
+            log!(warn internal_send => "Failover Requires A Redis Backup Storage. Failover Disabled.");
+            failover = None;
+        }
+
+        // Verify that each scheduled event exists
+        let schedule = yaml_config.schedule.unwrap_or_default();
+        for scheduled in schedule.iter() {
+            if !events.contains_key(&scheduled.event) {
+                log!(warn internal_send => "Schedule Contains Invalid Event: {}", &scheduled.event);
+            }
+        }
+
         // Load the item index
         index_access.send_index(item_index).await;
 
@@ -331,11 +368,16 @@ impl Config {
             identifier: yaml_config.identifier,
             system_connection: yaml_config.system_connection,
             server_location: yaml_config.server_location,
+            backup_storage: yaml_config.backup_storage,
+            backup_prefix: yaml_config.backup_prefix,
+            backup_ttl: yaml_config.backup_ttl,
+            failover,
             background_thread,
             current_scene,
             all_scenes,
             status_handler,
             events,
+            schedule,
             index_access,
             style_access,
             internal_send,
@@ -360,9 +402,43 @@ impl Config {
         self.system_connection.clone()
     }
 
-    /// A method to return the backup server location
-    pub fn server_location(&self) -> Option<String> {
-        self.server_location.clone()
+    /// A method to return a copy of the scheduled events.
+    ///
+    pub fn get_schedule(&self) -> Vec<ScheduledEvent> {
+        self.schedule.clone()
+    }
+
+    /// A method to return the backup storage. If only a backup server location
+    /// is specified, the backup is stored on that Redis server.
+    pub fn backup_storage(&self) -> Option<BackupStorage> {
+        self.backup_storage.clone().or_else(|| {
+            self.server_location
+                .clone()
+                .map(|location| BackupStorage::Redis { location })
+        })
+    }
+
+    /// A method to return the prefix for every backup key (the default prefix
+    /// is "minerva").
+    ///
+    pub fn backup_prefix(&self) -> String {
+        self.backup_prefix
+            .clone()
+            .unwrap_or_else(|| DEFAULT_BACKUP_PREFIX.to_string())
+    }
+
+    /// A method to return the time before an untouched backup expires, if
+    /// specified.
+    ///
+    pub fn backup_ttl(&self) -> Option<Duration> {
+        self.backup_ttl
+    }
+
+    /// A method to return the failover settings for this instance, if
+    /// specified.
+    ///
+    pub fn failover(&self) -> Option<Failover> {
+        self.failover.clone()
     }
 
     /// A method to return a status from the status handler.
@@ -391,6 +467,13 @@ impl Config {
         self.status_handler.get_ids()
     }
 
+    /// A method to return the current value of a variable status, or None if
+    /// the status does not exist or is not a variable.
+    ///
+    pub fn get_value(&self, status_id: &ItemId) -> Option<i64> {
+        self.status_handler.get_status(status_id)?.value()
+    }
+
     /// A method to silently update the status of the system based on a previous
     /// backup.
     ///
@@ -412,6 +495,74 @@ impl Config {
         }
     }
 
+    /// A method to silently update the value of the variables in the system
+    /// based on a previous backup.
+    ///
+    pub async fn load_backup_values(&mut self, mut value_pairs: Vec<(ItemId, i64)>) {
+        // For every variable in the value pairs, set the current value
+        for (status_id, value) in value_pairs.drain(..) {
+            if let Some((state, value, _)) = self
+                .status_handler
+                .modify_variable(&status_id, VariableOperation::Set { value })
+                .await
+            {
+                // Notify the system of the successful value change
+                log!(status &self.internal_send => status_id, state, value);
+            }
+        }
+    }
+
+    /// A method to return the complete saved state of every status in this
+    /// configuration.
+    ///
+    pub fn save_statuses(&self) -> SavedStatusMap {
+        self.status_handler.save_all()
+    }
+
+    /// A method to silently restore the statuses of the system from a
+    /// previous game snapshot, including the counts, values, and remaining
+    /// times.
+    ///
+    pub async fn load_saved_statuses(&mut self, statuses: SavedStatusMap) {
+        // For every saved status, restore the status
+        for (status_id, saved) in statuses {
+            self.restore_status(&status_id, &saved).await;
+        }
+    }
+
+    /// A method to return the complete saved state of a status, if it
+    /// exists.
+    ///
+    pub fn save_status(&self, status_id: &ItemId) -> Option<SavedStatus> {
+        self.status_handler.save(status_id)
+    }
+
+    /// A method to silently restore a status from a saved state, including
+    /// the count, value, and remaining time. Returns any on_exit and on_enter
+    /// events for the change of state, or None if the status was not found.
+    ///
+    pub async fn restore_status(
+        &mut self,
+        status_id: &ItemId,
+        saved: &SavedStatus,
+    ) -> Option<Vec<ItemId>> {
+        // Try to restore the status
+        let events = match self.status_handler.restore(status_id, saved) {
+            Some(events) => events,
+            None => {
+                log!(warn &self.internal_send => "Saved Status Not Found In Configuration: {}.", status_id);
+                return None;
+            }
+        };
+
+        // Notify the system of the successful status change
+        match saved.value {
+            Some(value) => log!(status &self.internal_send => *status_id, saved.current, value),
+            None => log!(status &self.internal_send => *status_id, saved.current),
+        }
+        Some(events)
+    }
+
     /// A method to return a hashmap of the statuses available in this
     /// configuration.
     ///
@@ -505,6 +656,13 @@ impl Config {
         self.status_handler.get_state(status_id).await
     }
 
+    /// A method to evaluate a status condition against the current statuses.
+    ///
+    pub fn evaluate_condition(&self, condition: &StatusCondition) -> bool {
+        // Return the result from the status handler
+        self.status_handler.evaluate(condition)
+    }
+
     /// A method to return the current scene.
     ///
     pub fn get_current_scene(&self) -> ItemId {
@@ -542,7 +700,8 @@ impl Config {
     }
 
     /// A method to modify a status state within the current scene based
-    /// on the provided status id and new state. Method returns the new state or
+    /// on the provided status id and new state. Method returns the new state
+    /// and any on_exit and on_enter events triggered by the change, or
     /// None. None is returned either because
     ///  * the status was already in this state and the status has the
     ///    no_change_silent flag set, or
@@ -558,9 +717,9 @@ impl Config {
         &mut self,
         status_id: &ItemId,
         new_state: &ItemId,
-    ) -> Option<ItemId> {
+    ) -> Option<(ItemId, Vec<ItemId>)> {
         // Try to update the underlying status
-        if let Some(new_id) = self
+        if let Some((new_id, events)) = self
             .status_handler
             .modify_status(&status_id, &new_state)
             .await
@@ -569,13 +728,52 @@ impl Config {
             log!(status &self.internal_send => status_id.clone(), new_state.clone());
 
             // Indicate status change
-            return Some(new_id);
+            return Some((new_id, events));
         }
 
         // Indicate no change
         None
     }
 
+    /// A method to modify the value of a variable status within the current
+    /// configuration. Method returns the current state of the variable, the
+    /// new value, and any threshold events reached, or None if the value
+    /// was not changed.
+    ///
+    pub async fn modify_variable(
+        &mut self,
+        status_id: &ItemId,
+        operation: VariableOperation,
+    ) -> Option<(ItemId, i64, Vec<ItemId>)> {
+        // Try to update the underlying variable
+        let (state, value, events) = self
+            .status_handler
+            .modify_variable(status_id, operation)
+            .await?;
+
+        // Notify the system of the successful value change
+        log!(status &self.internal_send => *status_id, state, value);
+
+        // Return the change
+        Some((state, value, events))
+    }
+
+    /// A method to update the timers in the current configuration. Returns
+    /// the changes to the timers since the last update.
+    ///
+    pub async fn update_timers(&mut self) -> TimerChanges {
+        // Update the timers in the status handler
+        let changes = self.status_handler.update_timers();
+
+        // Notify the system of any timers which ran out of time
+        for (status_id, new_state) in changes.stopped.iter() {
+            log!(status &self.internal_send => *status_id, *new_state);
+        }
+
+        // Return the changes
+        changes
+    }
+
     /// A method to modify or add an event with provided event id and new event.
     ///
     pub async fn edit_event(&mut self, event_id: ItemId, possible_event: Option<Event>) {
@@ -745,6 +943,10 @@ impl Config {
             version: env!("CARGO_PKG_VERSION").to_string(),
             identifier: self.identifier(),
             server_location: self.server_location.clone(),
+            backup_storage: self.backup_storage.clone(),
+            backup_prefix: self.backup_prefix.clone(),
+            backup_ttl: self.backup_ttl,
+            failover: self.failover.clone(),
             system_connection: self.system_connection.clone(),
             background_process,
             default_scene: Some(self.current_scene.clone()),
@@ -753,6 +955,7 @@ impl Config {
             status_map: self.status_handler.get_map(),
             event_set,
             user_styles,
+            schedule: Some(self.schedule.clone()).filter(|schedule| !schedule.is_empty()),
         };
 
         // Try to parse the configuration
@@ -804,6 +1007,27 @@ impl Config {
                 log!(warn internal_send => "Scene Not Described In Lookup: {}", id);
             }
         }
+
+        // Verify that each timer or variable threshold event exists
+        for (id, status) in status_map.iter() {
+            for event_id in status.threshold_events() {
+                if !events.contains_key(&event_id) {
+                    log!(warn internal_send => "Status Contains Invalid Threshold Event: {} ({})", event_id, id);
+                }
+            }
+
+            // Verify that each state with events is allowed and that the events exist
+            for state_events in status.state_events() {
+                if !status.is_allowed(&state_events.state) {
+                    log!(warn internal_send => "Status Contains Events For Invalid State: {} ({})", state_events.state, id);
+                }
+                for event_id in state_events.on_enter.iter().chain(state_events.on_exit.iter()) {
+                    if !events.contains_key(event_id) {
+                        log!(warn internal_send => "Status Contains Invalid State Event: {} ({})", event_id, id);
+                    }
+                }
+            }
+        }
     }
 
     /// An internal function to verify a particular scene in the context of config.
@@ -939,6 +1163,25 @@ impl Config {
                         & Config::verify_lookup(internal_send, lookup, new_state).await;
                 }
 
+                // If there is a variable modification, verify the variable exists
+                &ModifyVariable { ref status_id, .. } => {
+                    // Check that the status_id is valid and is a variable
+                    match status_map.get(status_id) {
+                        Some(status) if status.value().is_some() => (),
+                        Some(_) => {
+                            log!(warn internal_send => "Event Modifies Status That Is Not A Variable: {}", &status_id);
+                            return false;
+                        }
+                        None => {
+                            log!(warn internal_send => "Event Contains Invalid Status Id: {}", &status_id);
+                            return false;
+                        }
+                    }
+
+                    // If the variable exists, verify it is described
+                    return Config::verify_lookup(internal_send, lookup, status_id).await;
+                }
+
                 // If there is an event to cue, verify that it exists
                 &CueEvent { ref event } => {
                     // Verify that the event is listed in the current scene
@@ -963,6 +1206,25 @@ impl Config {
                     } // Don't need to check lookup as all valid individual events are already checked. Don't need to check scene validity because cancelled events are not necessarily in the same scene.
                 }
 
+                // If there is a repeating event, verify the event and the interval
+                &RepeatEvent {
+                    ref event,
+                    ref interval,
+                    ..
+                } => {
+                    // Return false if the event doesn't exist
+                    if !event_list.contains_key(&event) {
+                        log!(warn internal_send => "Event Contains Invalid Repeating Event: {}", &event);
+                        return false;
+                    }
+
+                    // Return false if the interval is empty
+                    if interval.as_nanos() == 0 {
+                        log!(warn internal_send => "Repeating Event Has No Interval: {}", &event);
+                        return false;
+                    }
+                }
+
                 // If there is data to save or send, assume validity
                 &SaveData { .. } => (),
                 &SendData { .. } => (),
@@ -1000,11 +1262,99 @@ impl Config {
                         return false;
                     }
                 }
+
+                // If there is a conditional event, verify the condition and the target events
+                &ConditionalEvent {
+                    ref condition,
+                    ref true_event,
+                    ref false_event,
+                } => {
+                    // Verify the condition
+                    if let Err(warning) = Config::verify_condition(condition, status_map) {
+                        log!(warn internal_send => "{}", warning);
+                        return false;
+                    }
+
+                    // Verify that each target event exists
+                    for target_event in true_event.iter().chain(false_event.iter()) {
+                        if !event_list.contains_key(target_event) {
+                            log!(warn internal_send => "Conditional Event Contains Invalid Target Event: {}", target_event);
+                            return false;
+                        }
+                    }
+                }
             }
         }
         true // If no errors were thrown
     }
 
+    /// An internal function to verify a status condition in the context of
+    /// the status map.
+    ///
+    /// # Errors
+    ///
+    /// This function returns a description of the first inconsistency found
+    /// in the condition, if any.
+    ///
+    fn verify_condition(condition: &StatusCondition, status_map: &StatusMap) -> Result<(), String> {
+        match condition {
+            // Verify each of the nested conditions
+            &StatusCondition::And { ref conditions } | &StatusCondition::Or { ref conditions } => {
+                // Verify that the conditions are not empty
+                if conditions.is_empty() {
+                    return Err("Event Contains Empty Condition".to_string());
+                }
+
+                // Verify each condition
+                for condition in conditions.iter() {
+                    Config::verify_condition(condition, status_map)?;
+                }
+                Ok(())
+            }
+
+            // Verify the inverted condition
+            &StatusCondition::Not { ref condition } => {
+                Config::verify_condition(condition, status_map)
+            }
+
+            // Verify that the status and state are valid
+            &StatusCondition::IsState {
+                ref status_id,
+                ref state,
+            } => match status_map.get(status_id) {
+                // Also verify the state
+                Some(status) if !status.is_allowed(state) => {
+                    Err(format!("Condition Contains Invalid State: {}", state))
+                }
+                Some(_) => Ok(()),
+                None => Err(format!("Condition Contains Invalid Status: {}", status_id)),
+            },
+
+            // Verify that the status is a counted state
+            StatusCondition::CountCompare { status_id, .. } => match status_map.get(status_id) {
+                Some(Status::CountedState { .. }) => Ok(()),
+                _ => Err(format!(
+                    "Condition Contains Invalid Counted State: {}",
+                    status_id
+                )),
+            },
+
+            // Verify that the status is a timer
+            StatusCondition::TimerCompare { status_id, .. } => {
+                match status_map.get(status_id) {
+                    Some(Status::Timer { .. }) => Ok(()),
+                    _ => Err(format!("Condition Contains Invalid Timer: {}", status_id)),
+                }
+            }
+
+            // Verify that the status is a variable
+            StatusCondition::ValueCompare { status_id, .. } => match status_map.get(status_id) {
+                Some(Status::Variable { .. }) => Ok(()),
+                _ => Err(format!("Condition Contains Invalid Variable: {}", status_id)),
+            },
+        }
+    }
+
     /// An internal function to verify that a particular id is in the lookup.
     ///
     /// Like all EventHandler functions and methods, this method will fail
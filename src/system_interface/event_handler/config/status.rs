@@ -25,6 +25,21 @@
 // Import crate definitions
 use crate::definitions::*;
 
+// Import standard library features
+use std::time::Duration;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the changes to the timers since the last update.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimerChanges {
+    pub remaining: Vec<(ItemId, Duration, bool)>, // the timers with a new remaining time (and whether each is running)
+    pub events: Vec<ItemId>,                      // the threshold, stop, and state events to trigger, in order
+    pub stopped: Vec<(ItemId, ItemId)>,           // the timers which ran out of time (and the new state)
+}
+
 /// A structure which holds the local status and manages any state changes.
 ///
 /// # Notes
@@ -35,6 +50,8 @@ use crate::definitions::*;
 pub struct StatusHandler {
     status_map: StatusMap,     // hash map of the local status
     update_line: InternalSend, // the update line for posting any warnings
+    timer_last: FnvHashMap<ItemId, Duration>, // the remaining time of each running timer at the last update
+    timer_reported: FnvHashMap<ItemId, (u32, bool)>, // the last reported seconds and running flag of each timer
 }
 
 // Implement key features for the status handler
@@ -54,6 +71,8 @@ impl StatusHandler {
         StatusHandler {
             status_map,
             update_line,
+            timer_last: FnvHashMap::default(),
+            timer_reported: FnvHashMap::default(),
         }
     }
 
@@ -126,8 +145,9 @@ impl StatusHandler {
     }
 
     /// A method to modify a status state within the current scene based
-    /// on the provided status id and new state. Method returns the new state or
-    /// None. None is returned either because
+    /// on the provided status id and new state. Method returns the new state
+    /// and any on_exit and on_enter events triggered by the change of the
+    /// current state, or None. None is returned either because
     ///  * the status was already in this state and the status has the
     ///    no_change_silent flag set, or
     ///  * if the state failed to change because one or both ids are invalid.
@@ -145,11 +165,23 @@ impl StatusHandler {
         &mut self,
         status_id: &ItemId,
         new_state: &ItemId,
-    ) -> Option<ItemId> {
+    ) -> Option<(ItemId, Vec<ItemId>)> {
         // Try to get a mutable reference to the status
         if let Some(status) = self.status_map.get_mut(status_id) {
-            // Try to update the status and return the result
-            status.update(new_state.clone())
+            // Try to update the status
+            let previous = status.current();
+            let result = status.update(new_state.clone());
+
+            // Track the remaining time of a timer from when it starts (or has time added)
+            if !status.is_running() {
+                self.timer_last.remove(status_id);
+            } else if let Some(remaining) = status.remaining() {
+                let last = self.timer_last.entry(*status_id).or_insert(remaining);
+                *last = remaining.max(*last);
+            }
+
+            // Return the result with any events for the change of state
+            result.map(|new_id| (new_id, status.transition_events(previous, status.current())))
 
         // Warn the system that this is not a valid id
         } else {
@@ -158,6 +190,111 @@ impl StatusHandler {
         }
     }
 
+    /// A method to modify the value of a variable status based on the provided
+    /// status id and operation. Method returns the current state of the
+    /// variable, the new value, and any threshold events reached by the change,
+    /// or None if the value did not change (and the variable has the
+    /// no_change_silent flag set).
+    ///
+    /// # Errors
+    ///
+    /// This method will raise a warning if the provided id was not found in the
+    /// configuration or if the status is not a variable.
+    ///
+    pub async fn modify_variable(
+        &mut self,
+        status_id: &ItemId,
+        operation: VariableOperation,
+    ) -> Option<(ItemId, i64, Vec<ItemId>)> {
+        // Try to get a mutable reference to the status
+        if let Some(status) = self.status_map.get_mut(status_id) {
+            // Warn the system if the status is not a variable
+            if status.value().is_none() {
+                log!(warn &self.update_line => "Status Is Not A Variable: {}", status_id);
+                return None;
+            }
+
+            // Try to update the value and find any thresholds reached
+            let (previous, new_value) = status.modify_value(operation)?;
+            let events = status.reached(previous, new_value);
+
+            // Return the current state, new value, and threshold events
+            Some((status.current(), new_value, events))
+
+        // Warn the system that this is not a valid id
+        } else {
+            log!(warn &self.update_line => "Status ID Not Found In Config: {}", status_id);
+            None
+        }
+    }
+
+    /// A method to update the timers in the status map. Returns the timers
+    /// whose remaining time (in whole seconds) or running state has changed
+    /// since the last update, the threshold events which have been reached,
+    /// and any timers which were stopped because they ran out of time.
+    ///
+    pub fn update_timers(&mut self) -> TimerChanges {
+        // Check each timer in the status map
+        let mut changes = TimerChanges::default();
+        for (status_id, status) in self.status_map.iter_mut() {
+            // Skip statuses which are not timers
+            let remaining = match status.remaining() {
+                Some(remaining) => remaining,
+                None => continue,
+            };
+
+            // If the timer is running
+            if status.is_running() {
+                // Find the thresholds reached since the last update
+                let previous = self
+                    .timer_last
+                    .insert(*status_id, remaining)
+                    .unwrap_or(remaining);
+                let mut reached: Vec<TimerThreshold> = status
+                    .thresholds()
+                    .into_iter()
+                    .filter(|threshold| {
+                        (threshold.remaining < previous) & (threshold.remaining >= remaining)
+                    })
+                    .collect();
+
+                // Trigger the events in order (most remaining time first)
+                reached.sort_by_key(|threshold| std::cmp::Reverse(threshold.remaining));
+                changes
+                    .events
+                    .extend(reached.iter().map(|threshold| threshold.event));
+
+                // Stop the timer if it has run out of time (and trigger the
+                // stop event, as with any other change of state)
+                let previous = status.current();
+                if let Some(new_state) = status.expire() {
+                    self.timer_last.remove(status_id);
+                    changes.stopped.push((*status_id, new_state));
+                    changes.events.push(new_state);
+                    changes
+                        .events
+                        .extend(status.transition_events(previous, new_state));
+                }
+            }
+
+            // Report the timer if the seconds or the running state changed
+            let report = (whole_seconds(remaining), status.is_running());
+            if self.timer_reported.insert(*status_id, report) != Some(report) {
+                changes.remaining.push((*status_id, remaining, report.1));
+            }
+        }
+
+        // Return the changes
+        changes
+    }
+
+    /// A method to evaluate a status condition against the current statuses.
+    /// Returns the result of the condition.
+    ///
+    pub fn evaluate(&self, condition: &StatusCondition) -> bool {
+        condition.evaluate(&self.status_map)
+    }
+
     /// A method to return a copy of the status map inside the status handler.
     ///
     /// # Errors
@@ -168,6 +305,39 @@ impl StatusHandler {
         self.status_map.clone()
     }
 
+    /// A method to return the complete saved state of every status in the
+    /// status handler.
+    ///
+    pub fn save_all(&self) -> SavedStatusMap {
+        self.status_map
+            .iter()
+            .map(|(status_id, status)| (*status_id, status.save()))
+            .collect()
+    }
+
+    /// A method to return the complete saved state of a status, if it
+    /// exists.
+    ///
+    pub fn save(&self, status_id: &ItemId) -> Option<SavedStatus> {
+        self.status_map.get(status_id).map(|status| status.save())
+    }
+
+    /// A method to restore a status from a saved state. Returns any events
+    /// for the change of state, or None if the status was not found.
+    ///
+    pub fn restore(&mut self, status_id: &ItemId, saved: &SavedStatus) -> Option<Vec<ItemId>> {
+        // Try to find the status
+        let status = self.status_map.get_mut(status_id)?;
+
+        // Restore the status (and forget the previous time of a timer)
+        let previous = status.current();
+        status.restore(saved);
+        self.timer_last.remove(status_id);
+
+        // Return any events for the change of state
+        Some(status.transition_events(previous, status.current()))
+    }
+
     /// A method to return a vector of the valid status ids in the status handler.
     ///
     /// # Errors
@@ -257,6 +427,7 @@ mod tests {
             Status::MultiState {
                 current: state1,
                 allowed: vec![state1, state2],
+                state_events: Vec::new(),
                 no_change_silent: false,
             },
         );
@@ -265,6 +436,7 @@ mod tests {
             Status::MultiState {
                 current: state1,
                 allowed: vec![state1, state2],
+                state_events: Vec::new(),
                 no_change_silent: false,
             },
         );
@@ -278,7 +450,7 @@ mod tests {
 
         // Modify both statuses and check the new state
         assert_eq!(
-            Some(state2),
+            Some((state2, Vec::new())),
             status_handler.modify_status(&status1, &state2).await
         );
         assert_eq!(
@@ -290,4 +462,176 @@ mod tests {
         assert_eq!(Some(state2), status_handler.get_state(&status1).await);
         assert_eq!(Some(state1), status_handler.get_state(&status2).await);
     }
+
+    // Test the on_enter and on_exit events of a status
+    #[tokio::test]
+    async fn state_events() {
+        // Import libraries for testing
+        use crate::definitions::InternalSend;
+
+        // Create the receiving line
+        let (internal_send, _rx) = InternalSend::new();
+
+        // Create placeholder ids
+        let multi = ItemId::new_unchecked(1);
+        let counted = ItemId::new_unchecked(2);
+        let state1 = ItemId::new_unchecked(10);
+        let state2 = ItemId::new_unchecked(11);
+        let reset = ItemId::new_unchecked(12);
+        let enter1 = ItemId::new_unchecked(20);
+        let exit1 = ItemId::new_unchecked(21);
+        let enter2 = ItemId::new_unchecked(22);
+
+        // Create the status map
+        let state_events = vec![
+            StateEvents {
+                state: state1,
+                on_enter: vec![enter1],
+                on_exit: vec![exit1],
+            },
+            StateEvents {
+                state: state2,
+                on_enter: vec![enter2],
+                on_exit: Vec::new(),
+            },
+        ];
+        let mut status_map = StatusMap::default();
+        status_map.insert(
+            multi,
+            Status::MultiState {
+                current: state1,
+                allowed: vec![state1, state2],
+                state_events: state_events.clone(),
+                no_change_silent: false,
+            },
+        );
+        status_map.insert(
+            counted,
+            Status::CountedState {
+                current: state1,
+                trigger: state2,
+                anti_trigger: state1,
+                reset,
+                count: 2,
+                default_count: 2,
+                state_events,
+                no_change_silent: false,
+            },
+        );
+
+        // Create a new status handler
+        let mut status_handler = StatusHandler::new(internal_send, status_map);
+
+        // Check the events when the state changes (and not otherwise)
+        assert_eq!(
+            Some((state2, vec![exit1, enter2])),
+            status_handler.modify_status(&multi, &state2).await
+        );
+        assert_eq!(
+            Some((state2, Vec::new())),
+            status_handler.modify_status(&multi, &state2).await
+        );
+        assert_eq!(
+            Some((state1, vec![enter1])),
+            status_handler.modify_status(&multi, &state1).await
+        );
+
+        // Check that the events follow the current state of a counted state
+        assert_eq!(
+            Some((state1, Vec::new())),
+            status_handler.modify_status(&counted, &state2).await
+        );
+        assert_eq!(
+            Some((state2, vec![exit1, enter2])),
+            status_handler.modify_status(&counted, &state2).await
+        );
+        assert_eq!(
+            Some((state1, vec![enter1])),
+            status_handler.modify_status(&counted, &reset).await
+        );
+    }
+
+    // Test the thresholds and expiry of a timer
+    #[tokio::test(start_paused = true)]
+    async fn update_timers() {
+        // Import libraries for testing
+        use crate::definitions::InternalSend;
+        use tokio::time::advance;
+
+        // Create the receiving line
+        let (internal_send, _rx) = InternalSend::new();
+
+        // Create placeholder ids
+        let timer = ItemId::new_unchecked(1);
+        let start = ItemId::new_unchecked(10);
+        let stop = ItemId::new_unchecked(11);
+        let reset = ItemId::new_unchecked(12);
+        let add_time = ItemId::new_unchecked(13);
+        let one_minute = ItemId::new_unchecked(20);
+        let half_minute = ItemId::new_unchecked(21);
+        let time_up = ItemId::new_unchecked(22);
+
+        // Create a two minute timer with three thresholds
+        Clock::set_virtual(1.0);
+        let mut status_map = StatusMap::default();
+        status_map.insert(
+            timer,
+            Status::Timer {
+                current: reset,
+                start,
+                stop,
+                reset,
+                add_time,
+                duration: Duration::from_secs(120),
+                increment: Duration::from_secs(60),
+                remaining: Duration::from_secs(120),
+                started: None,
+                thresholds: vec![
+                    TimerThreshold {
+                        remaining: Duration::from_secs(0),
+                        event: time_up,
+                    },
+                    TimerThreshold {
+                        remaining: Duration::from_secs(60),
+                        event: one_minute,
+                    },
+                    TimerThreshold {
+                        remaining: Duration::from_secs(30),
+                        event: half_minute,
+                    },
+                ],
+                state_events: Vec::new(),
+                no_change_silent: false,
+            },
+        );
+        let mut status_handler = StatusHandler::new(internal_send, status_map);
+
+        // Check the first report of the stopped timer
+        let changes = status_handler.update_timers();
+        assert_eq!(vec![(timer, Duration::from_secs(120), false)], changes.remaining);
+        assert!(status_handler.update_timers().remaining.is_empty());
+
+        // Start the timer and pass two thresholds at once
+        status_handler.modify_status(&timer, &start).await;
+        advance(Duration::from_secs(100)).await;
+        let changes = status_handler.update_timers();
+        assert_eq!(vec![(timer, Duration::from_secs(20), true)], changes.remaining);
+        assert_eq!(vec![one_minute, half_minute], changes.events);
+
+        // Add time and pass the same thresholds again
+        status_handler.modify_status(&timer, &add_time).await;
+        advance(Duration::from_secs(60)).await;
+        assert_eq!(
+            vec![one_minute, half_minute],
+            status_handler.update_timers().events
+        );
+
+        // Run out of time and check that the timer stopped
+        advance(Duration::from_secs(30)).await;
+        let changes = status_handler.update_timers();
+        assert_eq!(vec![time_up, stop], changes.events);
+        assert_eq!(vec![(timer, stop)], changes.stopped);
+        assert_eq!(vec![(timer, Duration::from_secs(0), false)], changes.remaining);
+        assert_eq!(Some(stop), status_handler.get_state(&timer).await);
+    }
 }
@@ -0,0 +1,210 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the heartbeat monitor for hot-standby failover.
+//! The active instance writes a heartbeat to the shared backup storage at
+//! regular intervals. An instance in standby checks the heartbeat at the same
+//! intervals and takes over once the heartbeat has not changed for longer
+//! than the timeout.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::backup::BackupHandler;
+
+// Import Tokio features
+use tokio::time::{sleep, Instant};
+
+/// A structure to track the heartbeat of the active instance. The monitor
+/// only compares heartbeats for changes, so the clocks of the two instances
+/// do not need to agree.
+///
+pub struct FailoverMonitor {
+    settings: Failover,        // the failover settings for this instance
+    is_standby: bool,          // a flag to indicate that this instance is in standby
+    last_check: Instant,       // the time of the last heartbeat written or checked
+    last_change: Instant,      // the time the heartbeat of the active instance last changed
+    heartbeat: Option<String>, // the last heartbeat of the active instance
+}
+
+// Implement key features of the failover monitor
+impl FailoverMonitor {
+    /// A function to create a new failover monitor. A secondary instance
+    /// begins in standby.
+    ///
+    pub fn new(settings: Failover) -> FailoverMonitor {
+        let is_standby = settings.role == FailoverRole::Secondary;
+        FailoverMonitor {
+            settings,
+            is_standby,
+            last_check: Instant::now(),
+            last_change: Instant::now(),
+            heartbeat: None,
+        }
+    }
+
+    /// A method to indicate whether this instance is in standby.
+    ///
+    pub fn is_standby(&self) -> bool {
+        self.is_standby
+    }
+
+    /// A method to indicate whether the next heartbeat should be written or
+    /// checked. If so, the next heartbeat is due one interval from now.
+    ///
+    pub fn is_due(&mut self) -> bool {
+        // Check whether the heartbeat interval has passed
+        if self.last_check.elapsed() < self.settings.heartbeat {
+            return false;
+        }
+
+        // Note the time of this heartbeat
+        self.last_check = Instant::now();
+        true
+    }
+
+    /// A method to note the latest heartbeat of the active instance. Returns
+    /// true if the heartbeat has not changed for longer than the timeout
+    /// (i.e. this instance should take over).
+    ///
+    pub fn is_lost(&mut self, heartbeat: Option<String>) -> bool {
+        // If the heartbeat has changed, note the time of the change
+        if heartbeat.is_some() && heartbeat != self.heartbeat {
+            self.heartbeat = heartbeat;
+            self.last_change = Instant::now();
+        }
+
+        // Check whether the timeout has passed
+        self.last_change.elapsed() > self.settings.timeout
+    }
+
+    /// A method to check for another active instance before this instance
+    /// becomes active (for example, a secondary which took over while this
+    /// instance was stopped). If the heartbeat changes before the timeout,
+    /// this instance begins in standby instead. Returns true if another
+    /// active instance was found.
+    ///
+    pub async fn check_active(&mut self, backup: &mut BackupHandler) -> bool {
+        // Only check if this instance would be active
+        if self.is_standby {
+            return false;
+        }
+
+        // Note the latest heartbeat (if there is none, no other instance is active)
+        let heartbeat = backup.reload_heartbeat().await;
+        if heartbeat.is_none() {
+            return false;
+        }
+        self.is_lost(heartbeat);
+
+        // Wait for the heartbeat to change or the timeout to pass
+        loop {
+            sleep(self.settings.heartbeat).await;
+            let previous = self.heartbeat.clone();
+            if self.is_lost(backup.reload_heartbeat().await) {
+                return false;
+            }
+
+            // If the heartbeat changed, begin in standby
+            if self.heartbeat != previous {
+                self.is_standby = true;
+                return true;
+            }
+        }
+    }
+
+    /// A method to promote this instance from standby to active.
+    ///
+    pub fn promote(&mut self) {
+        self.is_standby = false;
+    }
+}
+
+// Tests of the failover module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Import standard library features
+    use std::time::Duration;
+
+    // Test that the secondary takes over only when the heartbeat stops
+    #[tokio::test(start_paused = true)]
+    async fn detect_lost_primary() {
+        // Create the monitor for a secondary
+        let mut monitor = FailoverMonitor::new(Failover {
+            role: FailoverRole::Secondary,
+            heartbeat: Duration::from_secs(1),
+            timeout: Duration::from_secs(3),
+        });
+        assert!(monitor.is_standby());
+        assert!(!monitor.is_due());
+
+        // Check that a changing heartbeat keeps the secondary in standby
+        for count in 0..10 {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            assert!(monitor.is_due());
+            assert!(!monitor.is_lost(Some(count.to_string())));
+        }
+
+        // Check that a missing or unchanged heartbeat is lost after the timeout
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(!monitor.is_lost(Some("9".to_string())));
+        assert!(!monitor.is_lost(None));
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(monitor.is_lost(None));
+
+        // Promote the secondary
+        monitor.promote();
+        assert!(!monitor.is_standby());
+    }
+
+    // Test that a restarted primary only waits for a heartbeat that exists
+    #[tokio::test(start_paused = true)]
+    async fn check_stale_heartbeat() {
+        // Create the backup handler and the monitor for a primary
+        let (internal_send, _rx) = InternalSend::new();
+        let mut backup = BackupHandler::new(
+            internal_send,
+            Identifier { id: Some(4) },
+            Some(BackupStorage::Memory),
+            "minerva",
+            None,
+        )
+        .await
+        .unwrap();
+        let settings = Failover {
+            role: FailoverRole::Primary,
+            heartbeat: Duration::from_secs(1),
+            timeout: Duration::from_secs(3),
+        };
+
+        // Check that the primary starts immediately without a heartbeat
+        let mut monitor = FailoverMonitor::new(settings.clone());
+        let start = Instant::now();
+        assert!(!monitor.check_active(&mut backup).await);
+        assert_eq!(start.elapsed(), Duration::from_secs(0));
+
+        // Check that an unchanged heartbeat delays the primary until the timeout
+        backup.backup_heartbeat().await;
+        let mut monitor = FailoverMonitor::new(settings);
+        assert!(!monitor.check_active(&mut backup).await);
+        assert!(start.elapsed() > Duration::from_secs(3));
+        assert!(!monitor.is_standby());
+    }
+}
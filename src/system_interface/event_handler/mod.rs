@@ -24,15 +24,19 @@
 // Define private submodules
 mod backup;
 mod config;
+mod failover;
 mod queue;
+mod scheduler;
 
 // Import crate definitions
 use crate::definitions::*;
 
 // Import other definitions
-use self::backup::BackupHandler;
+use self::backup::{BackupHandler, GameSnapshot, QueuedEvent};
 use self::config::Config;
+use self::failover::FailoverMonitor;
 use self::queue::Queue;
+use self::scheduler::Scheduler;
 
 // Import standard library features
 use std::path::PathBuf;
@@ -55,9 +59,13 @@ use failure::Error;
 pub struct EventHandler {
     internal_send: InternalSend, // sending line for event updates and timed events
     queue: Queue,                // current event queue
+    scheduler: Scheduler,        // current schedule of events
     config: Config,              // current configuration
     config_path: PathBuf,        // current configuration path
     backup: BackupHandler,       // current backup server
+    pending_snapshot: Option<GameSnapshot>, // the previous game snapshot, until the operator decides whether to restore it
+    failover: Option<FailoverMonitor>, // the heartbeat monitor for failover, if specified
+    mirror: Option<GameSnapshot>, // the latest snapshot of the primary instance, while in standby
 }
 
 // Implement the event handler functions
@@ -117,16 +125,53 @@ impl EventHandler {
         let mut backup = BackupHandler::new(
             internal_send.clone(),
             config.identifier(),
-            config.server_location(),
+            config.backup_storage(),
+            &config.backup_prefix(),
+            config.backup_ttl(),
         )
         .await?;
 
+        // Create the failover monitor, if specified (a secondary begins in standby)
+        let mut failover = config.failover().map(FailoverMonitor::new);
+
+        // Begin in standby if another instance is already active
+        if let Some(monitor) = failover.as_mut() {
+            if monitor.check_active(&mut backup).await {
+                log!(err &internal_send => "Another Instance Is Active. Starting In Standby.");
+            }
+        }
+        let is_standby = failover
+            .as_ref()
+            .is_some_and(|monitor| monitor.is_standby());
+        backup.set_standby(is_standby);
+
         // Create an empty event queue
         let mut queue = Queue::new(internal_send.clone());
 
-        // Check for existing data from the backup handler
-        let possible_backup = backup.reload_backup(config.get_status_ids());
-        if let Some((current_scene, status_pairs, queued_events)) = possible_backup {
+        // Create the scheduler (enabled unless disabled in the backup)
+        let is_enabled = backup.reload_schedule().await.unwrap_or(true);
+        let scheduler = Scheduler::new(config.get_schedule(), is_enabled);
+
+        // Check for a snapshot of the previous game (restored when the operator confirms)
+        let pending_snapshot = if is_standby {
+            None
+        } else {
+            backup.reload_snapshot().await
+        };
+
+        // If in standby, wait for the primary instance to stop
+        if is_standby {
+            log!(err &internal_send => "Standby Mode. Mirroring The Primary Instance ...");
+
+        // Otherwise, notify that a saved game was found
+        } else if let Some(ref snapshot) = pending_snapshot {
+            // Notify that a saved game was found
+            log!(err &internal_send => "Detected Saved Game From {}. Waiting For Operator ...", snapshot.saved.format("%F %T"));
+
+        // Otherwise, check for existing data from the backup handler
+        } else if let Some((current_scene, status_pairs, queued_events, is_paused)) =
+            backup.reload_backup(config.get_status_ids()).await
+        {
             // Notify that existing data was found
             log!(err &internal_send => "Detected Lingering Backup Data. Reloading ...");
 
@@ -136,12 +181,19 @@ impl EventHandler {
             // Update the current status states based on the backup
             config.load_backup_status(status_pairs).await;
 
-            // Update the queue with the found events
-            for event in queued_events {
-                queue
-                    .add_event(EventDelay::new(Some(event.remaining), event.event_id))
-                    .await;
-            }
+            // Update the values of any variables based on the backup
+            let value_pairs = backup.reload_values(config.get_status_ids()).await;
+            config.load_backup_values(value_pairs).await;
+
+            // Restore the queue
+            EventHandler::restore_queue(
+                &mut queue,
+                &scheduler,
+                &internal_send,
+                queued_events,
+                is_paused,
+            )
+            .await;
 
             // Wait 10 nanoseconds for the queued events to process
             sleep(Duration::new(0, 20)).await;
@@ -156,7 +208,18 @@ impl EventHandler {
                 .await;
         }
 
+        // Load the scheduled events into the queue (unless in standby)
+        if !is_standby {
+            scheduler.load(&mut queue).await;
+        }
+
+        // Write the first heartbeat, if failover is specified
+        if failover.is_some() {
+            backup.backup_heartbeat().await;
+        }
+
         // Load the current scene into the backup (to detect any crash after this point)
+        backup.backup_statuses(config.save_statuses()).await;
         backup
             .backup_current_scene(&config.get_current_scene())
             .await;
@@ -165,12 +228,273 @@ impl EventHandler {
         Ok(EventHandler {
             internal_send: internal_send,
             queue,
+            scheduler,
             config,
             config_path,
             backup,
+            pending_snapshot,
+            failover,
+            mirror: None,
         })
     }
 
+    /// An internal function to restore the events of a previous queue. Any
+    /// scheduled events which were missed while the program was stopped are
+    /// triggered (the others are reloaded from the schedule).
+    ///
+    async fn restore_queue(
+        queue: &mut Queue,
+        scheduler: &Scheduler,
+        internal_send: &InternalSend,
+        queued_events: Vec<QueuedEvent>,
+        is_paused: bool,
+    ) {
+        // Pause the queue first, if it was paused
+        if is_paused {
+            queue.pause().await;
+        }
+
+        // Update the queue with the found events
+        let mut due_events = Vec::new();
+        for event in queued_events {
+            // Collect scheduled events (reloaded from the schedule)
+            if let Some(due) = event.due {
+                due_events.push((event.event_id, due));
+                continue;
+            }
+
+            // Restore the other events
+            queue
+                .restore_event(
+                    event.remaining,
+                    event.event_id,
+                    event.is_held,
+                    event.repeat,
+                    event.priority,
+                )
+                .await;
+        }
+
+        // Trigger any scheduled events that were missed while stopped
+        for event_id in scheduler.missed(&due_events, Clock::now()) {
+            log!(update internal_send => "Catching Up Missed Scheduled Event: {}", event_id);
+            queue.add_event(EventDelay::new(None, event_id)).await;
+        }
+    }
+
+    /// A method to return the time and current scene of the previous game, if
+    /// a saved game is waiting to be restored.
+    ///
+    pub fn saved_game(&self) -> Option<(NaiveDateTime, ItemId)> {
+        let snapshot = self.pending_snapshot.as_ref()?;
+        Some((snapshot.saved, snapshot.current_scene?))
+    }
+
+    /// A method to restore or discard the saved game. When restored, the
+    /// scene, statuses, and queue of the previous game are restored silently
+    /// and the method returns the previous state of the outputs. When
+    /// discarded, the reset event of the current scene is triggered.
+    ///
+    pub async fn restore_game(&mut self, restore: bool) -> Option<OutputState> {
+        // Take the saved game, if it exists
+        let snapshot = self.pending_snapshot.take()?;
+
+        // If the game should be discarded, start the current scene as normal
+        if !restore {
+            log!(update &self.internal_send => "Saved Game Discarded.");
+            self.backup.release_snapshot().await;
+            self.queue
+                .add_event(EventDelay::new(None, self.config.get_current_scene()))
+                .await;
+            return None;
+        }
+
+        // Change the current scene silently (i.e. do not trigger the reset event)
+        log!(update &self.internal_send => "Restoring Saved Game ...");
+        if let Some(scene_id) = snapshot.current_scene {
+            self.config.choose_scene(scene_id).await.unwrap_or(());
+        }
+
+        // Restore the statuses and the queue
+        self.config.load_saved_statuses(snapshot.statuses).await;
+        EventHandler::restore_queue(
+            &mut self.queue,
+            &self.scheduler,
+            &self.internal_send,
+            snapshot.queue,
+            snapshot.is_paused,
+        )
+        .await;
+
+        // Replace the saved game with the restored game
+        self.backup.release_snapshot().await;
+        self.backup
+            .backup_current_scene(&self.config.get_current_scene())
+            .await;
+        self.backup_statuses().await;
+
+        // Trigger a redraw of the window and timeline
+        self.internal_send.send_refresh().await;
+
+        // Return the state of the outputs to restore
+        Some(snapshot.output)
+    }
+
+    /// A method to end the current game, clearing its backup (and any saved
+    /// game waiting to be restored).
+    ///
+    pub async fn end_game(&mut self) {
+        self.pending_snapshot = None;
+        self.backup.clear_backup().await;
+        log!(update &self.internal_send => "Game Ended. Backup Cleared.");
+    }
+
+    /// A method to indicate whether this instance is in standby (mirroring
+    /// the primary instance without driving the system connections).
+    ///
+    pub fn is_standby(&self) -> bool {
+        self.failover
+            .as_ref()
+            .is_some_and(|monitor| monitor.is_standby())
+    }
+
+    /// A method to update the failover heartbeat. The active instance writes
+    /// a new heartbeat, and an instance in standby mirrors the latest game
+    /// snapshot of the primary instance. If the primary instance has stopped,
+    /// this instance takes over and the method returns the previous state of
+    /// the outputs (to restore once the system connections are open).
+    ///
+    pub async fn update_failover(&mut self) -> Option<OutputState> {
+        // Check whether the next heartbeat is due
+        let monitor = self.failover.as_mut()?;
+        if !monitor.is_due() {
+            return None;
+        }
+
+        // If active, write the next heartbeat
+        if !monitor.is_standby() {
+            self.backup.backup_heartbeat().await;
+            return None;
+        }
+
+        // If the primary instance has stopped, take over
+        let heartbeat = self.backup.reload_heartbeat().await;
+        if monitor.is_lost(heartbeat) {
+            monitor.promote();
+            return Some(self.take_over().await);
+        }
+
+        // Otherwise, mirror the latest snapshot of the primary instance
+        let snapshot = self.backup.read_snapshot().await;
+        if snapshot != self.mirror {
+            if let Some(ref snapshot) = snapshot {
+                self.mirror_game(snapshot).await;
+            }
+            self.mirror = snapshot;
+        }
+        None
+    }
+
+    /// An internal method to mirror the scene and statuses from a snapshot of
+    /// the primary instance. Timers are mirrored without running (so that
+    /// they do not trigger events while in standby).
+    ///
+    async fn mirror_game(&mut self, snapshot: &GameSnapshot) {
+        // Change the current scene silently, if it changed
+        if let Some(scene_id) = snapshot.current_scene {
+            if scene_id != self.config.get_current_scene() {
+                self.config.choose_scene(scene_id).await.unwrap_or(());
+            }
+        }
+
+        // Restore any statuses which changed state, count, or value
+        let current = self.config.save_statuses();
+        let mut changed = SavedStatusMap::default();
+        for (status_id, saved) in snapshot.statuses.iter() {
+            let is_same = current.get(status_id).is_some_and(|status| {
+                (status.current, status.count, status.value)
+                    == (saved.current, saved.count, saved.value)
+            });
+            if !is_same {
+                let mut saved = saved.clone();
+                saved.is_running = false;
+                changed.insert(*status_id, saved);
+            }
+        }
+        self.config.load_saved_statuses(changed).await;
+    }
+
+    /// An internal method to take over from the primary instance. The game is
+    /// restored from the latest snapshot of the primary instance (or the
+    /// current scene is started if there is no snapshot) and this instance
+    /// begins to write the backup. The queue and timers are advanced by the
+    /// age of the snapshot. Returns the previous state of the outputs.
+    ///
+    async fn take_over(&mut self) -> OutputState {
+        // Notify that the primary instance was lost
+        log!(err &self.internal_send => "Primary Instance Lost. Taking Over ...");
+        self.backup.set_standby(false);
+
+        // Read the latest snapshot of the primary instance
+        let mirror = self.mirror.take();
+        let output = match self.backup.read_snapshot().await.or(mirror) {
+            Some(mut snapshot) => {
+                // Account for the time since the snapshot was saved
+                let elapsed = snapshot.age();
+                snapshot.advance_queue(elapsed);
+                snapshot.advance_timers(elapsed);
+
+                // Restore the scene, statuses, and queue silently
+                if let Some(scene_id) = snapshot.current_scene {
+                    self.config.choose_scene(scene_id).await.unwrap_or(());
+                }
+                self.config.load_saved_statuses(snapshot.statuses).await;
+                EventHandler::restore_queue(
+                    &mut self.queue,
+                    &self.scheduler,
+                    &self.internal_send,
+                    snapshot.queue,
+                    snapshot.is_paused,
+                )
+                .await;
+                snapshot.output
+            }
+
+            // Otherwise, start the current scene
+            None => {
+                self.queue
+                    .add_event(EventDelay::new(None, self.config.get_current_scene()))
+                    .await;
+                OutputState::default()
+            }
+        };
+
+        // Load the scheduled events and begin to write the backup
+        self.scheduler.load(&mut self.queue).await;
+        self.backup
+            .backup_current_scene(&self.config.get_current_scene())
+            .await;
+        self.backup_statuses().await;
+        self.backup.backup_heartbeat().await;
+
+        // Trigger a redraw of the window and timeline
+        self.internal_send.send_refresh().await;
+        output
+    }
+
+    /// A method to backup the state of the dmx and media outputs.
+    ///
+    pub async fn backup_output(&mut self, output: OutputState) {
+        self.backup.backup_output(output).await;
+    }
+
+    /// An internal method to backup the complete state of every status.
+    ///
+    async fn backup_statuses(&mut self) {
+        let statuses = self.config.save_statuses();
+        self.backup.backup_statuses(statuses).await;
+    }
+
     /// A method to return the configured system connection type.
     ///
     pub fn system_connection(&self) -> (ConnectionSet, Identifier) {
@@ -188,16 +512,49 @@ impl EventHandler {
     /// This method clears all the events in the timed queue, effective
     /// immediately. This means that any events that have not been processed
     /// (even if their delay has already expired) will not be processed.
+    /// If the schedule is enabled, the next scheduled events are reloaded.
     ///
     pub async fn clear_events(&mut self) {
         self.queue.clear().await;
+        self.scheduler.load(&mut self.queue).await;
+    }
+
+    /// A method to enable or disable the schedule. While disabled, no
+    /// scheduled events are triggered.
+    ///
+    pub async fn enable_schedule(&mut self, is_enabled: bool) {
+        // Change the schedule and backup the setting
+        self.scheduler.set_enabled(&mut self.queue, is_enabled).await;
+        self.backup.backup_schedule(is_enabled).await;
+
+        // Notify the user of the change
+        if self.scheduler.is_enabled() {
+            log!(update &self.internal_send => "Schedule Enabled.");
+        } else {
+            log!(update &self.internal_send => "Schedule Disabled.");
+        }
+    }
+
+    /// A method to pause the events in the timed queue, freezing the
+    /// remaining time of each event.
+    ///
+    pub async fn pause_events(&mut self) {
+        self.queue.pause().await;
     }
 
-    /// A method to backup a list of coming events.
+    /// A method to resume the events in the timed queue with the time that
+    /// was remaining when they were paused.
     ///
-    pub async fn backup_events(&mut self, events: Vec<ComingEvent>) {
+    pub async fn resume_events(&mut self) {
+        self.queue.resume().await;
+    }
+
+    /// A method to backup a list of coming events and whether they are
+    /// paused.
+    ///
+    pub async fn backup_events(&mut self, events: Vec<ComingEvent>, is_paused: bool) {
         // Backup the coming events
-        self.backup.backup_events(events).await;
+        self.backup.backup_events(events, is_paused).await;
     }
 
     /// A method to return a copy of the current path for the configuration.
@@ -296,15 +653,128 @@ impl EventHandler {
     ///
     pub async fn modify_status(&mut self, status_id: &ItemId, new_state: &ItemId) {
         // Try to modify the underlying status
-        if let Some(new_id) = self.config.modify_status(status_id, new_state).await {
+        if let Some((new_id, events)) = self.config.modify_status(status_id, new_state).await {
             // Backup the status change
-            self.backup.backup_status(status_id, &new_id).await;
+            self.backup.backup_status(status_id, &new_id, None).await;
+            self.backup_statuses().await;
 
             // Run the change event for the new state (no backup necessary)
             self.queue.add_event(EventDelay::new(None, new_id)).await;
+
+            // Run any on_exit and on_enter events for the change of state
+            for event_id in events {
+                self.queue.add_event(EventDelay::new(None, event_id)).await;
+            }
         }
     }
 
+    /// A method to return the complete saved state of the provided status,
+    /// if it exists.
+    ///
+    pub fn save_status(&self, status_id: &ItemId) -> Option<SavedStatus> {
+        self.config.save_status(status_id)
+    }
+
+    /// A method to restore a status to a previous saved state (for example,
+    /// to undo a change by the operator), including the count, value, and
+    /// remaining time. Unless fire_events is true, the events for the state
+    /// are not triggered.
+    ///
+    pub async fn restore_status(
+        &mut self,
+        status_id: &ItemId,
+        saved: &SavedStatus,
+        fire_events: bool,
+    ) {
+        // Try to restore the status
+        if let Some(events) = self.config.restore_status(status_id, saved).await {
+            // Backup the status change
+            self.backup
+                .backup_status(status_id, &saved.current, saved.value)
+                .await;
+            self.backup_statuses().await;
+
+            // If requested, run the events for the restored state
+            if fire_events {
+                self.queue
+                    .add_event(EventDelay::new(None, saved.current))
+                    .await;
+                for event_id in events {
+                    self.queue.add_event(EventDelay::new(None, event_id)).await;
+                }
+            }
+        }
+    }
+
+    /// A method to restore a previous scene (for example, to undo a change by
+    /// the operator). Unless fire_events is true, the reset event for the
+    /// scene is not triggered.
+    ///
+    pub async fn restore_scene(&mut self, scene_id: ItemId, fire_events: bool) {
+        // If requested, change the scene as normal
+        if fire_events {
+            return self.choose_scene(scene_id).await;
+        }
+
+        // Otherwise, change and backup the scene without the reset event
+        if self.config.choose_scene(scene_id).await.is_ok() {
+            self.backup.backup_current_scene(&scene_id).await;
+        }
+    }
+
+    /// A method to change the value of a variable status within the current
+    /// configuration. Triggers the event for the current state of the
+    /// variable and any threshold events which were reached.
+    ///
+    pub async fn modify_variable(&mut self, status_id: &ItemId, operation: VariableOperation) {
+        // Try to modify the underlying variable
+        if let Some((state, value, events)) =
+            self.config.modify_variable(status_id, operation).await
+        {
+            // Backup the value change
+            self.backup.backup_status(status_id, &state, Some(value)).await;
+            self.backup_statuses().await;
+
+            // Run the change event for the variable
+            self.queue.add_event(EventDelay::new(None, state)).await;
+
+            // Trigger any threshold events
+            for event_id in events {
+                self.queue.add_event(EventDelay::new(None, event_id)).await;
+            }
+        }
+    }
+
+    /// A method to update the timers in the current configuration. Any
+    /// threshold events which have been reached are triggered and any
+    /// timers which ran out of time are stopped and backed up. Returns the
+    /// timers whose remaining time or running state has changed (and whether
+    /// each is running).
+    ///
+    pub async fn update_timers(&mut self) -> Vec<(ItemId, Duration, bool)> {
+        // Update the timers in the configuration
+        let changes = self.config.update_timers().await;
+
+        // Backup any timers which ran out of time
+        for (status_id, new_state) in changes.stopped.iter() {
+            self.backup.backup_status(status_id, new_state, None).await;
+        }
+
+        // Backup the remaining time only when a threshold or stop is reached
+        // (starting, stopping, and adding time are backed up as they happen)
+        if !changes.events.is_empty() {
+            self.backup_statuses().await;
+        }
+
+        // Trigger any threshold, stop, and state events
+        for event_id in changes.events {
+            self.queue.add_event(EventDelay::new(None, event_id)).await;
+        }
+
+        // Return the timers to update
+        changes.remaining
+    }
+
     /// A method to add or modify an event within the current configuration.
     ///
     pub async fn edit_event(&mut self, event_id: ItemId, new_event: Option<Event>) {
@@ -346,7 +816,7 @@ impl EventHandler {
     }
 
     /// A method to change the remaining delay for the provided event currently
-    /// in the queue, or to cancel the event.
+    /// in the queue, or to cancel, hold, release, or trigger the event.
     ///
     /// # Errors
     ///
@@ -359,33 +829,40 @@ impl EventHandler {
         &mut self,
         event_id: ItemId,
         start_time: NaiveDateTime,
-        new_delay: Option<Duration>,
+        change: EventAdjustment,
     ) {
-        // Check to see if a delay was specified
-        match new_delay {
-            // If a delay was specified
-            Some(delay) => {
-                // Try to modify the provided event in the current queue
-                self.queue
-                    .adjust_event(ComingEvent {
-                        event_id,
-                        start_time,
-                        delay,
-                    })
-                    .await;
-            }
+        // Identify the event by the id and the start time
+        let mut event = ComingEvent {
+            event_id,
+            start_time,
+            delay: Duration::from_secs(0),
+            paused: None,
+            is_held: false,
+            repeat: None,
+            schedule: None,
+            priority: 0,
+            conflict: ConflictPolicy::KeepBoth,
+        };
 
-            // Otherwise
-            None => {
-                // Try to cancel the event
-                self.queue
-                    .cancel_event(ComingEvent {
-                        event_id,
-                        start_time,
-                        delay: Duration::from_secs(0),
-                    })
-                    .await;
+        // Apply the requested change
+        match change {
+            // Try to modify the provided event in the current queue
+            EventAdjustment::Delay(delay) => {
+                event.delay = delay;
+                self.queue.adjust_event(event).await;
             }
+
+            // Try to cancel the event
+            EventAdjustment::Cancel => self.queue.cancel_event(event).await,
+
+            // Try to hold the event
+            EventAdjustment::Hold => self.queue.hold_event(event).await,
+
+            // Try to release the event
+            EventAdjustment::Release => self.queue.release_event(event).await,
+
+            // Try to trigger the event now
+            EventAdjustment::FireNow => self.queue.fire_event(event).await,
         }
     }
 
@@ -452,11 +929,12 @@ impl EventHandler {
             None => return false,
         };
 
-        // Unpack and process each action of the event
+        // Unpack and process each action of the event (with a shared start time)
         let mut was_broadcast = false;
+        let start_time = Clock::now();
         for action in event {
             // Switch based on the result of unpacking the action
-            match self.unpack_action(action).await {
+            match self.unpack_action(action, start_time).await {
                 // No additional action required
                 UnpackResult::None => (),
 
@@ -509,9 +987,14 @@ impl EventHandler {
     }
 
     /// An internal function to unpack the event and act on it. If the
-    /// event results in data to broadcast, the data will be returned.
+    /// event results in data to broadcast, the data will be returned. Any
+    /// cued events are delayed from the provided start time.
     ///
-    async fn unpack_action(&mut self, event_action: EventAction) -> UnpackResult {
+    async fn unpack_action(
+        &mut self,
+        event_action: EventAction,
+        start_time: NaiveDateTime,
+    ) -> UnpackResult {
         // Unpack the event
         match event_action {
             // If there is a new scene, execute the change
@@ -529,10 +1012,32 @@ impl EventHandler {
                 self.modify_status(&status_id, &new_state).await;
             }
 
+            // If there is a variable modification, execute the change
+            ModifyVariable {
+                status_id,
+                operation,
+            } => {
+                // Try to change the value of the variable and trigger any events
+                self.modify_variable(&status_id, operation).await;
+            }
+
             // If there is a queued event to load, load it into the queue
             CueEvent { event } => {
                 // Add the event to the queue
-                self.queue.add_event(event).await;
+                self.queue.add_event_at(event, start_time).await;
+            }
+
+            // If there is a repeating event, load it into the queue
+            RepeatEvent {
+                event,
+                interval,
+                count,
+                end_after,
+            } => {
+                // Add the repeating event to the queue
+                self.queue
+                    .add_repeating_event(event, interval, count, end_after)
+                    .await;
             }
 
             // If there is an event to cancel, remove it from the queue
@@ -594,6 +1099,15 @@ impl EventHandler {
                         // Error that this is not yet implemented
                         log!(err &self.internal_send => "Saving a User String is not yet implemented.");
                     }
+
+                    // Collect the value of a variable
+                    DataType::VariableValue { status_id } => {
+                        // Check to see if the variable exists
+                        if let Some(value) = self.config.get_value(&status_id) {
+                            // Save the value to the game log
+                            log!(save &self.internal_send => format!("Value {}", value));
+                        }
+                    }
                 }
             }
 
@@ -674,6 +1188,13 @@ impl EventHandler {
 
                     // Solicit a string from the user
                     DataType::UserString => return UnpackResult::String,
+
+                    // Collect the value of a variable
+                    DataType::VariableValue { status_id } => {
+                        // Convert the value to u32 (as a two's complement i32, truncated)
+                        let value = self.config.get_value(&status_id).unwrap_or(0);
+                        return UnpackResult::Data(vec![value as i32 as u32]);
+                    }
                 }
             }
 
@@ -695,6 +1216,24 @@ impl EventHandler {
                     }
                 }
             }
+
+            // If there is a conditional event, evaluate the condition
+            ConditionalEvent {
+                condition,
+                true_event,
+                false_event,
+            } => {
+                // Select the event that matches the result of the condition
+                let event_id = match self.config.evaluate_condition(&condition) {
+                    true => true_event,
+                    false => false_event,
+                };
+
+                // Trigger the event if one was specified
+                if let Some(event_id) = event_id {
+                    self.queue.add_event(EventDelay::new(None, event_id)).await;
+                }
+            }
         }
 
         // Return none for most cases
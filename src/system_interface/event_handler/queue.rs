@@ -24,13 +24,16 @@
 use crate::definitions::*;
 
 // Import standard library features
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+// Import Chrono features
+use chrono::NaiveDateTime;
+
 // Import tokio features
 use tokio::runtime::Handle;
 use tokio::sync::mpsc;
-use tokio::time::sleep;
 
 /// An internal struct to hold the coming events and associated updates.
 ///
@@ -42,6 +45,7 @@ use tokio::time::sleep;
 #[derive(Clone)]
 struct ComingEvents {
     list: Arc<Mutex<Vec<ComingEvent>>>, // a threadsafe vector to hold the coming events
+    is_paused: Arc<AtomicBool>, // a threadsafe flag to indicate that the coming events are paused
     interface_send: InternalSend, // the general update line for passing current events back to the rest of the system
 }
 
@@ -52,6 +56,7 @@ impl ComingEvents {
     fn new(interface_send: InternalSend) -> ComingEvents {
         ComingEvents {
             list: Arc::new(Mutex::new(Vec::new())),
+            is_paused: Arc::new(AtomicBool::new(false)),
             interface_send,
         }
     }
@@ -65,7 +70,61 @@ impl ComingEvents {
             Ok(list) => list.clone(),
             _ => Vec::new(), // inelegant failure handling
         };
-        self.interface_send.send_coming_events(list).await;
+        self.interface_send
+            .send_coming_events(list, self.is_paused())
+            .await;
+    }
+
+    /// A method to indicate if the coming events are paused.
+    ///
+    fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::SeqCst)
+    }
+
+    /// A method to pause the coming events, freezing the remaining time of
+    /// each event. Scheduled events are not frozen (so that they still
+    /// trigger at the scheduled time).
+    ///
+    async fn pause(&mut self) {
+        // Mark the events as paused
+        self.is_paused.store(true, Ordering::SeqCst);
+
+        // Freeze the remaining time for each event (other than scheduled events)
+        if let Ok(mut list) = self.list.lock() {
+            for event in list.iter_mut().filter(|event| event.schedule.is_none()) {
+                event.paused = Some(event.remaining().unwrap_or_default());
+            }
+        }
+
+        // Update the system
+        self.send_current().await;
+    }
+
+    /// A method to resume the coming events. Removes and returns the frozen
+    /// events, restarted with their remaining time, to be loaded again.
+    ///
+    fn resume(&mut self) -> Vec<ComingEvent> {
+        // Mark the events as resumed
+        self.is_paused.store(false, Ordering::SeqCst);
+
+        // Remove the frozen events from the list (scheduled events were not frozen)
+        let events: Vec<ComingEvent> = match self.list.lock() {
+            Ok(mut list) => {
+                let (frozen, active) = list.drain(..).partition(|event| event.paused.is_some());
+                *list = active;
+                frozen
+            }
+            _ => Vec::new(),
+        };
+
+        // Restart each event with the remaining time (unless held)
+        events
+            .into_iter()
+            .map(|event| match event.paused {
+                Some(remaining) if !event.is_held => restart(&event, remaining),
+                _ => event,
+            })
+            .collect()
     }
 
     /// A method to load an additional coming event.
@@ -76,33 +135,33 @@ impl ComingEvents {
     /// Otherwise the thread may not process an event properly that has a shorter
     /// delay than existing events
     ///
-    async fn load_event(&mut self, event: ComingEvent) {
+    async fn load_event(&mut self, mut event: ComingEvent) {
+        // Freeze the event if the events are paused (unless it is scheduled)
+        if self.is_paused() && event.paused.is_none() && event.schedule.is_none() {
+            event.paused = Some(event.remaining().unwrap_or_default());
+        }
+
         // Get access to the list
         if let Ok(mut list) = self.list.lock() {
-            // Calculate the remaining time before the event triggers
-            if let Some(event_remaining) = event.remaining() {
-                // Find the correct spot in the queue
-                let mut index = 0;
-                for coming in list.iter() {
-                    // Calculate the remaining time for this particular coming event
-                    if let Some(coming_remaining) = coming.remaining() {
-                        // If event delay is larger than coming event, put new event in front
-                        if event_remaining > coming_remaining {
-                            break;
-                        }
-                    }
+            // Apply the conflict policy of the event
+            match event.conflict {
+                // Keep any existing events
+                ConflictPolicy::KeepBoth => (),
 
-                    // Otherwise, increment
-                    index += 1;
-                }
-
-                // Load the event at the appropriate point in the queue
-                list.insert(index, event);
+                // Remove any existing events with the same id
+                ConflictPolicy::Replace => list.retain(|coming| coming.event_id != event.event_id),
 
-            // If the event had no time left, put it at the back of the list
-            } else {
-                list.push(event);
+                // Ignore the new event if the id is already in the list
+                ConflictPolicy::Ignore => {
+                    if list.iter().any(|coming| coming.event_id == event.event_id) {
+                        return;
+                    }
+                }
             }
+
+            // Insert the event (the policy only applies when first loaded)
+            event.conflict = ConflictPolicy::KeepBoth;
+            insert_event(&mut list, event);
         }
 
         // Update the system
@@ -121,47 +180,105 @@ impl ComingEvents {
         self.send_current().await;
     }
 
-    /// A method that returns a copy of the last coming event in the list,
-    /// if it exists.
+    /// A method that returns a copy of the last coming event in the list
+    /// which is not paused or held, if it exists.
     ///
     fn last(&self) -> Option<ComingEvent> {
         // Get access to the list
         if let Ok(list) = self.list.lock() {
-            // Return the last entry
-            return match list.last() {
-                Some(event) => Some(event.clone()),
-                None => None,
-            };
+            // Return the last active entry
+            return list.iter().rev().find(|event| event.paused.is_none()).cloned();
         }
         None
     }
 
-    /// A method that removes the last event in the list if it matches the
-    /// provided coming event. Returns the event if they match and None
-    /// otherwise.
+    /// A method that removes the last active event in the list if it matches
+    /// the provided coming event. Returns the event if they match and None
+    /// otherwise. If the event repeats or is scheduled, the next occurrence is
+    /// added to the list in its place.
     ///
     async fn pop_if(&mut self, test_event: &ComingEvent) -> Option<ComingEvent> {
-        // If an event was found, compare it
-        let mut result = false;
-        if let Some(event) = self.last() {
-            // Compare the id and the start time with the test event
-            result = event.compare_with(test_event);
+        // Remove the last active event if it matches the test event
+        let tmp = match self.list.lock() {
+            Ok(mut list) => match list.iter().rposition(|event| event.paused.is_none()) {
+                // Compare the id and the start time with the test event
+                Some(index) if list[index].compare_with(test_event) => {
+                    let event = list.remove(index);
+
+                    // Add the next occurrence, if the event repeats or is scheduled
+                    if let Some(next) = event.next_occurrence(event.trigger_time()) {
+                        insert_event(&mut list, next);
+                    }
+                    Some(event)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        // If the event was removed, notify the system
+        if tmp.is_some() {
+            self.send_current().await;
         }
+        tmp
+    }
 
-        // If the event is correct, pop it from the list and notify the system
-        if result {
-            let tmp = match self.list.lock() {
-                Ok(mut list) => list.pop(), // technically could be None, but isn't because of the logic above
-                _ => None,
-            };
+    /// A method to hold a specific event, freezing the remaining time until
+    /// the event is released.
+    ///
+    async fn hold(&mut self, test_event: &ComingEvent) {
+        // Get access to the list
+        let mut is_changed = false;
+        if let Ok(mut list) = self.list.lock() {
+            // Look for the requested event
+            if let Some(event) = list.iter_mut().find(|event| event.compare_with(test_event)) {
+                // Freeze the remaining time (unless already frozen)
+                if event.paused.is_none() {
+                    event.paused = Some(event.remaining().unwrap_or_default());
+                }
+                event.is_held = true;
+                is_changed = true;
+            }
+        }
 
-            // Send the update
+        // If changed, update the current events
+        if is_changed {
             self.send_current().await;
-            return tmp;
         }
+    }
 
-        // Otherwise return None
-        None
+    /// A method to release a held event. If the events are not paused, the
+    /// event is removed and returned, restarted with the remaining time, to
+    /// be loaded again.
+    ///
+    async fn release(&mut self, test_event: &ComingEvent) -> Option<ComingEvent> {
+        // Get access to the list
+        let mut is_changed = false;
+        let mut released = None;
+        if let Ok(mut list) = self.list.lock() {
+            // Look for the requested event
+            if let Some(index) = list
+                .iter()
+                .position(|event| event.is_held && event.compare_with(test_event))
+            {
+                // If paused, leave the event frozen until the events resume
+                if self.is_paused() {
+                    list[index].is_held = false;
+                    is_changed = true;
+
+                // Otherwise, restart the event
+                } else {
+                    let event = list.remove(index);
+                    released = Some(restart(&event, event.remaining().unwrap_or_default()));
+                }
+            }
+        }
+
+        // If changed, update the current events
+        if is_changed {
+            self.send_current().await;
+        }
+        released
     }
 
     /// A method to determine the amount of time remaining before an event
@@ -189,22 +306,23 @@ impl ComingEvents {
         None
     }
 
-    /// A method to remove the requested event from the list, change its delay
-    /// to the provided Duration, return it to the caller.
+    /// A method to remove the requested event from the list and return it
+    /// to the caller.
     ///
     /// # Errors
     ///
     /// If the requested event does not exist in the queue, this method will
     /// return None.
     ///
-    async fn withdraw(&mut self, new_event: ComingEvent) -> Option<ComingEvent> {
+    async fn withdraw(&mut self, test_event: &ComingEvent) -> Option<ComingEvent> {
         // Get access to the list
+        let mut withdrawn = None;
         if let Ok(mut list) = self.list.lock() {
             // Look for and remove the requested event (based on the drain_filter code)
             let mut index = 0;
             while index != list.len() {
                 // If the event was found,
-                if list[index].compare_with(&new_event) {
+                if list[index].compare_with(test_event) {
                     // Break at this index point
                     break;
                 }
@@ -219,14 +337,32 @@ impl ComingEvents {
             }
 
             // Otherwise, remove the event from the list
-            list.remove(index);
+            withdrawn = Some(list.remove(index));
         }
 
         // Send the update
         self.send_current().await;
 
-        // Return the new event
-        return Some(new_event);
+        // Return the withdrawn event
+        withdrawn
+    }
+
+    /// A method to remove any scheduled events from the list.
+    ///
+    async fn cancel_scheduled(&mut self) {
+        // Get access to the list
+        let mut is_changed = false;
+        if let Ok(mut list) = self.list.lock() {
+            // Remove any scheduled events
+            let length = list.len();
+            list.retain(|event| event.schedule.is_none());
+            is_changed = list.len() != length;
+        }
+
+        // If changed, update the current events
+        if is_changed {
+            self.send_current().await;
+        }
     }
 
     /// A method to remove any events that match the event id from the list.
@@ -266,6 +402,60 @@ impl ComingEvents {
     }
 }
 
+/// A helper function to indicate if the event should trigger after the
+/// coming event. Events at the same time are ordered by priority, and then
+/// by the order they were added.
+///
+fn is_after(event: &ComingEvent, coming: &ComingEvent) -> bool {
+    // Compare the trigger times directly, unless either event is frozen
+    let ordering = if event.paused.is_none() && coming.paused.is_none() {
+        event.trigger_time().cmp(&coming.trigger_time())
+    } else {
+        event
+            .remaining()
+            .unwrap_or_default()
+            .cmp(&coming.remaining().unwrap_or_default())
+    };
+
+    // Place the new event after existing events of the same or higher priority
+    match ordering {
+        std::cmp::Ordering::Equal => event.priority <= coming.priority,
+        ordering => ordering == std::cmp::Ordering::Greater,
+    }
+}
+
+/// A helper function to insert an event into the list of coming events,
+/// preserving the order of the list.
+///
+fn insert_event(list: &mut Vec<ComingEvent>, event: ComingEvent) {
+    // Check that the event has remaining time before it triggers
+    if event.remaining().is_some() {
+        // Find the correct spot in the queue (in front of the first event it follows)
+        let index = list
+            .iter()
+            .position(|coming| is_after(&event, coming))
+            .unwrap_or(list.len());
+
+        // Load the event at the appropriate point in the queue
+        list.insert(index, event);
+
+    // If the event had no time left, put it at the back of the list
+    } else {
+        list.push(event);
+    }
+}
+
+/// A helper function to restart a frozen event with the provided remaining
+/// time, preserving any repetition or schedule of the event.
+///
+fn restart(event: &ComingEvent, remaining: Duration) -> ComingEvent {
+    let mut restarted = ComingEvent::new(remaining, event.id());
+    restarted.repeat = event.repeat;
+    restarted.schedule = event.schedule;
+    restarted.priority = event.priority;
+    restarted
+}
+
 /// A struct to hold a queue of future events. This struct launches a
 /// separate daemon to preserve ordering of the events and minimize the spread
 /// of unnecessary threads. This version preserves the proper order of the
@@ -318,7 +508,7 @@ impl Queue {
     ) {
         // Run the background process indefinitely
         loop {
-            // Check for the next coming event
+            // Check for the next coming event (only scheduled events while paused)
             let next_event = coming_events.last();
             match next_event {
                 // If there isn't a coming event
@@ -352,8 +542,8 @@ impl Queue {
 
                         // If there is some time remaining, wait for a message to arrive or the time to pass
                         Some(delay) => {
-                            // Create the new sleep
-                            let sleep = sleep(delay);
+                            // Create the new sleep (following the program clock)
+                            let sleep = Clock::sleep(delay);
 
                             // Act on the first to return
                             tokio::select! {
@@ -365,7 +555,7 @@ impl Queue {
 
                                 // If the delay expires instead
                                 _ = sleep => {
-                                    // Remove the last event from the list
+                                    // Remove the last event from the list (unless it was frozen in the meantime)
                                     let last_event = coming_events.pop_if(&event).await;
 
                                     // Send it if it matches what we expected. Otherwise, do nothing.
@@ -385,15 +575,28 @@ impl Queue {
     /// A method to add a new event to the queue.
     ///
     /// This function adds the new event to the existing queue. This event may
-    /// preceed existing events in the queue.
+    /// preceed existing events in the queue. If an event with the same id is
+    /// already in the queue, the conflict policy of the new event determines
+    /// whether the existing event is replaced, the new event is ignored, or
+    /// both are kept.
     ///
     pub async fn add_event(&mut self, event: EventDelay) {
+        self.add_event_at(event, Clock::now()).await;
+    }
+
+    /// A method to add a new event to the queue, with the delay starting at
+    /// the provided time. Events added with the same start time and delay
+    /// trigger in order of their priority.
+    ///
+    pub async fn add_event_at(&mut self, event: EventDelay, start_time: NaiveDateTime) {
         // Sort between delayed events and static events
         match event.delay() {
             // Load delayed events into the queue
             Some(delay) => {
                 // Create a coming event and send it to the queue
-                let coming = ComingEvent::new(delay, event.id());
+                let mut coming = ComingEvent::starting_at(start_time, delay, event.id());
+                coming.priority = event.priority();
+                coming.conflict = event.conflict();
                 self.queue_load.send(coming).await.unwrap_or(());
             }
 
@@ -402,6 +605,67 @@ impl Queue {
         }
     }
 
+    /// A method to add a repeating event to the queue. The event is triggered
+    /// every interval until the count or the end time is reached, or the
+    /// event is cancelled. Only the next occurrence appears in the queue.
+    ///
+    pub async fn add_repeating_event(
+        &mut self,
+        event_id: ItemId,
+        interval: Duration,
+        count: Option<u32>,
+        end_after: Option<Duration>,
+    ) {
+        // Ignore events without an interval, to avoid an endless loop
+        if interval == Duration::from_secs(0) {
+            log!(warn &self.interface_send => "Repeating Event Has No Interval: {}", event_id);
+            return;
+        }
+
+        // Create the first occurrence and send it to the queue
+        if let Some(coming) = ComingEvent::repeating(event_id, interval, count, end_after) {
+            self.queue_load.send(coming).await.unwrap_or(());
+        }
+    }
+
+    /// A method to add a scheduled event to the queue. The event is triggered
+    /// at the next time that matches the schedule rule, and then at each
+    /// following match. Only the next occurrence appears in the queue.
+    ///
+    pub async fn add_scheduled_event(&mut self, event_id: ItemId, rule: ScheduleRule) {
+        // Create the next occurrence and send it to the queue
+        if let Some(coming) = ComingEvent::scheduled(event_id, rule, Clock::now()) {
+            self.queue_load.send(coming).await.unwrap_or(());
+        }
+    }
+
+    /// A method to restore an event to the queue with the provided remaining
+    /// time, hold, repetition and priority (used when restoring a backup of
+    /// the queue).
+    ///
+    pub async fn restore_event(
+        &mut self,
+        remaining: Duration,
+        event_id: ItemId,
+        is_held: bool,
+        repeat: Option<Repetition>,
+        priority: u8,
+    ) {
+        // Create the coming event
+        let mut coming = ComingEvent::new(remaining, event_id);
+        coming.repeat = repeat;
+        coming.priority = priority;
+
+        // Keep held events frozen
+        if is_held {
+            coming.paused = Some(remaining);
+            coming.is_held = true;
+        }
+
+        // Send the event to the queue
+        self.queue_load.send(coming).await.unwrap_or(());
+    }
+
     /// A method to check the remaining time until an event is triggered. If
     /// multiple events with the same id are in the queue, the remaining time
     /// until the earliest event (the one with the shortest delay) is provided.
@@ -438,14 +702,19 @@ impl Queue {
     /// release the lock on the queue. If the background process hangs, this
     /// function may hang as well.
     ///
-    pub async fn adjust_event(&mut self, new_event: ComingEvent) {
+    pub async fn adjust_event(&mut self, mut new_event: ComingEvent) {
         // Try to open the coming events
-        let possible_event = self.coming_events.withdraw(new_event).await;
+        let possible_event = self.coming_events.withdraw(&new_event).await;
 
         // Check to see if the operation was successful
         if let Some(event) = possible_event {
+            // Keep any repetition, schedule, or priority of the event
+            new_event.repeat = event.repeat;
+            new_event.schedule = event.schedule;
+            new_event.priority = event.priority;
+
             // If successful, send the new event to the queue. This also triggers the queue to notice the change.
-            self.queue_load.send(event).await.unwrap_or(());
+            self.queue_load.send(new_event).await.unwrap_or(());
         } // fail silently
     }
 
@@ -482,6 +751,12 @@ impl Queue {
                             start_time: event.start_time,
                             delay: event.delay + adjustment,
                             event_id: event.id(),
+                            paused: event.paused.map(|remaining| remaining + adjustment),
+                            is_held: event.is_held,
+                            repeat: event.repeat,
+                            schedule: event.schedule,
+                            priority: event.priority,
+                            conflict: event.conflict,
                         })
                         .await
                         .unwrap_or(());
@@ -510,6 +785,12 @@ impl Queue {
                                         start_time: event.start_time,
                                         delay,
                                         event_id: event.id(),
+                                        paused: event.paused.map(|remaining| remaining - adjustment),
+                                        is_held: event.is_held,
+                                        repeat: event.repeat,
+                                        schedule: event.schedule,
+                                        priority: event.priority,
+                                        conflict: event.conflict,
                                     })
                                     .await
                                     .unwrap_or(());
@@ -525,6 +806,63 @@ impl Queue {
         }
     }
 
+    /// A method to hold a specific upcoming event. The remaining time of the
+    /// event is frozen until the event is released, while the other events
+    /// continue.
+    ///
+    /// # Errors
+    ///
+    /// If the provided event does not exist in the queue, this method will
+    /// fail silently.
+    ///
+    pub async fn hold_event(&mut self, event: ComingEvent) {
+        self.coming_events.hold(&event).await;
+    }
+
+    /// A method to release a specific held event. The event is triggered
+    /// after the time that was remaining when it was held.
+    ///
+    /// # Errors
+    ///
+    /// If the provided event is not held in the queue, this method will
+    /// fail silently.
+    ///
+    pub async fn release_event(&mut self, event: ComingEvent) {
+        // Try to release the event
+        if let Some(event) = self.coming_events.release(&event).await {
+            // If successful, send the event to the queue again
+            self.queue_load.send(event).await.unwrap_or(());
+        }
+    }
+
+    /// A method to remove a specific upcoming event from the queue and
+    /// trigger it immediately (even if the event or the queue is paused). If
+    /// the event repeats, the next occurrence is one interval from now. If the
+    /// event is scheduled, the next occurrence follows the one that was fired.
+    ///
+    /// # Errors
+    ///
+    /// If the provided event does not exist in the queue, this method will
+    /// fail silently.
+    ///
+    pub async fn fire_event(&mut self, event: ComingEvent) {
+        // Try to withdraw the event from the queue
+        if let Some(event) = self.coming_events.withdraw(&event).await {
+            // If successful, trigger the event now
+            self.interface_send.send_event(event.id(), true, true).await;
+
+            // Add the next occurrence, if the event repeats or is scheduled
+            let now = Clock::now();
+            let after = match event.schedule {
+                Some(_) => event.trigger_time().max(now),
+                None => now,
+            };
+            if let Some(next) = event.next_occurrence(after) {
+                self.queue_load.send(next).await.unwrap_or(());
+            }
+        }
+    }
+
     /// A method to cancel a specific upcoming event.
     ///
     /// # Errors
@@ -540,7 +878,7 @@ impl Queue {
     ///
     pub async fn cancel_event(&mut self, new_event: ComingEvent) {
         // Try to withdraw the existing event from the queue
-        self.coming_events.withdraw(new_event).await; // Queue will automatically detect the change
+        self.coming_events.withdraw(&new_event).await; // Queue will automatically detect the change
     }
 
     /// A method to cancel all upcoming instances of an event.
@@ -561,6 +899,39 @@ impl Queue {
         self.coming_events.cancel(event_id).await; // Queue will automatically detect the change
     }
 
+    /// A method to cancel all scheduled events in the queue. Other events
+    /// are not affected.
+    ///
+    pub async fn cancel_scheduled(&mut self) {
+        self.coming_events.cancel_scheduled().await; // Queue will automatically detect the change
+    }
+
+    /// A method to pause the queue. The remaining time of each event is
+    /// frozen and no events are triggered until the queue is resumed.
+    ///
+    /// # Note
+    ///
+    /// Events added while the queue is paused are frozen with their full
+    /// delay. Events without a delay are still processed immediately, and
+    /// scheduled events still trigger at the scheduled time.
+    ///
+    pub async fn pause(&mut self) {
+        self.coming_events.pause().await;
+    }
+
+    /// A method to resume the queue. Each event is restarted with the
+    /// remaining time it had when the queue was paused.
+    ///
+    pub async fn resume(&mut self) {
+        // Withdraw the paused events and load them again
+        for event in self.coming_events.resume() {
+            self.queue_load.send(event).await.unwrap_or(());
+        }
+
+        // Update the system (in case the queue was empty)
+        self.coming_events.send_current().await;
+    }
+
     /// A method to clear any events in the queue.
     ///
     /// # Note
@@ -688,4 +1059,439 @@ mod tests {
         // If they were not found, fail the test
         panic!("Failed test vector comparison.");
     }
+
+    // Test pausing and resuming the queue
+    #[tokio::test]
+    async fn pause_events() {
+        // Import libraries for testing
+        use crate::definitions::{InternalSend, InternalUpdate};
+        use std::time::Duration;
+        use tokio::time::sleep;
+
+        // Create a channel for receiving messages from the queue
+        let (tx, mut rx) = InternalSend::new();
+
+        // Create a new message queue and pause it
+        let mut queue = Queue::new(tx);
+        queue
+            .add_event(EventDelay::new(
+                Some(Duration::from_millis(40)),
+                ItemId::new(40).unwrap(),
+            ))
+            .await;
+        queue.pause().await;
+
+        // Add another event while paused
+        queue
+            .add_event(EventDelay::new(
+                Some(Duration::from_millis(20)),
+                ItemId::new(20).unwrap(),
+            ))
+            .await;
+
+        // Make sure no events are triggered while paused
+        let mut is_paused = false;
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    match update {
+                        InternalUpdate::ProcessEvent { .. } => panic!("Event triggered while paused."),
+                        InternalUpdate::ComingEvents(events, paused) => {
+                            // Check that the remaining time is frozen
+                            for event in events.iter() {
+                                assert!(event.paused.is_some());
+                            }
+                            is_paused = paused;
+                        }
+                        _ => (),
+                    }
+                }
+
+                // Wait longer than both events
+                _ = sleep(Duration::from_millis(100)) => break,
+            }
+        }
+        assert!(is_paused);
+
+        // Resume the queue and check the order of the events
+        queue.resume().await;
+        let reference = vec![ItemId::new_unchecked(20), ItemId::new_unchecked(40)];
+        let mut received = Vec::new();
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        received.push(event);
+                    }
+
+                    // Check if the received vector matches the test vector
+                    if reference == received {
+                        return;
+                    }
+                }
+
+                // Only wait half a second
+                _ = sleep(Duration::from_millis(500)) => break,
+            }
+        }
+
+        // If they were not found, fail the test
+        panic!("Failed to resume: {:?}", received);
+    }
+
+    // Test holding, releasing, and firing individual events
+    #[tokio::test]
+    async fn hold_events() {
+        // Import libraries for testing
+        use crate::definitions::{InternalSend, InternalUpdate};
+        use std::time::Duration;
+        use tokio::time::sleep;
+
+        // Create a channel for receiving messages from the queue
+        let (tx, mut rx) = InternalSend::new();
+
+        // Create a new message queue and load three events
+        let mut queue = Queue::new(tx);
+        for delay in [20, 40, 200] {
+            queue
+                .add_event(EventDelay::new(
+                    Some(Duration::from_millis(delay)),
+                    ItemId::new(delay as u32).unwrap(),
+                ))
+                .await;
+        }
+
+        // Find the coming events once all three are loaded
+        let mut coming = Vec::new();
+        while coming.len() < 3 {
+            if let Some(InternalUpdate::ComingEvents(events, _)) = rx.recv().await {
+                coming = events;
+            }
+        }
+        let find = |id: u32| {
+            *coming
+                .iter()
+                .find(|event| event.id() == ItemId::new_unchecked(id))
+                .unwrap()
+        };
+
+        // Hold the first event and fire the last event now
+        queue.hold_event(find(20)).await;
+        queue.fire_event(find(200)).await;
+
+        // Check that only the other events are triggered
+        let mut received = Vec::new();
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        received.push(event);
+                    }
+                }
+
+                // Wait longer than the held event
+                _ = sleep(Duration::from_millis(100)) => break,
+            }
+        }
+        assert_eq!(received, vec![ItemId::new_unchecked(200), ItemId::new_unchecked(40)]);
+
+        // Release the held event and check that it is triggered
+        queue.release_event(find(20)).await;
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        assert_eq!(event, ItemId::new_unchecked(20));
+                        return;
+                    }
+                }
+
+                // Only wait half a second
+                _ = sleep(Duration::from_millis(500)) => break,
+            }
+        }
+
+        // If it was not found, fail the test
+        panic!("Held event was not released.");
+    }
+
+    // Test repeating events with a count and cancelling them
+    #[tokio::test]
+    async fn repeat_events() {
+        // Import libraries for testing
+        use crate::definitions::{InternalSend, InternalUpdate};
+        use std::time::Duration;
+        use tokio::time::sleep;
+
+        // Create a channel for receiving messages from the queue
+        let (tx, mut rx) = InternalSend::new();
+
+        // Create a new message queue and load two repeating events
+        let mut queue = Queue::new(tx);
+        queue
+            .add_repeating_event(ItemId::new_unchecked(10), Duration::from_millis(20), Some(3), None)
+            .await;
+        queue
+            .add_repeating_event(ItemId::new_unchecked(30), Duration::from_millis(30), None, None)
+            .await;
+
+        // Collect the triggered events and check the timeline size
+        let mut received = Vec::new();
+        let deadline = sleep(Duration::from_millis(150));
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    match update {
+                        InternalUpdate::ProcessEvent { event, .. } => received.push(event),
+                        InternalUpdate::ComingEvents(events, _) => assert!(events.len() <= 2),
+                        _ => (),
+                    }
+                }
+
+                // Wait long enough for the limited event to finish
+                _ = &mut deadline => break,
+            }
+        }
+
+        // Check the count of the limited event and the repetition of the other
+        let limited = received.iter().filter(|id| **id == ItemId::new_unchecked(10)).count();
+        let unlimited = received.iter().filter(|id| **id == ItemId::new_unchecked(30)).count();
+        assert_eq!(limited, 3);
+        assert!(unlimited >= 2);
+
+        // Cancel the repeating event and make sure it stops
+        queue.cancel_all(ItemId::new_unchecked(30)).await;
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        panic!("Event triggered after cancel: {}", event);
+                    }
+                }
+
+                // Wait for several intervals
+                _ = sleep(Duration::from_millis(100)) => break,
+            }
+        }
+    }
+
+    // Test scheduled events and cancelling them
+    #[tokio::test]
+    async fn schedule_events() {
+        // Import libraries for testing
+        use crate::definitions::{InternalSend, InternalUpdate};
+        use chrono::Duration as ChronoDuration;
+        use std::time::Duration;
+        use tokio::time::sleep;
+
+        // Create a channel for receiving messages from the queue
+        let (tx, mut rx) = InternalSend::new();
+
+        // Create a new message queue and schedule three events
+        let mut queue = Queue::new(tx);
+        let now = Clock::now();
+        for delay in [20, 200, 600] {
+            queue
+                .add_scheduled_event(
+                    ItemId::new(delay).unwrap(),
+                    ScheduleRule::At {
+                        time: now + ChronoDuration::milliseconds(delay as i64),
+                    },
+                )
+                .await;
+        }
+
+        // Wait for the first event to trigger
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        assert_eq!(event, ItemId::new_unchecked(20));
+                        break;
+                    }
+                }
+
+                // Only wait half a second
+                _ = sleep(Duration::from_millis(500)) => panic!("Scheduled event was not triggered."),
+            }
+        }
+
+        // Pause the queue and make sure the second event still triggers on time
+        queue.pause().await;
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        assert_eq!(event, ItemId::new_unchecked(200));
+                        break;
+                    }
+                }
+
+                // Only wait half a second
+                _ = sleep(Duration::from_millis(500)) => panic!("Scheduled event was frozen by the pause."),
+            }
+        }
+        queue.resume().await;
+
+        // Cancel the scheduled events and make sure the third does not trigger
+        queue.cancel_scheduled().await;
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        panic!("Event triggered after cancel: {}", event);
+                    }
+                }
+
+                // Wait longer than the third event
+                _ = sleep(Duration::from_millis(500)) => break,
+            }
+        }
+    }
+
+    // Test running a full game script on the virtual clock at rehearsal speed
+    #[tokio::test(start_paused = true)]
+    async fn rehearse_script() {
+        // Import libraries for testing
+        use crate::definitions::{InternalSend, InternalUpdate};
+        use std::time::Duration;
+        use tokio::time::Instant;
+
+        // Run the clock at four times the normal speed
+        Clock::set_virtual(4.0);
+        let start = Clock::now();
+        let real_start = Instant::now();
+
+        // Create a channel for receiving messages from the queue
+        let (tx, mut rx) = InternalSend::new();
+
+        // Load an event every ten minutes and three reminders every twelve minutes
+        let mut queue = Queue::new(tx);
+        for minutes in (10..=60).step_by(10) {
+            queue
+                .add_event(EventDelay::new(
+                    Some(Duration::from_secs(minutes * 60)),
+                    ItemId::new_unchecked(minutes as u32),
+                ))
+                .await;
+        }
+        queue
+            .add_repeating_event(ItemId::new_unchecked(100), Duration::from_secs(12 * 60), Some(3), None)
+            .await;
+
+        // Collect the triggered events until the end of the script
+        let mut received = Vec::new();
+        let deadline = Clock::sleep(Duration::from_secs(61 * 60));
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        received.push((event, Clock::elapsed(start)));
+                    }
+                }
+
+                // Stop just after the end of the script
+                _ = &mut deadline => break,
+            }
+        }
+
+        // Check that the events triggered in order at the correct clock times
+        let expected: Vec<ItemId> = [10, 100, 20, 100, 30, 100, 40, 50, 60]
+            .iter()
+            .map(|id| ItemId::new_unchecked(*id))
+            .collect();
+        assert_eq!(
+            received.iter().map(|(event, _)| *event).collect::<Vec<ItemId>>(),
+            expected
+        );
+        for (event, elapsed) in received.iter() {
+            if event.id() != 100 {
+                assert!(*elapsed >= Duration::from_secs(event.id() as u64 * 60));
+                assert!(*elapsed < Duration::from_secs(event.id() as u64 * 60 + 5));
+            }
+        }
+
+        // Check that the script took a quarter of the time
+        assert!(real_start.elapsed() <= Duration::from_secs(16 * 60));
+    }
+
+    // Test the priority and conflict policy of cued events
+    #[tokio::test]
+    async fn priority_and_conflict() {
+        // Import libraries for testing
+        use crate::definitions::{InternalSend, InternalUpdate};
+        use std::time::Duration;
+
+        // Create a channel for receiving messages from the queue
+        let (tx, mut rx) = InternalSend::new();
+
+        // Load three events with the same start time, one with a higher priority
+        let mut queue = Queue::new(tx);
+        let start_time = Clock::now();
+        let delay = Some(Duration::from_millis(100));
+        for (id, priority) in [(1, 0), (2, 5), (3, 0)].iter() {
+            queue
+                .add_event_at(
+                    EventDelay::with_policy(
+                        delay,
+                        ItemId::new_unchecked(*id),
+                        *priority,
+                        ConflictPolicy::KeepBoth,
+                    ),
+                    start_time,
+                )
+                .await;
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+
+        // Load two events, then immediately try to ignore one and replace the other
+        queue
+            .add_event(EventDelay::new(Some(Duration::from_millis(200)), ItemId::new_unchecked(4)))
+            .await;
+        queue
+            .add_event(EventDelay::new(Some(Duration::from_millis(200)), ItemId::new_unchecked(5)))
+            .await;
+        queue
+            .add_event(EventDelay::with_policy(
+                Some(Duration::from_millis(300)),
+                ItemId::new_unchecked(4),
+                0,
+                ConflictPolicy::Ignore,
+            ))
+            .await;
+        queue
+            .add_event(EventDelay::with_policy(
+                Some(Duration::from_millis(300)),
+                ItemId::new_unchecked(5),
+                0,
+                ConflictPolicy::Replace,
+            ))
+            .await;
+
+        // Collect the triggered events
+        let mut received = Vec::new();
+        let deadline = tokio::time::sleep(Duration::from_millis(500));
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                Some(update) = rx.recv() => {
+                    if let InternalUpdate::ProcessEvent { event, .. } = update {
+                        received.push(event);
+                    }
+                }
+
+                // Wait for all the events to trigger
+                _ = &mut deadline => break,
+            }
+        }
+
+        // Check the order and the number of events
+        let expected: Vec<ItemId> = [2, 1, 3, 4, 5]
+            .iter()
+            .map(|id| ItemId::new_unchecked(*id))
+            .collect();
+        assert_eq!(received, expected);
+    }
 }
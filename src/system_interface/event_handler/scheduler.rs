@@ -0,0 +1,178 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to schedule events at absolute local times or according to
+//! cron-like rules. The next occurrence of each scheduled event is placed in
+//! the queue (where it is visible in the timeline), and the queue loads the
+//! following occurrence when the event is triggered.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::queue::Queue;
+
+// Import Chrono features
+use chrono::NaiveDateTime;
+
+/// A structure to hold the scheduled events from the configuration and
+/// whether the schedule is enabled.
+///
+pub struct Scheduler {
+    schedule: Vec<ScheduledEvent>, // the scheduled events from the configuration
+    is_enabled: bool,              // a flag to indicate that the schedule is enabled
+}
+
+// Implement the scheduler features
+impl Scheduler {
+    /// A function to create a new scheduler from the scheduled events.
+    ///
+    pub fn new(schedule: Vec<ScheduledEvent>, is_enabled: bool) -> Scheduler {
+        Scheduler {
+            schedule,
+            is_enabled,
+        }
+    }
+
+    /// A method to indicate if the schedule is enabled.
+    ///
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    /// A method to load the next occurrence of each scheduled event into the
+    /// queue, if the schedule is enabled.
+    ///
+    pub async fn load(&self, queue: &mut Queue) {
+        // Do nothing if the schedule is disabled
+        if !self.is_enabled {
+            return;
+        }
+
+        // Add each scheduled event
+        for scheduled in self.schedule.iter() {
+            queue
+                .add_scheduled_event(scheduled.event, scheduled.rule)
+                .await;
+        }
+    }
+
+    /// A method to enable or disable the schedule. When enabled, the
+    /// scheduled events are loaded into the queue. When disabled, they are
+    /// removed from the queue.
+    ///
+    pub async fn set_enabled(&mut self, queue: &mut Queue, is_enabled: bool) {
+        // Do nothing if the setting has not changed
+        if self.is_enabled == is_enabled {
+            return;
+        }
+
+        // Update the setting and the queue
+        self.is_enabled = is_enabled;
+        if is_enabled {
+            self.load(queue).await;
+        } else {
+            queue.cancel_scheduled().await;
+        }
+    }
+
+    /// A method to find the scheduled events that were missed while the
+    /// program was stopped and should be caught up. Takes the event id and
+    /// the time each scheduled event was due (from the backup) and the
+    /// current time.
+    ///
+    pub fn missed(&self, due_events: &[(ItemId, NaiveDateTime)], now: NaiveDateTime) -> Vec<ItemId> {
+        // Do nothing if the schedule is disabled
+        if !self.is_enabled {
+            return Vec::new();
+        }
+
+        // Find each event which was due before now and allows catching up
+        let mut missed = Vec::new();
+        for (event_id, due) in due_events.iter() {
+            // Skip events which are not yet due
+            if *due > now {
+                continue;
+            }
+
+            // Check that the event is still scheduled and should be caught up
+            if self
+                .schedule
+                .iter()
+                .any(|scheduled| scheduled.event == *event_id && scheduled.catch_up)
+                && !missed.contains(event_id)
+            {
+                missed.push(*event_id);
+            }
+        }
+        missed
+    }
+}
+
+// Tests of the scheduler module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test finding the missed events after a restart
+    #[test]
+    fn missed_events() {
+        // Import chrono features
+        use chrono::NaiveDate;
+
+        // Create a schedule with one event to catch up and one to skip
+        let rule = ScheduleRule::At {
+            time: NaiveDate::from_ymd(2021, 6, 4).and_hms(21, 55, 0),
+        };
+        let scheduler = Scheduler::new(
+            vec![
+                ScheduledEvent {
+                    event: ItemId::new_unchecked(10),
+                    rule,
+                    catch_up: true,
+                },
+                ScheduledEvent {
+                    event: ItemId::new_unchecked(20),
+                    rule,
+                    catch_up: false,
+                },
+            ],
+            true,
+        );
+
+        // Check the missed events from shortly after the due time
+        let due = NaiveDate::from_ymd(2021, 6, 4).and_hms(21, 55, 0);
+        let now = NaiveDate::from_ymd(2021, 6, 4).and_hms(22, 0, 0);
+        let due_events = vec![
+            (ItemId::new_unchecked(10), due),
+            (ItemId::new_unchecked(20), due),
+            (ItemId::new_unchecked(30), due),
+        ];
+        assert_eq!(
+            scheduler.missed(&due_events, now),
+            vec![ItemId::new_unchecked(10)]
+        );
+
+        // Check that events not yet due are not missed
+        let earlier = NaiveDate::from_ymd(2021, 6, 4).and_hms(21, 0, 0);
+        assert!(scheduler.missed(&due_events, earlier).is_empty());
+
+        // Check that nothing is missed when the schedule is disabled
+        let scheduler = Scheduler::new(scheduler.schedule, false);
+        assert!(scheduler.missed(&due_events, now).is_empty());
+    }
+}
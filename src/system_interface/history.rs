@@ -0,0 +1,223 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to keep a bounded history of the status and scene changes made
+//! by the operator so that they can be undone and redone.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::collections::VecDeque;
+
+// Define module constants
+const HISTORY_LIMIT: usize = 50; // the maximum number of changes to remember
+
+/// An enum to describe a single change made by the operator
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// A variant for a change to a status (including the count, value, and
+    /// remaining time)
+    Status {
+        status_id: ItemId,       // the status which was changed
+        previous: SavedStatus,   // the status before the change
+        new_status: SavedStatus, // the status after the change
+    },
+
+    /// A variant for a change to the current scene
+    Scene {
+        previous: ItemId,  // the scene before the change
+        new_scene: ItemId, // the scene after the change
+    },
+}
+
+// Implement key features of the change
+impl Change {
+    /// A method to return the change which reverses this change
+    ///
+    pub fn reversed(self) -> Change {
+        match self {
+            Change::Status {
+                status_id,
+                previous,
+                new_status,
+            } => Change::Status {
+                status_id,
+                previous: new_status,
+                new_status: previous,
+            },
+            Change::Scene {
+                previous,
+                new_scene,
+            } => Change::Scene {
+                previous: new_scene,
+                new_scene: previous,
+            },
+        }
+    }
+}
+
+/// A structure to hold the undo and redo history of the operator changes
+///
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Change>, // the changes which can be undone (most recent at the back)
+    redo: Vec<Change>,      // the changes which can be redone (most recent at the back)
+}
+
+// Implement key features of the history
+impl History {
+    /// A function to create a new, empty history
+    ///
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    /// A method to record a new change. Any changes which were undone can no
+    /// longer be redone, and the oldest change is forgotten when the history
+    /// is full.
+    ///
+    pub fn record(&mut self, change: Change) {
+        // Clear the redo history
+        self.redo.clear();
+
+        // Add the change, forgetting the oldest change if necessary
+        self.undo.push_back(change);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+
+    /// A method to take the most recent change to undo, if there is one. The
+    /// change is moved to the redo history.
+    ///
+    pub fn undo(&mut self) -> Option<Change> {
+        let change = self.undo.pop_back()?;
+        self.redo.push(change.clone());
+        Some(change)
+    }
+
+    /// A method to take the most recently undone change to redo, if there is
+    /// one. The change is moved back to the undo history.
+    ///
+    pub fn redo(&mut self) -> Option<Change> {
+        let change = self.redo.pop()?;
+        self.undo.push_back(change.clone());
+        Some(change)
+    }
+
+    /// A method to forget all the changes (for example, when a new
+    /// configuration is loaded)
+    ///
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// A helper function to indicate whether a status was changed. The
+/// remaining time of a timer is compared in whole seconds, so that a running
+/// timer is not changed just by the time between the two saves.
+///
+pub fn is_changed(previous: &SavedStatus, new_status: &SavedStatus) -> bool {
+    (previous.current, previous.count, previous.value, previous.is_running)
+        != (new_status.current, new_status.count, new_status.value, new_status.is_running)
+        || previous.remaining.map(whole_seconds) != new_status.remaining.map(whole_seconds)
+}
+
+// Tests of the history module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Import standard library features
+    use std::time::Duration;
+
+    // Test recording, undoing, and redoing changes
+    #[test]
+    fn undo_and_redo() {
+        // Create placeholder changes (only the count of the status changes)
+        let previous = SavedStatus {
+            current: ItemId::new_unchecked(10),
+            count: Some(1),
+            value: None,
+            remaining: None,
+            is_running: false,
+        };
+        let new_status = SavedStatus {
+            count: Some(2),
+            ..previous.clone()
+        };
+        assert!(is_changed(&previous, &new_status));
+
+        // Check that a running timer is only changed by a whole second or more
+        let timer = SavedStatus {
+            remaining: Some(Duration::from_millis(30_500)),
+            is_running: true,
+            ..previous.clone()
+        };
+        let later = SavedStatus {
+            remaining: Some(Duration::from_millis(30_400)),
+            ..timer.clone()
+        };
+        let reset = SavedStatus {
+            remaining: Some(Duration::from_secs(60)),
+            ..timer.clone()
+        };
+        assert!(!is_changed(&timer, &later));
+        assert!(is_changed(&timer, &reset));
+        let status_change = Change::Status {
+            status_id: ItemId::new_unchecked(1),
+            previous,
+            new_status,
+        };
+        let scene_change = Change::Scene {
+            previous: ItemId::new_unchecked(20),
+            new_scene: ItemId::new_unchecked(21),
+        };
+
+        // Check undoing and redoing the changes in order
+        let mut history = History::new();
+        assert_eq!(None, history.undo());
+        history.record(status_change.clone());
+        history.record(scene_change.clone());
+        assert_eq!(Some(scene_change.clone()), history.undo());
+        assert_eq!(Some(status_change.clone()), history.undo());
+        assert_eq!(None, history.undo());
+        assert_eq!(Some(status_change.clone()), history.redo());
+        assert_eq!(Some(scene_change.clone()), history.redo());
+        assert_eq!(None, history.redo());
+
+        // Check that a new change clears the redo history
+        assert_eq!(Some(scene_change.clone()), history.undo());
+        history.record(status_change.clone().reversed());
+        assert_eq!(None, history.redo());
+        assert_eq!(Some(status_change.clone().reversed()), history.undo());
+
+        // Check that the history is bounded
+        history.clear();
+        for _ in 0..(HISTORY_LIMIT + 5) {
+            history.record(status_change.clone());
+        }
+        let mut count = 0;
+        while history.undo().is_some() {
+            count += 1;
+        }
+        assert_eq!(HISTORY_LIMIT, count);
+    }
+}
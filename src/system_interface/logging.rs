@@ -19,26 +19,46 @@
 //!
 //! Current event updates are sent to the provided broadcast line. Error updates
 //! are logged to the provided log file. Other updates are converted to a human
-//! readable format and returned to higher-level modules.
+//! readable format and returned to higher-level modules. Every update is also
+//! recorded in the structured game log (as JSON lines) so that the history of
+//! the game can be queried.
 
 // Import crate definitions
 use crate::definitions::*;
 
 // Import standard library modules
 use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 
 // Import the chrono library
-use chrono::{Duration, Local};
+use chrono::{Duration, NaiveDateTime};
 
 // Import the failure features
 use failure::Error as FailureError;
 
+// Import tokio features
+use tokio::task::{spawn_blocking, JoinHandle};
+
+// Define module constants
+const QUERY_LIMIT: usize = 1000; // the default (and maximum) number of entries to return from a query
+const GAME_LOG_FORMAT: &str = "game_log_%F_%H-%M"; // the file name format of the game logs
+
+/// A helper function to return the start time of a game log from the file
+/// name, if it follows the game log format.
+///
+fn game_log_time(path: &std::path::Path) -> Option<NaiveDateTime> {
+    let name = path.file_stem()?.to_str()?;
+    NaiveDateTime::parse_from_str(name, GAME_LOG_FORMAT).ok()
+}
+
 /// A structure to handle all logging and update processing for the program.
 ///
 pub struct Logger {
     game_log: Option<File>,               // game log file for the program
+    structured_log: Option<(PathBuf, File)>, // structured game log path and file for the program
+    log_folder: Option<PathBuf>,          // the folder of the game logs, if specified
+    current_scene: Option<ItemId>,        // the current scene for the structured game log, if any
     error_log: Option<File>,              // error log file for the program
     old_notifications: Vec<Notification>, // internal list of notifications less than 1 minute old
     index_access: IndexAccess,            // the item index access point
@@ -68,21 +88,28 @@ impl Logger {
         interface_send: InterfaceSend,
     ) -> Result<Logger, FailureError> {
         // Attempt to open the game log file
-        let game_log = match log_path {
+        let log_folder = log_path.clone();
+        let (game_log, structured_log) = match log_path {
             // If a file was specified, try to load it
             Some(mut filepath) => {
                 // Use the current time for each instance
-                filepath.push(format!("game_log_{}", Local::now().format("%F_%H-%M")).as_str());
+                filepath.push(Clock::now().format(GAME_LOG_FORMAT).to_string().as_str());
 
                 // Create the new file instance
-                match File::create(filepath.to_str().unwrap_or("")) {
-                    Ok(file) => Some(file),
+                let game_log = match File::create(filepath.to_str().unwrap_or("")) {
+                    Ok(file) => file,
                     Err(_) => return Err(format_err!("Unable to create game log file.")),
+                };
+
+                // Create the structured log alongside it
+                match Logger::open_structured(filepath) {
+                    Some(structured_log) => (Some(game_log), Some(structured_log)),
+                    None => return Err(format_err!("Unable to create structured game log file.")),
                 }
             }
 
             // If a file was not specified, run without a log file
-            None => None,
+            None => (None, None),
         };
 
         // Attempt to open the error log file
@@ -113,6 +140,9 @@ impl Logger {
         // Return the new logger
         Ok(Logger {
             game_log,
+            structured_log,
+            log_folder,
+            current_scene: None,
             error_log,
             old_notifications: Vec::new(),
             index_access,
@@ -130,7 +160,112 @@ impl Logger {
         self.game_log = match File::create(&log_path.to_str().unwrap_or("")) {
             Ok(file) => Some(file),
             Err(_) => None,
-        }
+        };
+
+        // Attempt to open the structured log alongside it
+        self.structured_log = Logger::open_structured(log_path);
+    }
+
+    /// A helper function to open (or create) the structured game log
+    /// alongside the provided game log path.
+    ///
+    fn open_structured(mut log_path: PathBuf) -> Option<(PathBuf, File)> {
+        log_path.set_extension("jsonl");
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .ok()?;
+        Some((log_path, file))
+    }
+
+    /// A method to set the current scene, which is recorded with every entry
+    /// in the structured game log.
+    ///
+    pub fn set_scene(&mut self, current_scene: Option<ItemId>) {
+        self.current_scene = current_scene;
+    }
+
+    /// A method to query the history in the structured game logs. Returns
+    /// the entries between the start and end times (if specified) with one of
+    /// the provided types (or any type, if none are provided), oldest first.
+    /// The first offset entries are skipped, and at most limit entries (or
+    /// the default limit) are returned.
+    ///
+    /// # Notes
+    ///
+    /// The history includes every structured game log in the log folder, as
+    /// well as the current structured game log. The logs are read in a
+    /// blocking task, so the returned handle should be awaited separately
+    /// from the rest of the system.
+    ///
+    pub fn query(
+        &self,
+        start: Option<NaiveDateTime>,
+        end: Option<NaiveDateTime>,
+        log_types: Vec<LogType>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> JoinHandle<Vec<LogEntry>> {
+        // Copy the locations of the logs
+        let log_folder = self.log_folder.clone();
+        let current = self.structured_log.as_ref().map(|(path, _)| path.clone());
+        let limit = limit.unwrap_or(QUERY_LIMIT).min(QUERY_LIMIT);
+
+        // Read the logs in the background
+        spawn_blocking(move || {
+            // Collect the structured logs in the log folder
+            let mut paths = Vec::new();
+            if let Some(Ok(dir)) = log_folder.as_ref().map(std::fs::read_dir) {
+                for path in dir.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                    if path.extension().is_some_and(|extension| extension == "jsonl") {
+                        paths.push(path);
+                    }
+                }
+            }
+
+            // Add the current structured log, if it is elsewhere
+            if let Some(path) = current {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+
+            // Skip any logs which started outside the time range (a game is
+            // assumed to last less than a day)
+            paths.retain(|path| match game_log_time(path) {
+                Some(time) => {
+                    end.is_none_or(|end| time <= end)
+                        && start.is_none_or(|start| time + Duration::days(1) >= start)
+                }
+                None => true,
+            });
+
+            // Read the matching entries from each log (ignoring unreadable lines)
+            let mut entries = Vec::new();
+            for path in paths {
+                if let Ok(file) = File::open(&path) {
+                    for line in BufReader::new(file).lines().map_while(Result::ok) {
+                        if let Ok(entry) = serde_json::from_str::<LogEntry>(&line) {
+                            // Check the time and type of the entry
+                            if start.is_none_or(|start| entry.time >= start)
+                                && end.is_none_or(|end| entry.time <= end)
+                                && (log_types.is_empty() || log_types.contains(&entry.log_type))
+                            {
+                                entries.push(entry);
+                            }
+                        }
+                    }
+                }
+
+                // Keep only the oldest entries that could be returned
+                entries.sort_by_key(|entry| entry.time);
+                entries.truncate(offset.saturating_add(limit));
+            }
+
+            // Return the requested entries
+            entries.into_iter().skip(offset).collect()
+        })
     }
 
     /// A method to set the error log file for the logger.
@@ -163,7 +298,7 @@ impl Logger {
         // Iterate through the old notifications
         for old_note in self.old_notifications.drain(..) {
             // If the notification is younger than one minute, add it back
-            if Local::now().naive_local() < old_note.time() + Duration::minutes(1) {
+            if Clock::now() < old_note.time() + Duration::minutes(1) {
                 notifications.push(old_note);
             }
         }
@@ -175,6 +310,70 @@ impl Logger {
         return notifications;
     }
 
+    /// An internal method to record an update in the structured game log,
+    /// with the descriptions of any items and the current scene.
+    ///
+    /// # Errors
+    ///
+    /// This method ignores any errors writing to the log, as the update is
+    /// still passed to the user interface.
+    ///
+    async fn record(&mut self, update: &LogUpdate, now: NaiveDateTime) {
+        // Do nothing if there is no structured log
+        if self.structured_log.is_none() {
+            return;
+        }
+
+        // Compose the new entry with the current scene
+        let scene = match self.current_scene {
+            Some(scene_id) => Some(self.index_access.get_pair(&scene_id).await.into()),
+            None => None,
+        };
+        let mut entry = LogEntry {
+            time: now,
+            log_type: update.log_type(),
+            message: None,
+            event: None,
+            status: None,
+            state: None,
+            value: None,
+            scene,
+            data: None,
+        };
+
+        // Add the fields which apply to the update
+        match update {
+            LogUpdate::Broadcast(event_id, data) => {
+                entry.event = Some(self.index_access.get_pair(event_id).await.into());
+                entry.data = *data;
+            }
+            LogUpdate::Current(event_id) => {
+                entry.event = Some(self.index_access.get_pair(event_id).await.into());
+            }
+            LogUpdate::Error(message, event_id) | LogUpdate::Warning(message, event_id) => {
+                entry.message = Some(message.clone());
+                if let Some(event_id) = event_id {
+                    entry.event = Some(self.index_access.get_pair(event_id).await.into());
+                }
+            }
+            LogUpdate::Save(message) | LogUpdate::Update(message) => {
+                entry.message = Some(message.clone());
+            }
+            LogUpdate::Status(status_id, new_state, value) => {
+                entry.status = Some(self.index_access.get_pair(status_id).await.into());
+                entry.state = Some(self.index_access.get_pair(new_state).await.into());
+                entry.value = *value;
+            }
+        }
+
+        // Try to write the entry as a single line
+        if let (Some((_, ref mut file)), Ok(line)) =
+            (self.structured_log.as_mut(), serde_json::to_string(&entry))
+        {
+            writeln!(file, "{}", line).unwrap_or(());
+        }
+    }
+
     /// An internal method to unpack any event updates.
     ///
     /// This method sorts event updates into their various types and applies
@@ -184,7 +383,10 @@ impl Logger {
     ///
     async fn unpack_update(&mut self, update: LogUpdate) -> Notification {
         // Note the current time
-        let now = Local::now().naive_local();
+        let now = Clock::now();
+
+        // Record the update in the structured game log
+        self.record(&update, now).await;
         
         // Unpack the event update based on its subtype
         match update {
@@ -276,7 +478,7 @@ impl Logger {
             }
 
             // Update the state of a status
-            LogUpdate::Status(status_id, new_state) => {
+            LogUpdate::Status(status_id, new_state, value) => {
                 // Get the item pairs for the status and state
                 let status_pair = self.index_access.get_pair(&status_id).await;
                 let state_pair = self.index_access.get_pair(&new_state).await;
@@ -286,13 +488,20 @@ impl Logger {
                     .send(InterfaceUpdate::UpdateStatus {
                         status_id: status_pair.clone(),
                         new_state: state_pair.clone(),
+                        value,
                     }).await;
 
+                // Describe the new value, or otherwise the new state
+                let description = match value {
+                    Some(value) => value.to_string(),
+                    None => state_pair.description,
+                };
+
                 // Return the notification
                 Notification::Update {
                     message: format!(
                         "Changing {} To {}.",
-                        status_pair.description, state_pair.description
+                        status_pair.description, description
                     ),
                     time: now,
                 }
@@ -400,4 +609,102 @@ mod tests {
         assert_eq!(result[3].message(), "Test Warning".to_string());
         assert_eq!(result[4].message(), "No Active Error Log.".to_string());
     }
+
+    // Test the structured game log and the history query
+    #[tokio::test]
+    async fn structured_log() {
+        // Import crate definitions
+        use crate::definitions::*;
+
+        // Create the communication lines
+        let (internal_send, _internal_recv) = InternalSend::new();
+        let (interface_send, _web_interface_recv) = InterfaceSend::new();
+
+        // Create a test index access and load the index
+        let (index_access, _rx) = IndexAccess::new();
+        let mut index = DescriptionMap::default();
+        for (id, description) in [
+            (3, "Test Status"),
+            (4, "Test State"),
+            (5, "Test Scene"),
+            (6, "Test Event"),
+        ] {
+            index.insert(
+                ItemId::new_unchecked(id),
+                ItemDescription::new(description, Hidden { edit_location: None }),
+            );
+        }
+        index_access.send_index(index).await;
+
+        // Create a new logger instance in a new log folder
+        let folder = std::env::temp_dir().join(format!("minerva_log_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let mut logger = Logger::new(
+            Some(folder.clone()),
+            None,
+            index_access,
+            internal_send,
+            interface_send,
+        )
+        .unwrap();
+
+        // Record a series of updates in the current scene
+        logger.set_scene(Some(ItemId::new_unchecked(5)));
+        logger.update(LogUpdate::Current(ItemId::new_unchecked(6))).await;
+        logger
+            .update(LogUpdate::Status(
+                ItemId::new_unchecked(3),
+                ItemId::new_unchecked(4),
+                Some(7),
+            ))
+            .await;
+        logger.update(LogUpdate::Save("Test Data".to_string())).await;
+
+        // Query every entry
+        let entries = logger.query(None, None, Vec::new(), 0, None).await.unwrap();
+        assert_eq!(3, entries.len());
+        assert_eq!(LogType::Current, entries[0].log_type);
+        assert_eq!(
+            Some(LogItem {
+                id: 6,
+                description: "Test Event".to_string()
+            }),
+            entries[0].event
+        );
+        assert_eq!(Some(5), entries[0].scene.as_ref().map(|scene| scene.id));
+        assert_eq!(Some("Test Data".to_string()), entries[2].message);
+
+        // Query the status changes
+        let entries = logger
+            .query(None, None, vec![LogType::Status], 0, None)
+            .await
+            .unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(
+            Some("Test Status".to_string()),
+            entries[0].status.as_ref().map(|status| status.description.clone())
+        );
+        assert_eq!(Some(4), entries[0].state.as_ref().map(|state| state.id));
+        assert_eq!(Some(7), entries[0].value);
+
+        // Query by time range
+        let later = Clock::now() + Duration::seconds(1);
+        let entries = logger.query(Some(later), None, Vec::new(), 0, None).await.unwrap();
+        assert!(entries.is_empty());
+        let entries = logger.query(None, Some(later), Vec::new(), 0, None).await.unwrap();
+        assert_eq!(3, entries.len());
+
+        // Query a page of the entries
+        let entries = logger.query(None, None, Vec::new(), 1, Some(1)).await.unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(LogType::Status, entries[0].log_type);
+
+        // Check that logs which started after the time range are skipped
+        let future = folder.join("game_log_2999-01-01_00-00.jsonl");
+        std::fs::copy(logger.structured_log.as_ref().unwrap().0.clone(), &future).unwrap();
+        let entries = logger.query(None, Some(later), Vec::new(), 0, None).await.unwrap();
+        assert_eq!(3, entries.len());
+        assert_eq!(6, logger.query(None, None, Vec::new(), 0, None).await.unwrap().len());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
 }
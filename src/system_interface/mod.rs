@@ -20,6 +20,7 @@
 //! to the application window.
 
 // Define private submodules
+mod history;
 mod logging;
 #[macro_use]
 mod event_handler;
@@ -30,8 +31,9 @@ use crate::definitions::*;
 
 // Import other definitions
 use self::event_handler::EventHandler;
+use self::history::{is_changed, Change, History};
 use self::logging::Logger;
-use self::system_connection::SystemConnection;
+use self::system_connection::{BroadcastContext, SystemConnection};
 
 // Import standard library features
 use std::env;
@@ -39,14 +41,20 @@ use std::fs::DirBuilder;
 use std::path::PathBuf;
 use std::ffi::OsStr;
 
+// Import standard library features
+use std::time::Duration;
+
 // Import Tokio features
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Interval};
 
 // Import the failure features
 use failure::Error as FailureError;
 
 // Define module constants
 const POLLING_RATE: u64 = 1; // the polling rate for the system in ms
+const TIMER_RATE: u64 = 250; // the update rate for the timers in ms
 const DEFAULT_FILE: &str = "default"; // the default configuration filename
 const LOG_FOLDER: &str = "log/"; // the default log folder
 const ERROR_LOG: &str = "debug_log.txt"; // the default logging filename
@@ -61,6 +69,7 @@ const ERROR_LOG: &str = "debug_log.txt"; // the default logging filename
 ///
 pub struct SystemInterface {
     event_handler: Option<EventHandler>, // the event handler instance for the program, if it exists
+    history: History,                    // the undo and redo history of the operator changes
     logger: Logger,                      // the logging instance for the program
     system_connection: SystemConnection, // the system connection instance for the program
     index_access: IndexAccess,           // the access point for the item index
@@ -69,6 +78,7 @@ pub struct SystemInterface {
     web_receive: mpsc::Receiver<WebRequest>, // the receiving line for web requests
     internal_receive: mpsc::Receiver<InternalUpdate>, // a receiving line to receive internal updates
     internal_send: InternalSend,                      // a sending line to pass internal updates
+    timer_interval: Interval,                         // the interval to update the timers
     is_debug_mode: bool,                              // a flag to indicate debug mode
 }
 
@@ -121,6 +131,7 @@ impl SystemInterface {
         // Create the new system interface instance
         let mut sys_interface = SystemInterface {
             event_handler: None,
+            history: History::new(),
             logger,
             system_connection,
             index_access,
@@ -129,6 +140,7 @@ impl SystemInterface {
             web_receive,
             internal_receive,
             internal_send,
+            timer_interval: interval(Duration::from_millis(TIMER_RATE)),
             is_debug_mode: false,
         };
 
@@ -190,6 +202,14 @@ impl SystemInterface {
                         request.reply_to.send(WebReply::Items { is_valid: true, items }).unwrap_or(());
                     }
 
+                    // The unpacking yielded log entries (reply once they are read)
+                    UnpackResult::SuccessWithLog(query) => {
+                        tokio::spawn(async move {
+                            let entries = query.await.unwrap_or_default();
+                            request.reply_to.send(WebReply::Log { is_valid: true, entries }).unwrap_or(());
+                        });
+                    }
+
                     // The unpacking yielded a message
                     UnpackResult::SuccessWithMessage(message) => {
                         request.reply_to.send(WebReply::Generic { is_valid: true, message }).unwrap_or(());
@@ -226,6 +246,12 @@ impl SystemInterface {
                     }
                 }
             }
+
+            // Updates to the timers
+            _ = self.timer_interval.tick() => {
+                self.update_timers().await;
+                self.update_failover().await;
+            }
         }
 
         // In most cases, indicate to continue normally
@@ -251,6 +277,88 @@ impl SystemInterface {
         drop(self);
     }
 
+    /// A method to update the timers in the current configuration. The
+    /// remaining time of each changed timer is sent to the user interface
+    /// and broadcast (in whole seconds) as data with the timer id.
+    ///
+    async fn update_timers(&mut self) {
+        // Update the timers, if the event handler exists
+        if let Some(ref mut handler) = self.event_handler {
+            for (status_id, remaining, is_running) in handler.update_timers().await {
+                // Broadcast the remaining seconds to the system
+                self.internal_send
+                    .send_broadcast(status_id, Some(whole_seconds(remaining)))
+                    .await;
+
+                // Send the remaining time to the interface
+                self.interface_send
+                    .send(InterfaceUpdate::UpdateTimer {
+                        status_id: self.index_access.get_pair(&status_id).await,
+                        remaining,
+                        is_running,
+                    }).await;
+            }
+        }
+    }
+
+    /// A method to update the failover heartbeat. If this instance takes over
+    /// from the primary instance, the system connections are opened and the
+    /// previous state of the outputs is restored.
+    ///
+    async fn update_failover(&mut self) {
+        // Update the heartbeat, if the event handler exists
+        if let Some(ref mut handler) = self.event_handler {
+            if let Some(output_state) = handler.update_failover().await {
+                // Open the connections to the underlying system
+                if !self
+                    .system_connection
+                    .update_system_connection(Some(handler.system_connection()))
+                    .await
+                {
+                    log!(err &mut self.internal_send => "Unable To Open System Connections.");
+                }
+
+                // Restore the previous outputs and forget the previous changes
+                self.system_connection.restore_output(output_state).await;
+                self.history.clear();
+            }
+        }
+    }
+
+    /// A method to compose the context of a broadcast event, including the
+    /// current scene and the state of each status.
+    ///
+    async fn broadcast_context(&mut self, event_id: ItemId) -> BroadcastContext {
+        // Get the description of the event
+        let description = self.index_access.get_description(&event_id).await.description;
+
+        // Get the current scene and statuses, if the event handler exists
+        let mut scene = None;
+        let mut statuses = Vec::new();
+        if let Some(ref mut handler) = self.event_handler {
+            // Find the current scene
+            let scene_id = handler.get_current_scene();
+            scene = Some(self.index_access.get_pair(&scene_id).await);
+
+            // Find the current state of each status
+            for (status_id, status) in handler.get_statuses() {
+                statuses.push((
+                    self.index_access.get_pair(&status_id).await,
+                    self.index_access.get_pair(&status.current).await,
+                ));
+            }
+            statuses.sort_by_key(|(status, _)| status.id());
+        }
+
+        // Return the completed context
+        BroadcastContext {
+            event_id: Some(event_id),
+            description,
+            scene,
+            statuses,
+        }
+    }
+
     /// A method to unpack internal updates from the main program thread.
     ///
     async fn unpack_internal_update(&mut self, update: InternalUpdate) {
@@ -258,15 +366,33 @@ impl SystemInterface {
         match update {
             // Broadcast the event via the system connection
             InternalUpdate::BroadcastEvent(event_id, data) => {
-                self.system_connection.broadcast(event_id, data).await;
+                // Compose the context of the event, if needed
+                let context = if self.system_connection.needs_context() {
+                    Some(self.broadcast_context(event_id).await)
+                } else {
+                    None
+                };
+
+                // Broadcast the event
+                self.system_connection
+                    .broadcast(event_id, data, context)
+                    .await;
+            }
+
+            // Backup the new state of the outputs
+            InternalUpdate::OutputState(output_state) => {
+                // If the event handler exists
+                if let Some(ref mut handler) = self.event_handler {
+                    handler.backup_output(output_state).await;
+                }
             }
 
             // Update the timeline with the new list of coming events
-            InternalUpdate::ComingEvents(mut events) => {
+            InternalUpdate::ComingEvents(mut events, is_paused) => {
                 // If the event handler exists
                 if let Some(ref mut handler) = self.event_handler {
                     // Backup the coming events
-                    handler.backup_events(events.clone()).await;
+                    handler.backup_events(events.clone(), is_paused).await;
                 }
 
                 // Repackage the list as upcoming events
@@ -276,6 +402,10 @@ impl SystemInterface {
                     upcoming_events.push(UpcomingEvent {
                         start_time: event.start_time,
                         delay: event.delay,
+                        paused: event.paused,
+                        is_held: event.is_held,
+                        repeat: event.repeat,
+                        schedule: event.schedule,
                         event: self.index_access.get_pair(&event.id()).await,
                     });
                 }
@@ -284,6 +414,7 @@ impl SystemInterface {
                 self.interface_send
                     .send(InterfaceUpdate::UpdateTimeline {
                         events: upcoming_events,
+                        is_paused,
                     }).await;
             }
 
@@ -343,6 +474,13 @@ impl SystemInterface {
 
             // Pass the information update to the logger
             InternalUpdate::Update(log_update) => {
+                // Note the current scene for the structured game log
+                self.logger.set_scene(
+                    self.event_handler
+                        .as_ref()
+                        .map(|handler| handler.get_current_scene()),
+                );
+
                 // Find the most recent notifications
                 let notifications = self.logger.update(log_update).await;
 
@@ -437,6 +575,17 @@ impl SystemInterface {
                 }
             }
 
+            // Change the speed of the program clock
+            UserRequest::ClockSpeed { speed } => {
+                // Try to change the speed
+                if let Err(error) = Clock::set_speed(speed) {
+                    return UnpackResult::Failure(error);
+                }
+
+                // Notify the user of the change
+                log!(update &self.internal_send => "Clock Speed Changed To {}x.", speed);
+            }
+
             // Close the system interface thread.
             UserRequest::Close => return UnpackResult::Close, // FIXME doesn't work when selected from the web interface
 
@@ -445,6 +594,7 @@ impl SystemInterface {
                 // Try to clear all the events in the queue
                 if let Some(mut handler) = self.event_handler.take() {
                     handler.clear_events().await;
+                    handler.end_game().await;
                 } // old handler is dropped
 
                 // Check to see if a new filepath was specified
@@ -660,12 +810,12 @@ impl SystemInterface {
             UserRequest::EventChange {
                 event_id,
                 start_time,
-                new_delay,
+                change,
             } => {
                 // If the event handler exists
                 if let Some(mut handler) = self.event_handler.take() {
                     // Adjust the current time of the event
-                    handler.adjust_event(event_id, start_time, new_delay).await;
+                    handler.adjust_event(event_id, start_time, change).await;
 
                     // Put the handler back
                     self.event_handler = Some(handler);
@@ -675,9 +825,45 @@ impl SystemInterface {
             // Update the system log provided to the underlying system
             UserRequest::ErrorLog { filepath } => self.logger.set_error_log(filepath),
 
+            // Enable or disable the scheduled events
+            UserRequest::EnableSchedule { is_enabled } => {
+                // Try to change the schedule
+                if let Some(ref mut handler) = self.event_handler {
+                    handler.enable_schedule(is_enabled).await;
+
+                // Otherwise, return a failure
+                } else {
+                    return UnpackResult::Failure("No active configuration.".into());
+                }
+            }
+
+            // End the current game and clear the backup
+            UserRequest::EndGame => {
+                // Try to end the game
+                if let Some(ref mut handler) = self.event_handler {
+                    handler.end_game().await;
+
+                // Otherwise, return a failure
+                } else {
+                    return UnpackResult::Failure("No active configuration.".into());
+                }
+            }
+
             // Update the game log provided to the underlying system
             UserRequest::GameLog { filepath } => self.logger.set_game_log(filepath),
 
+            // Pause the events currently in the queue
+            UserRequest::PauseQueue => {
+                // Try to pause all the events in the queue
+                if let Some(ref mut handler) = self.event_handler {
+                    handler.pause_events().await;
+
+                // Otherwise, return a failure
+                } else {
+                    return UnpackResult::Failure("No active configuration.".into());
+                }
+            }
+
             // Pass an event to the event_handler
             UserRequest::ProcessEvent {
                 event,
@@ -706,6 +892,19 @@ impl SystemInterface {
                 }
             }
 
+            // Query the history in the structured game log
+            UserRequest::QueryLog {
+                start,
+                end,
+                log_types,
+                offset,
+                limit,
+            } => {
+                return UnpackResult::SuccessWithLog(
+                    self.logger.query(start, end, log_types, offset, limit),
+                );
+            }
+
             // Redraw the current window
             UserRequest::Redraw => {
                 // Try to redraw the current window
@@ -725,6 +924,49 @@ impl SystemInterface {
                             key_map,
                         }).await;
                 }
+
+                // Repeat the prompt to restore a saved game, if there is one
+                self.prompt_restore().await;
+            }
+
+            // Restore or discard the saved game
+            UserRequest::RestoreGame { restore } => {
+                // If the event handler exists and there is a saved game
+                match self.event_handler {
+                    Some(ref mut handler) if handler.saved_game().is_some() => {
+                        // Restore the game and the previous outputs
+                        if let Some(output_state) = handler.restore_game(restore).await {
+                            self.system_connection.restore_output(output_state).await;
+                        }
+
+                        // Forget the previous changes
+                        self.history.clear();
+                    }
+
+                    // Otherwise, return a failure
+                    Some(_) => return UnpackResult::Failure("No saved game to restore.".into()),
+                    None => return UnpackResult::Failure("No active configuration.".into()),
+                }
+            }
+
+            // Resume the events currently in the queue
+            UserRequest::ResumeQueue => {
+                // Try to resume all the events in the queue
+                if let Some(ref mut handler) = self.event_handler {
+                    handler.resume_events().await;
+
+                // Otherwise, return a failure
+                } else {
+                    return UnpackResult::Failure("No active configuration.".into());
+                }
+            }
+
+            // Redo the most recently undone change
+            UserRequest::Redo { fire_events } => {
+                match self.history.redo() {
+                    Some(change) => self.apply_change(change, fire_events, "Redo").await,
+                    None => log!(warn &self.internal_send => "Nothing To Redo."),
+                }
             }
 
             // Save the current configuration to the provided file
@@ -744,8 +986,18 @@ impl SystemInterface {
                 // Change the current scene, if event handler exists
                 if let Some(mut handler) = self.event_handler.take() {
                     // Change the current scene (automatically triggers a redraw)
+                    let previous = handler.get_current_scene();
                     handler.choose_scene(scene).await;
 
+                    // Record the change in the history
+                    let new_scene = handler.get_current_scene();
+                    if new_scene != previous {
+                        self.history.record(Change::Scene {
+                            previous,
+                            new_scene,
+                        });
+                    }
+
                     // Put the handler back
                     self.event_handler = Some(handler);
                 }
@@ -756,12 +1008,36 @@ impl SystemInterface {
                 // Change the status, if event handler exists
                 if let Some(mut handler) = self.event_handler.take() {
                     // Change the state of the indicated status
+                    let possible_previous = handler.save_status(&status);
                     handler.modify_status(&status, &state).await;
 
+                    // Record the change in the history (including changes to the count or time)
+                    let possible_new = handler.save_status(&status);
+                    if let (Some(previous), Some(new_status)) = (possible_previous, possible_new) {
+                        if is_changed(&previous, &new_status) {
+                            self.history.record(Change::Status {
+                                status_id: status,
+                                previous,
+                                new_status,
+                            });
+                        }
+                    }
+
                     // Put the handler back
                     self.event_handler = Some(handler);
                 }
             }
+
+            // Undo the most recent change
+            UserRequest::Undo { fire_events } => {
+                match self.history.undo() {
+                    Some(change) => {
+                        self.apply_change(change.reversed(), fire_events, "Undo")
+                            .await
+                    }
+                    None => log!(warn &self.internal_send => "Nothing To Undo."),
+                }
+            }
         }
         UnpackResult::Success // indicate to continue and no errors
     }
@@ -794,11 +1070,15 @@ impl SystemInterface {
             Err(_) => return, // errors will be logged separately if log_failure is true
         };
 
-        // Create a new connection to the underlying system
-        let system_connection = event_handler.system_connection();
+        // Create a new connection to the underlying system (unless in standby)
+        let system_connection = if event_handler.is_standby() {
+            None
+        } else {
+            Some(event_handler.system_connection())
+        };
         if !self
             .system_connection
-            .update_system_connection(Some(system_connection))
+            .update_system_connection(system_connection)
             .await
         {
             log!(err &mut self.internal_send => "Unable To Open System Connections.");
@@ -847,8 +1127,76 @@ impl SystemInterface {
         // Trigger a redraw of the system
         self.internal_send.send_refresh().await;
 
-        // Update the event handler
+        // Update the event handler (and forget the previous changes)
         self.event_handler = Some(event_handler);
+        self.history.clear();
+
+        // Ask the operator whether to restore a saved game, if there is one
+        self.prompt_restore().await;
+    }
+
+    /// An internal method to ask the operator whether to restore the saved
+    /// game, if there is one.
+    ///
+    async fn prompt_restore(&mut self) {
+        // Check for a saved game
+        let saved_game = self
+            .event_handler
+            .as_ref()
+            .and_then(|handler| handler.saved_game());
+
+        // Send the prompt to the user interface
+        if let Some((saved, scene_id)) = saved_game {
+            self.interface_send
+                .send(InterfaceUpdate::RestorePrompt {
+                    saved,
+                    scene: self.index_access.get_pair(&scene_id).await,
+                }).await;
+        }
+    }
+
+    /// An internal method to apply a change from the undo or redo history,
+    /// restoring the status or scene to the new state of the change. The
+    /// change is noted in the game log.
+    ///
+    async fn apply_change(&mut self, change: Change, fire_events: bool, action: &str) {
+        // Restore the change, if the event handler exists
+        if let Some(mut handler) = self.event_handler.take() {
+            // Compose a description of the change
+            let description = match change {
+                // Restore the state of a status
+                Change::Status {
+                    status_id,
+                    new_status,
+                    ..
+                } => {
+                    handler.restore_status(&status_id, &new_status, fire_events).await;
+                    format!(
+                        "{}: {} Restored To {}",
+                        action,
+                        self.index_access.get_description(&status_id).await,
+                        self.index_access.get_description(&new_status.current).await
+                    )
+                }
+
+                // Restore the current scene
+                Change::Scene { new_scene, .. } => {
+                    handler.restore_scene(new_scene, fire_events).await;
+                    format!(
+                        "{}: Scene Restored To {}",
+                        action,
+                        self.index_access.get_description(&new_scene).await
+                    )
+                }
+            };
+
+            // Note the change in the game log
+            log!(update &self.internal_send => "{}.", description);
+            log!(save &self.internal_send => description);
+
+            // Put the handler back
+            self.event_handler = Some(handler);
+        }
     }
 }
 
@@ -866,6 +1214,9 @@ enum UnpackResult {
     // A variant for successful unpacking with items
     SuccessWithItems(Vec<ItemId>),
 
+    // A variant for successful unpacking with a query of the log entries
+    SuccessWithLog(JoinHandle<Vec<LogEntry>>),
+
     // A variant for successful unpacking with message
     SuccessWithMessage(String),
 
@@ -0,0 +1,318 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to send DMX frames over the network using the Art-Net (ArtDmx)
+//! and sACN (E1.31) protocols.
+
+// Import standard library features
+use std::hash::Hasher;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+// Import FNV HashMap
+use fnv::{FnvHashMap, FnvHasher};
+
+// Import the failure features
+use failure::Error;
+
+// Define the Art-Net constants
+const ARTNET_PORT: u16 = 6454; // the default Art-Net port
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0"; // the Art-Net packet identifier
+const ARTNET_OPDMX: u16 = 0x5000; // the ArtDmx operation code
+const ARTNET_VERSION: u16 = 14; // the Art-Net protocol version
+
+// Define the sACN constants
+const SACN_PORT: u16 = 5568; // the default sACN port
+const SACN_ID: &[u8; 12] = b"ASC-E1.17\0\0\0"; // the ACN packet identifier
+const SACN_PRIORITY: u8 = 100; // the default sACN priority
+const SACN_NAME_LENGTH: usize = 64; // the length of the source name field
+const SACN_MAX_UNIVERSE: u16 = 63999; // the highest valid sACN universe
+
+/// A structure to send DMX frames as Art-Net ArtDmx packets
+///
+pub struct ArtNetOut {
+    socket: UdpSocket,              // the UDP socket for sending
+    target: SocketAddr,             // the address of the Art-Net node (or broadcast address)
+    sequences: FnvHashMap<u16, u8>, // the current sequence number of each universe
+}
+
+// Implement key functionality for Art-Net output
+impl ArtNetOut {
+    /// A function to create a new Art-Net output. The default Art-Net port
+    /// is used if the target does not specify a port.
+    ///
+    pub fn new(target: &str) -> Result<ArtNetOut, Error> {
+        // Resolve the target address
+        let target = resolve_address(target, ARTNET_PORT)?;
+
+        // Create the socket and allow broadcast messages
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+
+        // Return the new output
+        Ok(ArtNetOut {
+            socket,
+            target,
+            sequences: FnvHashMap::default(),
+        })
+    }
+
+    /// A method to send a frame of DMX data to the provided universe (the
+    /// fifteen bit Art-Net port address)
+    ///
+    pub fn write_frame(&mut self, universe: u16, frame: &[u8]) -> Result<(), Error> {
+        // Increment the sequence of this universe (zero disables sequencing)
+        let sequence = self.sequences.entry(universe).or_insert(0);
+        *sequence = sequence.wrapping_add(1).max(1);
+
+        // Send the packet to the node
+        let packet = artnet_packet(*sequence, universe, frame);
+        self.socket.send_to(&packet, self.target)?;
+        Ok(())
+    }
+}
+
+/// A structure to send DMX frames as sACN (E1.31) data packets
+///
+pub struct SacnOut {
+    socket: UdpSocket,              // the UDP socket for sending
+    target: Option<SocketAddr>,     // the unicast address of the receiver (multicast if None)
+    source_name: String,            // the source name reported to the receivers
+    cid: [u8; 16],                  // the component identifier of this source
+    sequences: FnvHashMap<u16, u8>, // the current sequence number of each universe
+}
+
+// Implement key functionality for sACN output
+impl SacnOut {
+    /// A function to create a new sACN output. If no target is provided, the
+    /// frames are sent to the multicast address of each universe.
+    ///
+    pub fn new(target: &Option<String>, source_name: &str) -> Result<SacnOut, Error> {
+        // Resolve the target address, if specified
+        let target = match target {
+            Some(address) => Some(resolve_address(address, SACN_PORT)?),
+            None => None,
+        };
+
+        // Create the socket
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+
+        // Derive a consistent component identifier from the source name
+        let mut cid = [0; 16];
+        for (index, chunk) in cid.chunks_mut(8).enumerate() {
+            let mut hasher = FnvHasher::default();
+            hasher.write_usize(index);
+            hasher.write(source_name.as_bytes());
+            chunk.copy_from_slice(&hasher.finish().to_be_bytes());
+        }
+
+        // Return the new output
+        Ok(SacnOut {
+            socket,
+            target,
+            source_name: source_name.to_string(),
+            cid,
+            sequences: FnvHashMap::default(),
+        })
+    }
+
+    /// A method to send a frame of DMX data to the provided universe (from
+    /// 1 to 63999)
+    ///
+    pub fn write_frame(&mut self, universe: u16, frame: &[u8]) -> Result<(), Error> {
+        // Verify that the universe is valid
+        if !is_sacn_universe(universe) {
+            return Err(format_err!("Invalid sACN universe: {}", universe));
+        }
+
+        // Increment the sequence of this universe
+        let sequence = self.sequences.entry(universe).or_insert(0);
+        *sequence = sequence.wrapping_add(1);
+
+        // Select the unicast address or the multicast address of the universe
+        let target = match self.target {
+            Some(target) => target,
+            None => SocketAddr::from((
+                Ipv4Addr::new(239, 255, (universe >> 8) as u8, universe as u8),
+                SACN_PORT,
+            )),
+        };
+
+        // Send the packet to the receiver(s)
+        let packet = sacn_packet(&self.cid, &self.source_name, *sequence, universe, frame);
+        self.socket.send_to(&packet, target)?;
+        Ok(())
+    }
+}
+
+/// A function to check whether a universe number is valid for sACN (universe
+/// zero and the values above 63999 are reserved)
+///
+pub fn is_sacn_universe(universe: u16) -> bool {
+    (1..=SACN_MAX_UNIVERSE).contains(&universe)
+}
+
+// A helper function to resolve an address, adding the default port if missing
+fn resolve_address(address: &str, default_port: u16) -> Result<SocketAddr, Error> {
+    // Try the address as provided, then with the default port
+    let mut addresses = match address.to_socket_addrs() {
+        Ok(addresses) => addresses,
+        Err(_) => (address, default_port).to_socket_addrs()?,
+    };
+
+    // Return the first result
+    addresses
+        .next()
+        .ok_or_else(|| format_err!("Unable to resolve address: {}", address))
+}
+
+// A helper function to compose an ArtDmx packet
+fn artnet_packet(sequence: u8, universe: u16, frame: &[u8]) -> Vec<u8> {
+    // Add the packet header
+    let mut packet = Vec::with_capacity(18 + frame.len());
+    packet.extend_from_slice(ARTNET_ID);
+    packet.extend_from_slice(&ARTNET_OPDMX.to_le_bytes());
+    packet.extend_from_slice(&ARTNET_VERSION.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0); // the physical port
+
+    // Add the port address (sub-net and universe, then net)
+    packet.push((universe & 0xFF) as u8);
+    packet.push(((universe >> 8) & 0x7F) as u8);
+
+    // Add the length and the frame data
+    packet.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+    packet.extend_from_slice(frame);
+    packet
+}
+
+// A helper function to compose an E1.31 data packet
+fn sacn_packet(cid: &[u8; 16], source_name: &str, sequence: u8, universe: u16, frame: &[u8]) -> Vec<u8> {
+    // Calculate the length of each layer
+    let dmp_length = 10 + 1 + frame.len();
+    let framing_length = 77 + dmp_length;
+    let root_length = 22 + framing_length;
+
+    // Add the root layer
+    let mut packet = Vec::with_capacity(16 + root_length);
+    packet.extend_from_slice(&0x0010_u16.to_be_bytes()); // the preamble size
+    packet.extend_from_slice(&0x0000_u16.to_be_bytes()); // the postamble size
+    packet.extend_from_slice(SACN_ID);
+    packet.extend_from_slice(&(0x7000 | root_length as u16).to_be_bytes());
+    packet.extend_from_slice(&0x0000_0004_u32.to_be_bytes()); // the root data vector
+    packet.extend_from_slice(cid);
+
+    // Add the framing layer
+    packet.extend_from_slice(&(0x7000 | framing_length as u16).to_be_bytes());
+    packet.extend_from_slice(&0x0000_0002_u32.to_be_bytes()); // the framing data vector
+    let mut name = [0; SACN_NAME_LENGTH];
+    for (byte, character) in name.iter_mut().zip(source_name.bytes().take(SACN_NAME_LENGTH - 1)) {
+        *byte = character;
+    }
+    packet.extend_from_slice(&name);
+    packet.push(SACN_PRIORITY);
+    packet.extend_from_slice(&0_u16.to_be_bytes()); // the synchronization address
+    packet.push(sequence);
+    packet.push(0); // the options
+    packet.extend_from_slice(&universe.to_be_bytes());
+
+    // Add the DMP layer
+    packet.extend_from_slice(&(0x7000 | dmp_length as u16).to_be_bytes());
+    packet.push(0x02); // the set property vector
+    packet.push(0xA1); // the address and data type
+    packet.extend_from_slice(&0_u16.to_be_bytes()); // the first property address
+    packet.extend_from_slice(&1_u16.to_be_bytes()); // the address increment
+    packet.extend_from_slice(&(1 + frame.len() as u16).to_be_bytes());
+    packet.push(0x00); // the DMX start code
+    packet.extend_from_slice(frame);
+    packet
+}
+
+// Tests of the network DMX module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Verify the layout of an ArtDmx packet
+    #[test]
+    fn artnet_layout() {
+        let frame = vec![7; 512];
+        let packet = artnet_packet(3, 0x0123, &frame);
+        assert_eq!(packet.len(), 530);
+        assert_eq!(&packet[0..8], ARTNET_ID);
+        assert_eq!(&packet[8..10], &[0x00, 0x50]);
+        assert_eq!(&packet[10..12], &[0x00, 14]);
+        assert_eq!(packet[12], 3);
+        assert_eq!(&packet[14..16], &[0x23, 0x01]);
+        assert_eq!(&packet[16..18], &[0x02, 0x00]);
+        assert_eq!(packet[18], 7);
+    }
+
+    // Verify the layout of an E1.31 data packet
+    #[test]
+    fn sacn_layout() {
+        let frame = vec![9; 512];
+        let packet = sacn_packet(&[1; 16], "minerva", 5, 2, &frame);
+        assert_eq!(packet.len(), 638);
+        assert_eq!(&packet[4..16], SACN_ID);
+        assert_eq!(&packet[16..18], &[0x72, 0x6E]);
+        assert_eq!(&packet[38..40], &[0x72, 0x58]);
+        assert_eq!(&packet[44..51], b"minerva");
+        assert_eq!(packet[108], SACN_PRIORITY);
+        assert_eq!(packet[111], 5);
+        assert_eq!(&packet[113..115], &[0x00, 0x02]);
+        assert_eq!(&packet[115..117], &[0x72, 0x0B]);
+        assert_eq!(&packet[123..125], &[0x02, 0x01]);
+        assert_eq!(packet[125], 0);
+        assert_eq!(packet[126], 9);
+    }
+
+    // Verify that each universe keeps its own sequence
+    #[test]
+    fn universe_sequences() {
+        // Listen for the packets locally
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(std::time::Duration::from_millis(500)))
+            .unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut buffer = [0; 1024];
+
+        // Send alternating universes over Art-Net
+        let mut art_net = ArtNetOut::new(&address).unwrap();
+        for (universe, sequence) in [(1, 1), (2, 1), (1, 2), (2, 2)].iter() {
+            art_net.write_frame(*universe, &[0; 512]).unwrap();
+            listener.recv(&mut buffer).unwrap();
+            assert_eq!(buffer[12], *sequence);
+            assert_eq!(buffer[14], *universe as u8);
+        }
+
+        // Send alternating universes over sACN
+        let mut sacn = SacnOut::new(&Some(address), "minerva").unwrap();
+        for (universe, sequence) in [(1, 1), (2, 1), (1, 2), (2, 2)].iter() {
+            sacn.write_frame(*universe, &[0; 512]).unwrap();
+            listener.recv(&mut buffer).unwrap();
+            assert_eq!(buffer[111], *sequence);
+            assert_eq!(buffer[114], *universe as u8);
+        }
+
+        // Reject the reserved sACN universes
+        assert!(sacn.write_frame(0, &[0; 512]).is_err());
+        assert!(sacn.write_frame(64000, &[0; 512]).is_err());
+        assert!(!is_sacn_universe(0));
+        assert!(is_sacn_universe(SACN_MAX_UNIVERSE));
+    }
+}
@@ -15,23 +15,37 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-//! A module to communicate using a DMX serial connection
+//! A module to communicate using a DMX serial or network connection
 //!
 //! # Note
 //!
-//! This module is currently limited to Enttec DMX USB Pro-compatible hardware.
+//! Serial output is currently limited to Enttec DMX USB Pro-compatible
+//! hardware. Network output supports Art-Net and sACN (E1.31).
+//!
+//! Each event may start a single fade or a look of many fades. A look may
+//! crossfade from the previous look, fading the channels which are not part
+//! of the new look to zero.
+//!
+//! Each fade may specify the universe number of the channel. When no
+//! universe is specified, channels continue across the universes of the
+//! connection in order (e.g. channel 513 is the first channel of the second
+//! universe).
 
 // Import crate definitions
 use crate::definitions::*;
 
 // Import other definitions
 use super::{EventConnection, ReadResult};
+use super::dmx_net::{is_sacn_universe, ArtNetOut, SacnOut};
 
 // Import standard library features
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::mpsc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+// Import Chrono features
+use chrono::NaiveDateTime;
 
 // Import Tokio features
 use tokio::task;
@@ -41,7 +55,7 @@ use serial;
 use serial::prelude::*;
 
 // Import FNV HashMap
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 
 // Import the failure features
 use failure::Error;
@@ -59,24 +73,35 @@ const COMMAND_END: u8 = 0xE7 as u8; // the end of the command
 
 // Define fade constants
 const RESOLUTION: u64 = 50; // the time resolution of each fade, in ms
+const REFRESH: u64 = 1000; // the time between repeated frames when idle, in ms
+const SERIAL_UNIVERSE: u16 = 1; // the universe number of the serial output
 
-/// A structure to hold and manipulate the connection over serial
+/// An enum to hold the different outputs for the DMX frames
 ///
-pub struct DmxOut {
-    all_stop_dmx: Vec<DmxFade>,       // a vector of dmx fades for all stop
-    dmx_map: DmxMap,                  // the map of event ids to fade instructions
-    load_fade: mpsc::Sender<DmxFade>, // a line to load the dmx fade into the queue
+pub enum DmxOutput {
+    /// A variant to write frames to a DMX serial port
+    Serial {
+        port: serial::SystemPort, // the serial port of the connection
+    },
+
+    /// A variant to send frames as Art-Net packets
+    ArtNet {
+        output: ArtNetOut,    // the Art-Net output
+        universes: Vec<u16>, // the universes to send, in order
+    },
+
+    /// A variant to send frames as sACN (E1.31) packets
+    Sacn {
+        output: SacnOut,     // the sACN output
+        universes: Vec<u16>, // the universes to send, in order
+    },
 }
 
-// Implement key functionality for the DMX structure
-impl DmxOut {
-    /// A function to create a new instance of the DmxOut
+// Implement key functionality for the DMX output
+impl DmxOutput {
+    /// A function to create a new serial DMX output
     ///
-    pub fn new(
-        path: &PathBuf,
-        all_stop_dmx: Vec<DmxFade>,
-        dmx_map: DmxMap,
-    ) -> Result<DmxOut, Error> {
+    pub fn serial(path: &PathBuf) -> Result<DmxOutput, Error> {
         // Connect to the underlying serial port
         let mut port = serial::open(path)?;
 
@@ -93,9 +118,126 @@ impl DmxOut {
         // Adjust the timeout for the serial port
         port.set_timeout(Duration::from_millis(100))?;
 
+        // Return the serial output
+        Ok(DmxOutput::Serial { port })
+    }
+
+    /// A function to create a new Art-Net DMX output
+    ///
+    pub fn art_net(target: &str, universes: Vec<u16>) -> Result<DmxOutput, Error> {
+        // Verify that there is at least one universe
+        if universes.is_empty() {
+            return Err(format_err!("No Art-Net universes specified."));
+        }
+
+        // Return the Art-Net output
+        Ok(DmxOutput::ArtNet {
+            output: ArtNetOut::new(target)?,
+            universes,
+        })
+    }
+
+    /// A function to create a new sACN DMX output
+    ///
+    pub fn sacn(
+        target: &Option<String>,
+        source_name: &str,
+        universes: Vec<u16>,
+    ) -> Result<DmxOutput, Error> {
+        // Verify that there is at least one universe
+        if universes.is_empty() {
+            return Err(format_err!("No sACN universes specified."));
+        }
+
+        // Verify that the universes are within the valid sACN range
+        if let Some(universe) = universes.iter().find(|universe| !is_sacn_universe(**universe)) {
+            return Err(format_err!("Invalid sACN universe: {}", universe));
+        }
+
+        // Return the sACN output
+        Ok(DmxOutput::Sacn {
+            output: SacnOut::new(target, source_name)?,
+            universes,
+        })
+    }
+
+    /// A method to return the universe numbers of this output, in order
+    ///
+    fn universes(&self) -> Vec<u16> {
+        match self {
+            DmxOutput::Serial { .. } => vec![SERIAL_UNIVERSE],
+            DmxOutput::ArtNet { universes, .. } => universes.clone(),
+            DmxOutput::Sacn { universes, .. } => universes.clone(),
+        }
+    }
+
+    /// A method to write the status of every universe to the output
+    ///
+    fn write_frame(&mut self, status: &[Vec<u8>]) {
+        match self {
+            // Write the frame to the serial port
+            DmxOutput::Serial { port } => {
+                // Add the message header
+                let mut bytes = Vec::new();
+                bytes.push(COMMAND_START);
+                bytes.push(MESSAGE_LABEL);
+                bytes.push(DATA_LSB);
+                bytes.push(DATA_MSB);
+                bytes.push(DMX_START_CODE);
+
+                // Add the current status to the message
+                bytes.extend_from_slice(&status[0]);
+
+                // Add the message ending
+                bytes.push(COMMAND_END);
+
+                // Send the bytes to the board
+                port.write(bytes.as_slice()).unwrap_or(0); // silently ignore errors
+            }
+
+            // Send each universe as an Art-Net packet
+            DmxOutput::ArtNet { output, universes } => {
+                for (universe, frame) in universes.iter().zip(status.iter()) {
+                    output.write_frame(*universe, frame).unwrap_or(()); // silently ignore errors
+                }
+            }
+
+            // Send each universe as an sACN packet
+            DmxOutput::Sacn { output, universes } => {
+                for (universe, frame) in universes.iter().zip(status.iter()) {
+                    output.write_frame(*universe, frame).unwrap_or(()); // silently ignore errors
+                }
+            }
+        }
+    }
+}
+
+/// A structure to hold and manipulate the connection over serial or network
+///
+pub struct DmxOut {
+    all_stop_dmx: Vec<DmxFade>,       // a vector of dmx fades for all stop
+    dmx_map: DmxMap,                  // the map of event ids to fade instructions
+    universes: Vec<u16>,              // the universe numbers of the output, in order
+    load_fade: mpsc::Sender<DmxCue>,  // a line to load the dmx cue into the queue
+    levels: FnvHashMap<(usize, u32), u8>, // the final level of each channel which has been set
+    current_look: FnvHashSet<(usize, u32)>, // the universes and channels of the most recent look
+}
+
+// Implement key functionality for the DMX structure
+impl DmxOut {
+    /// A function to create a new instance of the DmxOut
+    ///
+    pub fn new(
+        output: DmxOutput,
+        all_stop_dmx: Vec<DmxFade>,
+        dmx_map: DmxMap,
+    ) -> Result<DmxOut, Error> {
+        // Save the universes of the output
+        let universes = output.universes();
+
         // Create a new DMX queue
         let (load_fade, receive_fade) = mpsc::channel();
-        let mut dmx_queue = DmxQueue::new(port, receive_fade);
+        let mut dmx_queue = DmxQueue::new(output, receive_fade);
 
         // Start the dmx queue thread
         task::spawn_blocking(move || {
@@ -106,9 +248,85 @@ impl DmxOut {
         Ok(DmxOut {
             all_stop_dmx,
             dmx_map,
+            universes,
             load_fade,
+            levels: FnvHashMap::default(),
+            current_look: FnvHashSet::default(),
         })
     }
+
+    /// A method to return the final level of every channel which has been
+    /// set, sorted by universe and channel
+    ///
+    pub fn output_state(&self) -> Vec<DmxFade> {
+        // Sort the channels
+        let mut locations: Vec<&(usize, u32)> = self.levels.keys().collect();
+        locations.sort_unstable();
+
+        // Convert each level back to an instantaneous fade
+        locations
+            .into_iter()
+            .map(|location| DmxFade {
+                universe: Some(self.universes[location.0]),
+                channel: location.1 + 1,
+                value: self.levels[location],
+                duration: None,
+                curve: None,
+            })
+            .collect()
+    }
+
+    /// A method to restore the provided channel levels instantly, ignoring
+    /// any channels which are out of range
+    ///
+    pub fn restore_levels(&mut self, dmx_levels: &[DmxFade]) {
+        for dmx_fade in dmx_levels.iter() {
+            // Verify the universe and range of the selected channel
+            if let Some(location) = locate_channel(&self.universes, dmx_fade) {
+                // Send the level to the background thread, ignoring errors
+                let level = DmxFade {
+                    duration: None,
+                    curve: None,
+                    ..dmx_fade.clone()
+                };
+                self.load_fade.send(DmxCue::Fade(level)).unwrap_or(());
+                self.levels.insert(location, dmx_fade.value);
+            }
+        }
+    }
+
+    /// A helper method to note the final levels of the channels changed by
+    /// the cue (following the same rules as the dmx queue)
+    ///
+    fn track_cue(&mut self, dmx_cue: &DmxCue) {
+        match dmx_cue {
+            // Note the level of a single fade
+            DmxCue::Fade(dmx_fade) => {
+                if let Some(location) = locate_channel(&self.universes, dmx_fade) {
+                    self.levels.insert(location, dmx_fade.value);
+                }
+            }
+
+            // Note the levels of the look
+            DmxCue::Look(dmx_look) => {
+                let mut new_look = FnvHashSet::default();
+                for dmx_fade in dmx_look.fades.iter() {
+                    if let Some(location) = locate_channel(&self.universes, dmx_fade) {
+                        self.levels.insert(location, dmx_fade.value);
+                        new_look.insert(location);
+                    }
+                }
+
+                // If crossfading, the channels of the previous look end at zero
+                if dmx_look.crossfade.is_some() {
+                    for location in self.current_look.difference(&new_look) {
+                        self.levels.insert(*location, 0);
+                    }
+                }
+                self.current_look = new_look;
+            }
+        }
+    }
 }
 
 // Implement the event connection trait for DMXOut
@@ -125,25 +343,37 @@ impl EventConnection for DmxOut {
         // Check to see if the event is all stop
         if id == ItemId::all_stop() {
             // Run all of the all stop fades, ignoring errors
-            for dmx_fade in self.all_stop_dmx.iter() {
-                // Verify the range of the selected channel
-                if (dmx_fade.channel <= DMX_MAX) & (dmx_fade.channel > 0) {
+            for dmx_fade in self.all_stop_dmx.clone() {
+                // Verify the universe and range of the selected channel
+                if locate_channel(&self.universes, &dmx_fade).is_some() {
                     // Send the fade to the background thread
-                    self.load_fade.send(dmx_fade.clone()).unwrap_or(()) // ignore errors
+                    let dmx_cue = DmxCue::Fade(dmx_fade);
+                    self.load_fade.send(dmx_cue.clone()).unwrap_or(()); // ignore errors
+                    self.track_cue(&dmx_cue);
                 }
             }
 
         // Check to see if the event is in the DMX map
-        } else if let Some(dmx_fade) = self.dmx_map.get(&id) {
-            // Verify the range of the selected channel
-            if (dmx_fade.channel > DMX_MAX) | (dmx_fade.channel < 1) {
-                return Err(format_err!("Selected DMX channel is out of range."));
+        } else if let Some(dmx_cue) = self.dmx_map.get(&id).cloned() {
+            // Verify the universe and range of every selected channel
+            let all_valid = match &dmx_cue {
+                DmxCue::Fade(dmx_fade) => locate_channel(&self.universes, dmx_fade).is_some(),
+                DmxCue::Look(dmx_look) => dmx_look
+                    .fades
+                    .iter()
+                    .all(|dmx_fade| locate_channel(&self.universes, dmx_fade).is_some()),
+            };
+            if !all_valid {
+                return Err(format_err!("Selected DMX universe or channel is out of range."));
             }
 
-            // Send the fade to the background thread
-            if let Err(_) = self.load_fade.send(dmx_fade.clone()) {
+            // Send the cue to the background thread
+            if let Err(_) = self.load_fade.send(dmx_cue.clone()) {
                 return Err(format_err!("Background DMX fading control has crashed."));
             }
+
+            // Note the new levels of the channels
+            self.track_cue(&dmx_cue);
         };
 
         // If the event wasn't found or was processed correctly, indicate success
@@ -166,13 +396,47 @@ enum FadeStatus {
     Complete(u8),
 }
 
+/// A helper function to find the universe index and the zero-indexed channel
+/// of the fade. Returns None if the universe or channel is out of range.
+///
+fn locate_channel(universes: &[u16], dmx_fade: &DmxFade) -> Option<(usize, u32)> {
+    // Verify the lower range of the channel
+    if dmx_fade.channel < 1 {
+        return None;
+    }
+
+    // Check whether the universe is specified
+    let (index, channel) = match dmx_fade.universe {
+        // Find the specified universe
+        Some(universe) => (
+            universes
+                .iter()
+                .position(|number| *number == universe)?,
+            dmx_fade.channel - 1,
+        ),
+
+        // Otherwise, continue across the universes
+        None => (
+            ((dmx_fade.channel - 1) / DMX_MAX) as usize,
+            (dmx_fade.channel - 1) % DMX_MAX,
+        ),
+    };
+
+    // Verify the upper range of the universe and channel
+    if (index >= universes.len()) | (channel >= DMX_MAX) {
+        return None;
+    }
+    Some((index, channel))
+}
+
 /// A struct to allow easier manipulation of queued DMX changes.
 #[derive(Copy, Clone, PartialEq, Debug)]
 struct DmxChange {
-    start_time: Instant, // the original start time of the fade
-    difference: f64,     // the difference between the start value and end value
-    end_value: u8,       // the final value at the end of the fade
-    duration: Duration,  // the duration of the fade (None if instantaneous)
+    start_time: NaiveDateTime, // the original start time of the fade
+    start_value: u8,           // the value at the start of the fade
+    end_value: u8,             // the final value at the end of the fade
+    duration: Duration,        // the duration of the fade (None if instantaneous)
+    curve: FadeCurve,          // the shape of the fade
 }
 
 // Implement the DMX Change features
@@ -180,13 +444,14 @@ impl DmxChange {
     /// A function to return a new DmxChange by composing the elements of the
     /// fade
     ///
-    fn new(start_value: u8, end_value: u8, duration: Duration) -> DmxChange {
+    fn new(start_value: u8, end_value: u8, duration: Duration, curve: FadeCurve) -> DmxChange {
         // Compose and return the new dmx change
         DmxChange {
-            start_time: Instant::now(),
-            difference: start_value as f64 - end_value as f64,
+            start_time: Clock::now(),
+            start_value,
             end_value,
             duration,
+            curve,
         }
     }
 
@@ -196,23 +461,29 @@ impl DmxChange {
     ///
     fn current_fade(&self) -> FadeStatus {
         // Calculate the ratio of elapsed time to total fade time
-        let fade_factor = 1.0
-            - (self.start_time.elapsed().as_millis() as f64)
-                / (self.duration.as_millis() as f64 + 0.1); // cheap fix to avoid dividing by zero
+        let progress = (Clock::elapsed(self.start_time).as_millis() as f64)
+            / (self.duration.as_millis() as f64 + 0.1); // cheap fix to avoid dividing by zero
 
-        // If the fade factor is still less than one
-        if fade_factor > 0.0 {
+        // If the progress is still less than one
+        if progress < 1.0 {
             // Return the correct fade amount with an ongoing fade
-            return FadeStatus::Ongoing(
-                ((self.end_value as f64) + (self.difference * fade_factor)) as u8,
-            );
+            return FadeStatus::Ongoing(self.value_at(progress));
 
-        // If the fade factor is over one (the fade is complete)
+        // If the progress is over one (the fade is complete)
         } else {
             // Return the final value and a complete fade
             return FadeStatus::Complete(self.end_value);
         }
     }
+
+    /// A method to calculate the value of the fade at the provided progress
+    /// (from zero to one), following the curve of the fade.
+    ///
+    fn value_at(&self, progress: f64) -> u8 {
+        // Calculate the portion of the change from the curve
+        let difference = self.end_value as f64 - self.start_value as f64;
+        (self.start_value as f64 + difference * self.curve.apply(progress)).round() as u8
+    }
 }
 
 /// A struct to hold a queue of future dmx changes. This struct launches a
@@ -221,10 +492,12 @@ impl DmxChange {
 /// changes.
 ///
 pub struct DmxQueue {
-    port: serial::SystemPort,                // the serial port of the connection
-    status: Vec<u8>,                         // the current status of all the channels
-    queue_receive: mpsc::Receiver<DmxFade>, // the queue receiving line that sends additional fade items to the daemon
-    dmx_changes: FnvHashMap<u32, DmxChange>, // the dmx queue holding the coming changes, sorted by channel
+    output: DmxOutput,                       // the output for the dmx frames
+    universes: Vec<u16>,                     // the universe numbers of the output, in order
+    status: Vec<Vec<u8>>,                    // the current status of all the channels, for each universe
+    queue_receive: mpsc::Receiver<DmxCue>, // the queue receiving line that sends additional cues to the daemon
+    dmx_changes: FnvHashMap<(usize, u32), DmxChange>, // the dmx queue holding the coming changes, sorted by universe and channel
+    current_look: FnvHashSet<(usize, u32)>, // the universes and channels of the most recent look
 }
 
 // Implement the Dmx Queue methods
@@ -232,17 +505,20 @@ impl DmxQueue {
     /// A function to create a new dmx queue.
     ///
     /// This function returns a new dmx queue which will send segments of a fade
-    /// (at time resolution RESOLUTION) to the specified port. This
+    /// (at time resolution RESOLUTION) to the specified output. This
     /// implementation of the queue launches a background thread to manage
     /// updates.
     ///
-    pub fn new(port: serial::SystemPort, queue_receive: mpsc::Receiver<DmxFade>) -> DmxQueue {
+    pub fn new(output: DmxOutput, queue_receive: mpsc::Receiver<DmxCue>) -> DmxQueue {
         // Return the newly constructed dmx queue
+        let universes = output.universes();
         DmxQueue {
-            port,
-            status: vec![0; DMX_MAX as usize],
+            output,
+            status: vec![vec![0; DMX_MAX as usize]; universes.len()],
+            universes,
             queue_receive,
             dmx_changes: FnvHashMap::default(),
+            current_look: FnvHashSet::default(),
         }
     }
 
@@ -257,18 +533,18 @@ impl DmxQueue {
                 // Update the current status for every fade
                 // TODO: This could perhaps be more efficient with retain()
                 let mut new_changes = FnvHashMap::default();
-                for (channel, change) in self.dmx_changes.iter() {
+                for (&(index, channel), change) in self.dmx_changes.iter() {
                     // Check to see if the fade is complete
                     match change.current_fade() {
                         // If ongoing, re-save the change
                         FadeStatus::Ongoing(value) => {
-                            self.status[*channel as usize] = value;
-                            new_changes.insert(channel.clone(), change.clone());
+                            self.status[index][channel as usize] = value;
+                            new_changes.insert((index, channel), change.clone());
                         }
 
                         // If complete, drop the change
                         FadeStatus::Complete(value) => {
-                            self.status[*channel as usize] = value;
+                            self.status[index][channel as usize] = value;
                         }
                     }
                 }
@@ -285,7 +561,7 @@ impl DmxQueue {
                     .recv_timeout(Duration::from_millis(RESOLUTION))
                 {
                     // Process a message if received
-                    Ok(new_fade) => self.process_fade(new_fade),
+                    Ok(new_cue) => self.process_cue(new_cue),
 
                     // Ignore timeout messages
                     Err(mpsc::RecvTimeoutError::Timeout) => (),
@@ -294,78 +570,133 @@ impl DmxQueue {
                     _ => break,
                 }
 
-            // Otherwise just wait for new message indefinitely
+            // Otherwise just wait for new message
             } else {
                 // Process a message if received
-                match self.queue_receive.recv() {
-                    // Add the new fade to the queue
-                    Ok(new_fade) => self.process_fade(new_fade),
+                match self
+                    .queue_receive
+                    .recv_timeout(Duration::from_millis(REFRESH))
+                {
+                    // Add the new cue to the queue
+                    Ok(new_cue) => self.process_cue(new_cue),
+
+                    // Repeat the current frame to keep network receivers active
+                    Err(mpsc::RecvTimeoutError::Timeout) => self.write_frame(),
 
-                    // Quit the thread on any error
+                    // Quit the thread on any other error
                     _ => break,
                 }
             }
         }
     }
 
-    /// A helper function to process new dmx fade messages
+    /// A helper function to process new dmx cue messages
     ///
-    fn process_fade(&mut self, dmx_fade: DmxFade) {
-        // Correct the channel range (convert to zero-indexed, rather than
-        // the one-indexed standard of dmx
-        let channel = dmx_fade.channel - 1;
+    fn process_cue(&mut self, dmx_cue: DmxCue) {
+        // Process the fade(s) and note any immediate changes
+        let mut is_changed = false;
+        match dmx_cue {
+            // Process a single fade
+            DmxCue::Fade(dmx_fade) => {
+                // Find the universe and the zero-indexed channel (rather than
+                // the one-indexed standard of dmx), ignoring invalid fades
+                if let Some(location) = locate_channel(&self.universes, &dmx_fade) {
+                    is_changed = self.process_fade(
+                        location,
+                        dmx_fade.value,
+                        dmx_fade.duration,
+                        dmx_fade.curve.unwrap_or(FadeCurve::Linear),
+                    );
+                }
+            }
+
+            // Process every fade in the look
+            DmxCue::Look(dmx_look) => {
+                // Process each fade, noting the channels of the look
+                let mut new_look = FnvHashSet::default();
+                for dmx_fade in dmx_look.fades.iter() {
+                    if let Some(location) = locate_channel(&self.universes, dmx_fade) {
+                        is_changed |= self.process_fade(
+                            location,
+                            dmx_fade.value,
+                            dmx_fade.duration,
+                            dmx_fade.curve.unwrap_or(FadeCurve::Linear),
+                        );
+                        new_look.insert(location);
+                    }
+                }
 
+                // If crossfading, fade out the channels of the previous look
+                if let Some(crossfade) = dmx_look.crossfade {
+                    let released: Vec<(usize, u32)> =
+                        self.current_look.difference(&new_look).cloned().collect();
+                    for location in released {
+                        is_changed |= self.process_fade(
+                            location,
+                            0,
+                            Some(crossfade),
+                            FadeCurve::Linear,
+                        );
+                    }
+                }
+
+                // Save the new look as the current look
+                self.current_look = new_look;
+            }
+        }
+
+        // Write any immediate changes
+        if is_changed {
+            self.write_frame();
+        }
+    }
+
+    /// A helper function to process a fade of a single channel. Returns true
+    /// if the change was made immediately.
+    ///
+    fn process_fade(
+        &mut self,
+        (index, channel): (usize, u32),
+        value: u8,
+        duration: Option<Duration>,
+        curve: FadeCurve,
+    ) -> bool {
         // Check whether there is a fade specified
-        match dmx_fade.duration {
+        match duration {
             // If a fade was specified
             Some(duration) => {
                 // Repack the fade as a dmx change
                 let change =
-                    DmxChange::new(self.status[channel as usize], dmx_fade.value, duration);
+                    DmxChange::new(self.status[index][channel as usize], value, duration, curve);
 
                 // Save the new fade, replace the existing fade if necessary
-                self.dmx_changes.insert(channel, change);
+                self.dmx_changes.insert((index, channel), change);
+                false
             }
 
             // Otherwise
             None => {
                 // Remove a fade on that channel, if it exists
-                self.dmx_changes.remove(&channel);
+                self.dmx_changes.remove(&(index, channel));
 
                 // Make the change immediately
-                self.status[channel as usize] = dmx_fade.value;
-                self.write_frame();
+                self.status[index][channel as usize] = value;
+                true
             }
         }
     }
 
-    /// A helper function to write the existing frame to the serial port
+    /// A helper function to write the existing frame to the output
     ///
     fn write_frame(&mut self) {
-        // Add the message header
-        let mut bytes = Vec::new();
-        bytes.push(COMMAND_START);
-        bytes.push(MESSAGE_LABEL);
-        bytes.push(DATA_LSB);
-        bytes.push(DATA_MSB);
-        bytes.push(DMX_START_CODE);
-
-        // Add the current status to the message
-        let mut status_copy = self.status.clone();
-        bytes.append(&mut status_copy);
-
-        // Add the message ending
-        bytes.push(COMMAND_END);
-
-        // Send the bytes to the board
-        self.port.write(bytes.as_slice()).unwrap_or(0); // silently ignore errors
+        self.output.write_frame(&self.status);
     }
 }
 
 // Tests of the DMXOut module
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
 
     // FIXME Define tests of this module
     #[test]
@@ -374,6 +705,146 @@ mod tests {
         unimplemented!();
     }
 
+    // Test the location of channels across universes
+    #[test]
+    fn locate_channels() {
+        // Create a fade helper
+        let fade = |universe, channel| DmxFade {
+            universe,
+            channel,
+            value: 255,
+            duration: None,
+            curve: None,
+        };
+
+        // Check channels with and without universes
+        let universes = vec![3, 4];
+        assert_eq!(locate_channel(&universes, &fade(None, 1)), Some((0, 0)));
+        assert_eq!(locate_channel(&universes, &fade(None, 513)), Some((1, 0)));
+        assert_eq!(locate_channel(&universes, &fade(None, 1025)), None);
+        assert_eq!(locate_channel(&universes, &fade(Some(4), 512)), Some((1, 511)));
+        assert_eq!(locate_channel(&universes, &fade(Some(4), 513)), None);
+        assert_eq!(locate_channel(&universes, &fade(Some(5), 1)), None);
+        assert_eq!(locate_channel(&universes, &fade(None, 0)), None);
+    }
+
+    // Test that single fades and looks are both read from the dmx map
+    #[test]
+    fn read_cues() {
+        // Read a map with a single fade and a look
+        let yaml = "? id: 1\n: channel: 5\n  value: 255\n  duration: ~\n? id: 2\n: fades:\n    - universe: 2\n      channel: 1\n      value: 10\n      duration: ~\n      curve: EaseIn\n  crossfade:\n    secs: 2\n    nanos: 0\n";
+        let dmx_map: DmxMap = serde_yaml::from_str(yaml).unwrap();
+
+        // Check the resulting cues
+        assert!(matches!(
+            dmx_map.get(&ItemId::new_unchecked(1)),
+            Some(DmxCue::Fade(DmxFade { channel: 5, universe: None, curve: None, .. }))
+        ));
+        if let Some(DmxCue::Look(dmx_look)) = dmx_map.get(&ItemId::new_unchecked(2)) {
+            assert_eq!(dmx_look.fades.len(), 1);
+            assert_eq!(dmx_look.fades[0].universe, Some(2));
+            assert_eq!(dmx_look.fades[0].curve, Some(FadeCurve::EaseIn));
+            assert_eq!(dmx_look.crossfade, Some(Duration::from_secs(2)));
+        } else {
+            panic!("The DMX look was not read correctly.");
+        }
+    }
+
+    // Test the values of fades with different curves
+    #[test]
+    fn fade_curves() {
+        // Create a change helper
+        let change = |curve| DmxChange::new(0, 200, Duration::from_secs(1), curve);
+
+        // Check the start, middle, and end of each fade
+        assert_eq!(change(FadeCurve::Linear).value_at(0.5), 100);
+        assert_eq!(change(FadeCurve::EaseIn).value_at(0.5), 50);
+        assert_eq!(change(FadeCurve::EaseOut).value_at(0.5), 150);
+        assert_eq!(change(FadeCurve::EaseInOut).value_at(0.5), 100);
+        assert!(change(FadeCurve::Exponential).value_at(0.5) < 10);
+        assert_eq!(change(FadeCurve::SnapAtEnd).value_at(0.99), 0);
+        for curve in [
+            FadeCurve::Linear,
+            FadeCurve::EaseIn,
+            FadeCurve::EaseOut,
+            FadeCurve::EaseInOut,
+            FadeCurve::Exponential,
+            FadeCurve::SnapAtEnd,
+        ] {
+            assert_eq!(change(curve).value_at(0.0), 0);
+            assert_eq!(change(curve).value_at(1.0), 200);
+        }
+
+        // Check a fade down
+        let fade_down = DmxChange::new(200, 0, Duration::from_secs(1), FadeCurve::Linear);
+        assert_eq!(fade_down.value_at(0.25), 150);
+    }
+
+    // Test that the final channel levels are tracked and restored
+    #[test]
+    fn track_levels() {
+        // Create a fade helper
+        let fade = |universe, channel, value| DmxFade {
+            universe,
+            channel,
+            value,
+            duration: Some(Duration::from_secs(1)),
+            curve: None,
+        };
+
+        // Create a dmx connection without an output
+        let (load_fade, receive_fade) = mpsc::channel();
+        let mut dmx_map = DmxMap::default();
+        dmx_map.insert(ItemId::new_unchecked(1), DmxCue::Fade(fade(None, 513, 200)));
+        dmx_map.insert(
+            ItemId::new_unchecked(2),
+            DmxCue::Look(DmxLook {
+                fades: vec![fade(Some(3), 1, 10), fade(Some(3), 2, 20)],
+                crossfade: None,
+            }),
+        );
+        dmx_map.insert(
+            ItemId::new_unchecked(3),
+            DmxCue::Look(DmxLook {
+                fades: vec![fade(Some(3), 2, 30)],
+                crossfade: Some(Duration::from_secs(2)),
+            }),
+        );
+        let mut dmx_out = DmxOut {
+            all_stop_dmx: vec![fade(Some(4), 1, 0)],
+            dmx_map,
+            universes: vec![3, 4],
+            load_fade,
+            levels: FnvHashMap::default(),
+            current_look: FnvHashSet::default(),
+        };
+
+        // Trigger the cues and check the final levels
+        for id in 1..4 {
+            dmx_out.write_event(ItemId::new_unchecked(id), 0, 0).unwrap();
+        }
+        let level = |universe, channel, value| DmxFade {
+            universe: Some(universe),
+            channel,
+            value,
+            duration: None,
+            curve: None,
+        };
+        let levels = vec![level(3, 1, 0), level(3, 2, 30), level(4, 1, 200)];
+        assert_eq!(levels, dmx_out.output_state());
+        dmx_out.write_event(ItemId::all_stop(), 0, 0).unwrap();
+        assert_eq!(level(4, 1, 0), dmx_out.output_state()[2]);
+
+        // Restore the levels to a new connection
+        let (load_fade, new_receive) = mpsc::channel();
+        dmx_out.load_fade = load_fade;
+        dmx_out.levels.clear();
+        dmx_out.restore_levels(&levels);
+        assert_eq!(levels, dmx_out.output_state());
+        assert_eq!(levels.len(), new_receive.try_iter().count());
+        drop(receive_fade);
+    }
+
     // FIXME Rewrite this test to use the new infrastructure
     /*
     // Import the library items for the testing function
@@ -0,0 +1,660 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to communicate using newline-delimited messages over TCP or UDP
+//!
+//! # Note
+//!
+//! Each message is a single line, either as text (`id data1 data2`) or as a
+//! JSON object (`{"id": 1, "data1": 0, "data2": 0}`). Missing data fields
+//! are treated as zero.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::{push_filter, EventConnection, ReadResult};
+
+// Import standard library modules and traits
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+// Import the failure features
+use failure::Error;
+
+// Define the communication constants
+const MAX_PACKET: usize = 65507; // the largest possible UDP payload
+const CONNECT_TIMEOUT: u64 = 1; // the time to wait for the server to respond, in seconds
+const RECONNECT_DELAY: u64 = 5; // the initial time between attempts to reconnect, in seconds
+const MAX_RECONNECT_DELAY: u64 = 60; // the maximum time between attempts to reconnect, in seconds
+const MAX_OUTPUT: usize = 1 << 20; // the largest unsent output before the stream is dropped
+const MAX_INPUT: usize = 1 << 16; // the longest incomplete line before the stream is dropped
+
+/// A helper struct to hold a TCP stream and the incomplete input and output
+///
+struct LineStream {
+    stream: TcpStream, // the TCP stream
+    buffer: Vec<u8>,   // the current input buffer
+    output: Vec<u8>,   // the output which has not been sent yet
+}
+
+// Implement key functionality for the line stream
+impl LineStream {
+    /// A function to prepare a new stream for polling
+    ///
+    fn new(stream: TcpStream) -> Result<LineStream, Error> {
+        // Allow the stream to be polled without blocking
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(LineStream {
+            stream,
+            buffer: Vec::new(),
+            output: Vec::new(),
+        })
+    }
+
+    /// A function to connect to a remote server, waiting at most the connect
+    /// timeout
+    ///
+    fn connect(address: &str) -> Result<LineStream, Error> {
+        let socket_address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format_err!("Invalid TCP address: {}", address))?;
+        let stream = TcpStream::connect_timeout(
+            &socket_address,
+            Duration::from_secs(CONNECT_TIMEOUT),
+        )?;
+        LineStream::new(stream)
+    }
+
+    /// A method to send bytes to the stream. Any bytes which cannot be sent
+    /// without blocking are kept and sent with the next write or flush.
+    /// Returns an error if the stream failed or too much output is waiting.
+    ///
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.output.extend_from_slice(bytes);
+        self.flush()
+    }
+
+    /// A method to send as much of the unsent output as possible without
+    /// blocking. Returns an error if the stream failed or too much output is
+    /// waiting.
+    ///
+    fn flush(&mut self) -> Result<(), Error> {
+        // Send the output until done or the stream would block
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(format_err!("Connection closed.")),
+                Ok(count) => {
+                    self.output.drain(..count);
+                }
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => (),
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        // Check that the waiting output is not too large
+        if self.output.len() > MAX_OUTPUT {
+            return Err(format_err!("Too much unsent output."));
+        }
+        Ok(())
+    }
+
+    /// A method to read all complete lines from the stream. Returns an error
+    /// if the stream was closed or an incomplete line is too long.
+    ///
+    fn read_lines(&mut self) -> Result<Vec<String>, Error> {
+        // Read any new bytes from the stream (up to the input limit)
+        let mut buffer = [0; 1024];
+        while self.buffer.len() <= MAX_INPUT {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(format_err!("Connection closed.")),
+                Ok(count) => self.buffer.extend_from_slice(&buffer[..count]),
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        // Remove every complete line from the buffer
+        let mut lines = Vec::new();
+        while let Some(position) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=position).collect();
+            lines.push(String::from_utf8_lossy(&line).to_string());
+        }
+
+        // Check that the incomplete line is not too long
+        if self.buffer.len() > MAX_INPUT {
+            return Err(format_err!("Line too long."));
+        }
+        Ok(lines)
+    }
+}
+
+/// An enum to hold the two modes of the TCP connection
+///
+enum TcpMode {
+    /// A variant which connects to a remote server
+    Client {
+        address: String,            // the address of the remote server
+        stream: Option<LineStream>, // the connection to the server, if connected
+        last_attempt: Instant,      // the time of the most recent connection attempt
+        reconnect_delay: Duration,  // the current time between attempts to reconnect
+    },
+
+    /// A variant which listens for remote clients
+    Server {
+        listener: TcpListener,    // the listener for new clients
+        clients: Vec<LineStream>, // the connected clients
+    },
+}
+
+/// A structure to hold and manipulate a line-based connection over TCP
+///
+pub struct TcpComm {
+    mode: TcpMode,                          // the client or server connection
+    format: LineFormat,                     // the format of each line
+    filter_events: Vec<(ItemId, u32, u32)>, // events to filter out
+}
+
+// Implement key functionality for the TCP structure
+impl TcpComm {
+    /// A function to create a new instance of the TcpComm, either connecting
+    /// to a remote server or listening for remote clients.
+    ///
+    pub fn new(address: &str, is_server: bool, format: LineFormat) -> Result<TcpComm, Error> {
+        // Create the selected mode
+        let mode = match is_server {
+            // Listen for new clients
+            true => {
+                let listener = TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+                TcpMode::Server {
+                    listener,
+                    clients: Vec::new(),
+                }
+            }
+
+            // Connect to the server
+            false => TcpMode::Client {
+                address: address.to_string(),
+                stream: Some(LineStream::connect(address)?),
+                last_attempt: Instant::now(),
+                reconnect_delay: Duration::from_secs(RECONNECT_DELAY),
+            },
+        };
+
+        // Return the new connection
+        Ok(TcpComm {
+            mode,
+            format,
+            filter_events: Vec::new(),
+        })
+    }
+}
+
+// Implement the event connection trait for TcpComm
+impl EventConnection for TcpComm {
+    /// A method to receive new events from the TCP connection
+    ///
+    fn read_events(&mut self) -> Vec<ReadResult> {
+        // Read the lines from the connection
+        let mut results = Vec::new();
+        match self.mode {
+            // Read from the server, reconnecting if necessary
+            TcpMode::Client {
+                ref address,
+                ref mut stream,
+                ref mut last_attempt,
+                ref mut reconnect_delay,
+            } => {
+                // Try to reconnect (waiting longer after each failed attempt)
+                if stream.is_none() && (last_attempt.elapsed() > *reconnect_delay) {
+                    *last_attempt = Instant::now();
+                    match LineStream::connect(address) {
+                        Ok(new_stream) => {
+                            *stream = Some(new_stream);
+                            *reconnect_delay = Duration::from_secs(RECONNECT_DELAY);
+                        }
+                        Err(error) => {
+                            results.push(ReadResult::ReadError(error));
+                            *reconnect_delay = (*reconnect_delay * 2)
+                                .min(Duration::from_secs(MAX_RECONNECT_DELAY));
+                        }
+                    }
+                }
+
+                // Send any waiting output and read from the stream
+                let mut is_closed = false;
+                if let Some(ref mut line_stream) = stream {
+                    match line_stream.flush().and_then(|_| line_stream.read_lines()) {
+                        Ok(lines) => {
+                            for line in lines {
+                                results.push(self.format.decode(&line));
+                            }
+                        }
+                        Err(error) => {
+                            results.push(ReadResult::ReadError(error));
+                            is_closed = true;
+                        }
+                    }
+                }
+
+                // Drop the stream if it was closed
+                if is_closed {
+                    *stream = None;
+                }
+            }
+
+            // Accept new clients and read from each client
+            TcpMode::Server {
+                ref listener,
+                ref mut clients,
+            } => {
+                // Accept any new clients
+                while let Ok((stream, _)) = listener.accept() {
+                    match LineStream::new(stream) {
+                        Ok(line_stream) => clients.push(line_stream),
+                        Err(error) => results.push(ReadResult::ReadError(error)),
+                    }
+                }
+
+                // Send any waiting output and read from each client, dropping closed connections
+                let mut relay = Vec::new();
+                clients.retain_mut(|client| match client.flush().and_then(|_| client.read_lines()) {
+                    Ok(lines) => {
+                        for line in lines {
+                            relay.push((client.stream.peer_addr().ok(), line));
+                        }
+                        true
+                    }
+                    Err(_) => false,
+                });
+
+                // Relay each line to the other clients (dropping failed clients) and decode the line
+                for (source, line) in relay {
+                    clients.retain_mut(|client| {
+                        client.stream.peer_addr().ok() == source
+                            || client.write(line.as_bytes()).is_ok()
+                    });
+                    results.push(self.format.decode(&line));
+                }
+            }
+        }
+
+        // Add the incoming events to the filter
+        for result in results.iter() {
+            if let ReadResult::Normal(id, data1, data2) = result {
+                push_filter(&mut self.filter_events, (*id, *data1, *data2));
+            }
+        }
+
+        // Return the resulting events
+        results
+    }
+
+    /// A method to send a new event to the TCP connection
+    ///
+    fn write_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Compose the line
+        let line = self.format.encode(id, data1, data2);
+
+        // Send the line to the server or every client
+        match self.mode {
+            TcpMode::Client { ref mut stream, .. } => {
+                // Send the line, dropping the stream on failure
+                let result = match stream {
+                    Some(ref mut line_stream) => line_stream.write(line.as_bytes()),
+                    None => return Err(format_err!("Not connected to the TCP server.")),
+                };
+                if let Err(error) = result {
+                    *stream = None;
+                    return Err(error);
+                }
+            }
+
+            // Send the line to every client, dropping failed clients
+            TcpMode::Server {
+                ref mut clients, ..
+            } => {
+                clients.retain_mut(|client| client.write(line.as_bytes()).is_ok());
+            }
+        }
+        Ok(())
+    }
+
+    /// A method to echo an event to the TCP connection
+    ///
+    fn echo_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Filter each event before echoing it to the system
+        match self
+            .filter_events
+            .iter()
+            .position(|filter| *filter == (id, data1, data2))
+        {
+            // Remove that event from the filter
+            Some(index) => {
+                self.filter_events.remove(index);
+                Ok(())
+            }
+
+            // Otherwise, echo the event to the system
+            None => self.write_event(id, data1, data2),
+        }
+    }
+}
+
+/// A structure to hold and manipulate a line-based connection over UDP
+///
+pub struct UdpComm {
+    socket: UdpSocket,                      // the UDP socket of the connection
+    send_address: String,                   // the remote address for outgoing messages
+    format: LineFormat,                     // the format of each line
+    filter_events: Vec<(ItemId, u32, u32)>, // events to filter out
+}
+
+// Implement key functionality for the UDP structure
+impl UdpComm {
+    /// A function to create a new instance of the UdpComm
+    ///
+    pub fn new(bind_address: &str, send_address: &str, format: LineFormat) -> Result<UdpComm, Error> {
+        // Bind to the local address and allow broadcast addresses
+        let socket = UdpSocket::bind(bind_address)?;
+        socket.set_broadcast(true)?;
+
+        // Allow the socket to be polled without blocking
+        socket.set_nonblocking(true)?;
+
+        // Return the new UdpComm instance
+        Ok(UdpComm {
+            socket,
+            send_address: send_address.to_string(),
+            format,
+            filter_events: Vec::new(),
+        })
+    }
+}
+
+// Implement the event connection trait for UdpComm
+impl EventConnection for UdpComm {
+    /// A method to receive new events from the UDP connection
+    ///
+    fn read_events(&mut self) -> Vec<ReadResult> {
+        // Read all the packets available on the socket
+        let mut results = Vec::new();
+        let mut buffer = vec![0; MAX_PACKET];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                // Decode each line in the packet
+                Ok((length, _)) => {
+                    for line in String::from_utf8_lossy(&buffer[..length]).lines() {
+                        if !line.trim().is_empty() {
+                            results.push(self.format.decode(line));
+                        }
+                    }
+                }
+
+                // Stop when there are no more packets
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => break,
+
+                // Otherwise, notify the system of the error
+                Err(error) => {
+                    results.push(ReadResult::ReadError(error.into()));
+                    break;
+                }
+            }
+        }
+
+        // Add the incoming events to the filter
+        for result in results.iter() {
+            if let ReadResult::Normal(id, data1, data2) = result {
+                push_filter(&mut self.filter_events, (*id, *data1, *data2));
+            }
+        }
+
+        // Return the resulting events
+        results
+    }
+
+    /// A method to send a new event to the UDP connection
+    ///
+    fn write_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        let line = self.format.encode(id, data1, data2);
+        self.socket.send_to(line.as_bytes(), self.send_address.as_str())?;
+        Ok(())
+    }
+
+    /// A method to echo an event to the UDP connection
+    ///
+    fn echo_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Filter each event before echoing it to the system
+        match self
+            .filter_events
+            .iter()
+            .position(|filter| *filter == (id, data1, data2))
+        {
+            // Remove that event from the filter
+            Some(index) => {
+                self.filter_events.remove(index);
+                Ok(())
+            }
+
+            // Otherwise, echo the event to the system
+            None => self.write_event(id, data1, data2),
+        }
+    }
+}
+
+/// A helper struct to read and write JSON lines
+///
+#[derive(Serialize, Deserialize)]
+struct JsonLine {
+    id: u32,
+    #[serde(default)]
+    data1: u32,
+    #[serde(default)]
+    data2: u32,
+}
+
+// Implement the line conversions
+impl LineFormat {
+    /// A method to decode a single line into an event
+    ///
+    fn decode(&self, line: &str) -> ReadResult {
+        // Read the fields of the line
+        let fields = match self {
+            // Read up to three numbers from the line
+            LineFormat::Text => {
+                let mut numbers = line.split_whitespace().map(|number| number.parse::<u32>());
+                match (
+                    numbers.next(),
+                    numbers.next().unwrap_or(Ok(0)),
+                    numbers.next().unwrap_or(Ok(0)),
+                ) {
+                    (Some(Ok(id)), Ok(data1), Ok(data2)) => Some((id, data1, data2)),
+                    _ => None,
+                }
+            }
+
+            // Read the fields of the JSON object
+            LineFormat::Json => match serde_json::from_str::<JsonLine>(line) {
+                Ok(json) => Some((json.id, json.data1, json.data2)),
+                Err(_) => None,
+            },
+        };
+
+        // Verify the event id
+        match fields.and_then(|(id, data1, data2)| ItemId::new(id).map(|id| (id, data1, data2))) {
+            Some((id, data1, data2)) => ReadResult::Normal(id, data1, data2),
+            None => ReadResult::ReadError(format_err!("Invalid line received: {}", line.trim())),
+        }
+    }
+
+    /// A method to encode an event as a single line, including the newline
+    ///
+    fn encode(&self, id: ItemId, data1: u32, data2: u32) -> String {
+        match self {
+            LineFormat::Text => format!("{} {} {}\n", id, data1, data2),
+            LineFormat::Json => {
+                let json = JsonLine {
+                    id: id.id(),
+                    data1,
+                    data2,
+                };
+                format!("{}\n", serde_json::to_string(&json).unwrap_or_default())
+            }
+        }
+    }
+}
+
+// Tests of the line module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Import std library features
+    use std::thread;
+
+    // A helper function to collect results for a short period
+    fn wait_for(connection: &mut impl EventConnection) -> Vec<(ItemId, u32, u32)> {
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            for result in connection.read_events() {
+                if let ReadResult::Normal(id, data1, data2) = result {
+                    events.push((id, data1, data2));
+                }
+            }
+            if !events.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        events
+    }
+
+    // Test decoding and encoding of lines
+    #[test]
+    fn line_formats() {
+        // Check the text format
+        let id = ItemId::new_unchecked(12);
+        assert_eq!(LineFormat::Text.encode(id, 1, 2), "12 1 2\n");
+        assert!(matches!(LineFormat::Text.decode("12 1 2\n"), ReadResult::Normal(read, 1, 2) if read == id));
+        assert!(matches!(LineFormat::Text.decode("12"), ReadResult::Normal(read, 0, 0) if read == id));
+        assert!(matches!(LineFormat::Text.decode("twelve"), ReadResult::ReadError(_)));
+
+        // Check the JSON format
+        assert_eq!(LineFormat::Json.encode(id, 1, 2), "{\"id\":12,\"data1\":1,\"data2\":2}\n");
+        assert!(matches!(LineFormat::Json.decode("{\"id\": 12, \"data2\": 5}"), ReadResult::Normal(read, 0, 5) if read == id));
+        assert!(matches!(LineFormat::Json.decode("{\"data1\": 5}"), ReadResult::ReadError(_)));
+    }
+
+    // Send events between a TCP server and client
+    #[test]
+    fn tcp_server_and_client() {
+        // Create the server and find its address
+        let mut server = TcpComm::new("127.0.0.1:0", true, LineFormat::Text).unwrap();
+        let address = match server.mode {
+            TcpMode::Server { ref listener, .. } => listener.local_addr().unwrap().to_string(),
+            _ => unreachable!(),
+        };
+
+        // Connect the client and wait for the server to accept it
+        let mut client = TcpComm::new(&address, false, LineFormat::Text).unwrap();
+        let id = ItemId::new_unchecked(30);
+        client.write_event(id, 1, 2).unwrap();
+        assert_eq!(wait_for(&mut server), vec![(id, 1, 2)]);
+
+        // Verify the received event is not echoed, then send an event back
+        server.echo_event(id, 1, 2).unwrap();
+        server.write_event(id, 3, 4).unwrap();
+        assert_eq!(wait_for(&mut client), vec![(id, 3, 4)]);
+    }
+
+    // Check that output to a slow client is kept rather than dropped
+    #[test]
+    fn tcp_buffered_output() {
+        // Create a server with a client which does not read
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut reader = TcpStream::connect(&address).unwrap();
+        let mut line_stream = LineStream::new(listener.accept().unwrap().0).unwrap();
+
+        // Write until the stream would block
+        let line = [b'1'; 1024];
+        let mut count = 0;
+        while line_stream.output.is_empty() {
+            line_stream.write(&line).unwrap();
+            count += line.len();
+        }
+
+        // Read everything and check that the remaining output is sent
+        let mut received = 0;
+        let mut buffer = [0; 4096];
+        reader.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        while received < count {
+            line_stream.flush().unwrap();
+            received += reader.read(&mut buffer).unwrap();
+        }
+        assert_eq!(received, count);
+        assert!(line_stream.output.is_empty());
+    }
+
+    // Check that a client which never ends a line is dropped
+    #[test]
+    fn tcp_long_line() {
+        // Connect a client to a line stream
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut writer = TcpStream::connect(&address).unwrap();
+        let mut line_stream = LineStream::new(listener.accept().unwrap().0).unwrap();
+
+        // Send a complete line and read it
+        writer.write_all(b"10 1 2\n").unwrap();
+        let mut lines = Vec::new();
+        while lines.is_empty() {
+            lines = line_stream.read_lines().unwrap();
+        }
+        assert_eq!(lines, vec!["10 1 2\n".to_string()]);
+
+        // Send more than the limit without a newline
+        writer.write_all(&vec![b'1'; MAX_INPUT + 1]).unwrap();
+        let mut result = Ok(Vec::new());
+        for _ in 0..1000 {
+            result = line_stream.read_lines();
+            if result.is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(result.is_err());
+    }
+
+    // Send events to and from a UDP connection
+    #[test]
+    fn udp_write_and_read() {
+        // Create a connection which sends to itself
+        let mut udp = UdpComm::new("127.0.0.1:0", "127.0.0.1:9", LineFormat::Json).unwrap();
+        udp.send_address = udp.socket.local_addr().unwrap().to_string();
+
+        // Send and receive an event
+        let id = ItemId::new_unchecked(40);
+        udp.write_event(id, 5, 6).unwrap();
+        assert_eq!(wait_for(&mut udp), vec![(id, 5, 6)]);
+    }
+}
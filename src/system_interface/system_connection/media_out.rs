@@ -37,6 +37,10 @@ use reqwest::Client as AsyncClient;
 #[cfg(feature = "media-out")]
 use reqwest::blocking::Client;
 
+// Import FNV HashMap
+#[cfg(feature = "media-out")]
+use fnv::FnvHashMap;
+
 // Import the failure elements
 use failure::Error;
 
@@ -168,6 +172,8 @@ pub struct MediaOut {
     client: Option<Client>,         // the reqwest client for pass media changes
     #[cfg(feature = "media-out")]
     address: String,                // the address for requests to Apollo
+    #[cfg(feature = "media-out")]
+    last_cues: FnvHashMap<u32, MediaCue>, // the most recent media cue of each channel
 }
 
 // Implement key functionality for the Media Out structure
@@ -197,6 +203,7 @@ impl MediaOut {
             media_map,
             client: None,
             address,
+            last_cues: FnvHashMap::default(),
         })
     }
 
@@ -227,6 +234,47 @@ impl MediaOut {
         // Indicate success
         Ok(())
     }
+
+    /// A method to return the most recent media cue of each channel, sorted
+    /// by channel, active version
+    ///
+    #[cfg(feature = "media-out")]
+    pub fn output_state(&self) -> Vec<MediaCue> {
+        let mut media_cues: Vec<MediaCue> = self.last_cues.values().cloned().collect();
+        media_cues.sort_by_key(|media_cue| media_cue.channel);
+        media_cues
+    }
+
+    /// A method to return the most recent media cue of each channel, inactive
+    /// version
+    ///
+    #[cfg(not(feature = "media-out"))]
+    pub fn output_state(&self) -> Vec<MediaCue> {
+        Vec::new()
+    }
+
+    /// A method to restart the provided media cues (from the beginning of
+    /// the media), active version
+    ///
+    #[cfg(feature = "media-out")]
+    pub fn restore_cues(&mut self, media_cues: &[MediaCue]) {
+        // Create the request client if it doen't exist
+        if self.client.is_none() {
+            self.client = Some(Client::new());
+        }
+
+        // Add each media cue, ignoring errors
+        for media_cue in media_cues.iter() {
+            if self.add_cue(media_cue.clone()).is_ok() {
+                self.last_cues.insert(media_cue.channel, media_cue.clone());
+            }
+        }
+    }
+
+    /// A method to restart the provided media cues, inactive version
+    ///
+    #[cfg(not(feature = "media-out"))]
+    pub fn restore_cues(&mut self, _media_cues: &[MediaCue]) {}
 }
 
 // Implement the event connection trait for Media Out
@@ -250,18 +298,22 @@ impl EventConnection for MediaOut {
         if id == ItemId::all_stop() {
             // Stop all the currently playing media
             let response = self.client.as_ref().unwrap().post(&format!("http://{}/allStop", &self.address)).send()?;
+            self.last_cues.clear();
 
             // Run all of the all stop media, ignoring errors
-            for media_cue in self.all_stop_media.iter() {
+            for media_cue in self.all_stop_media.clone() {
                 // Add the media cues
-                self.add_cue(media_cue.clone()).unwrap_or(());
+                if self.add_cue(media_cue.clone()).is_ok() {
+                    self.last_cues.insert(media_cue.channel, media_cue);
+                }
             }
 
         // Check to see if the event is in the media map
         } else {
             // Pass the new media cue
-            if let Some(media_cue) = self.media_map.get(&id) {
+            if let Some(media_cue) = self.media_map.get(&id).cloned() {
                 self.add_cue(media_cue.clone())?;
+                self.last_cues.insert(media_cue.channel, media_cue);
             }
         }
 
@@ -0,0 +1,540 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to communicate with MIDI devices using raw MIDI device files
+//!
+//! # Note
+//!
+//! Raw MIDI devices are provided by ALSA (e.g. "/dev/snd/midiC1D0") and by
+//! the snd-virmidi module for software which uses the ALSA sequencer. Incoming
+//! note on, note off, control change, and program change messages are
+//! matched against the MIDI map. Other messages (including system exclusive
+//! and real-time messages) are ignored.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::{EventConnection, ReadResult};
+
+// Import standard library modules and traits
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::sync::mpsc;
+use std::thread;
+
+// Import the failure features
+use failure::Error;
+
+// Define the MIDI constants
+const MAX_DATA: u8 = 127; // the largest value of a MIDI data byte
+const NOTE_OFF: u8 = 0x80; // the note off status
+const NOTE_ON: u8 = 0x90; // the note on status
+const CONTROL_CHANGE: u8 = 0xB0; // the control change status
+const PROGRAM_CHANGE: u8 = 0xC0; // the program change status
+const CHANNEL_PRESSURE: u8 = 0xD0; // the channel pressure status
+const SYSEX_START: u8 = 0xF0; // the start of a system exclusive message
+const REALTIME_START: u8 = 0xF8; // the first real-time status
+
+/// A structure to hold and manipulate the connection to a MIDI device
+///
+pub struct MidiComm {
+    input: Option<mpsc::Receiver<Result<Vec<u8>, Error>>>, // the bytes received from the input device
+    output: Option<File>,                   // the output device
+    parser: MidiParser,                     // the parser for incoming bytes
+    midi_map: MidiMap,                      // the map of event ids to MIDI messages
+    filter_events: Vec<(ItemId, u32, u32)>, // events to filter out
+}
+
+// Implement key functionality for the MIDI structure
+impl MidiComm {
+    /// A function to create a new instance of the MidiComm
+    ///
+    pub fn new(
+        input: &Option<String>,
+        output: &Option<String>,
+        midi_map: MidiMap,
+    ) -> Result<MidiComm, Error> {
+        // Read from the input device in the background
+        let input = input.as_ref().map(|path| MidiComm::spawn_reader(path.clone()));
+
+        // Open the output device, if specified
+        let output = match output {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .map_err(|error| format_err!("Unable to open MIDI output {}: {}", path, error))?,
+            ),
+            None => None,
+        };
+
+        // Return the new MidiComm instance
+        Ok(MidiComm {
+            input,
+            output,
+            parser: MidiParser::default(),
+            midi_map,
+            filter_events: Vec::new(),
+        })
+    }
+
+    /// A helper function to read from the input device on a separate thread
+    /// (raw MIDI devices block until bytes arrive)
+    ///
+    fn spawn_reader(path: String) -> mpsc::Receiver<Result<Vec<u8>, Error>> {
+        // Create the line to pass bytes back
+        let (byte_send, byte_receive) = mpsc::channel();
+
+        // Read until the device closes or the connection is dropped
+        thread::spawn(move || {
+            // Try to open the device
+            let mut device = match File::open(&path) {
+                Ok(device) => device,
+                Err(error) => {
+                    byte_send
+                        .send(Err(format_err!("Unable to open MIDI input {}: {}", path, error)))
+                        .unwrap_or(());
+                    return;
+                }
+            };
+
+            // Pass along the bytes as they arrive
+            let mut buffer = [0; 256];
+            loop {
+                match device.read(&mut buffer) {
+                    // Notify the system that the device was closed
+                    Ok(0) => {
+                        byte_send
+                            .send(Err(format_err!("MIDI input closed: {}", path)))
+                            .unwrap_or(());
+                        break;
+                    }
+
+                    // Pass the bytes, quitting if the connection was dropped
+                    Ok(length) => {
+                        if byte_send.send(Ok(buffer[..length].to_vec())).is_err() {
+                            break;
+                        }
+                    }
+
+                    // Retry if interrupted
+                    Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+
+                    // Otherwise, notify the system of the error
+                    Err(error) => {
+                        byte_send.send(Err(error.into())).unwrap_or(());
+                        break;
+                    }
+                }
+            }
+        });
+
+        // Return the receiving line
+        byte_receive
+    }
+}
+
+// Implement the event connection trait for MidiComm
+impl EventConnection for MidiComm {
+    /// A method to receive new events from the MIDI device
+    ///
+    fn read_events(&mut self) -> Vec<ReadResult> {
+        // Collect the bytes which have arrived
+        let mut results = Vec::new();
+        let mut bytes = Vec::new();
+        let mut is_closed = false;
+        if let Some(ref input) = self.input {
+            loop {
+                match input.try_recv() {
+                    Ok(Ok(mut new_bytes)) => bytes.append(&mut new_bytes),
+                    Ok(Err(error)) => results.push(ReadResult::ReadError(error)),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        is_closed = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Stop listening if the reader has finished
+        if is_closed {
+            self.input = None;
+        }
+
+        // Convert the messages to events
+        for message in self.parser.parse(&bytes) {
+            for (id, entry) in self.midi_map.iter() {
+                if let Some(data1) = match_message(entry, &message) {
+                    results.push(ReadResult::Normal(*id, data1, 0));
+                }
+            }
+        }
+
+        // Add the incoming events to the filter
+        for result in results.iter() {
+            // Check to make sure it's a valid event
+            if let ReadResult::Normal(id, data1, data2) = result {
+                self.filter_events.push((*id, *data1, *data2));
+            }
+        }
+
+        // Return the resulting events
+        results
+    }
+
+    /// A method to send a new event to the MIDI device
+    ///
+    fn write_event(&mut self, id: ItemId, data1: u32, _data2: u32) -> Result<(), Error> {
+        // Check to see if the event has a message and there is an output
+        if let (Some(message), Some(output)) = (self.midi_map.get(&id), self.output.as_mut()) {
+            // Encode the message and send it to the device
+            let bytes = encode_message(message, data1)?;
+            output.write_all(&bytes)?;
+            output.flush()?;
+        }
+
+        // Ignore events without a message
+        Ok(())
+    }
+
+    /// A method to echo an event to the MIDI device
+    ///
+    fn echo_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Filter each event before echoing it to the system
+        match self
+            .filter_events
+            .iter()
+            .position(|filter| *filter == (id, data1, data2))
+        {
+            // Remove that event from the filter
+            Some(count) => {
+                self.filter_events.remove(count);
+                Ok(())
+            }
+
+            // Otherwise, echo the event to the system
+            None => self.write_event(id, data1, data2),
+        }
+    }
+}
+
+/// A helper structure to assemble MIDI messages from a stream of bytes,
+/// including messages which use running status
+///
+#[derive(Debug, Default)]
+struct MidiParser {
+    status: Option<u8>, // the current (running) status, if any
+    data: Vec<u8>,      // the data bytes of the current message
+}
+
+// Implement the MIDI parser
+impl MidiParser {
+    /// A method to parse the new bytes, returning any completed messages
+    ///
+    fn parse(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        // Process each byte
+        let mut messages = Vec::new();
+        for &byte in bytes {
+            match byte {
+                // Ignore real-time messages (they may appear anywhere)
+                REALTIME_START..=0xFF => (),
+
+                // Cancel the running status for system messages
+                SYSEX_START..=0xF7 => {
+                    self.status = None;
+                    self.data.clear();
+                }
+
+                // Start a new channel message
+                NOTE_OFF..=0xEF => {
+                    self.status = Some(byte);
+                    self.data.clear();
+                }
+
+                // Add data bytes to the current message
+                _ => {
+                    if let Some(status) = self.status {
+                        self.data.push(byte);
+
+                        // Decode the message when complete
+                        if self.data.len() == data_length(status) {
+                            if let Some(message) = decode_message(status, &self.data) {
+                                messages.push(message);
+                            }
+                            self.data.clear();
+                        }
+                    }
+                }
+            }
+        }
+
+        // Return the completed messages
+        messages
+    }
+}
+
+// A helper function to return the number of data bytes for a channel status
+fn data_length(status: u8) -> usize {
+    match status & 0xF0 {
+        PROGRAM_CHANGE | CHANNEL_PRESSURE => 1,
+        _ => 2,
+    }
+}
+
+// A helper function to decode a complete channel message
+fn decode_message(status: u8, data: &[u8]) -> Option<MidiMessage> {
+    // Separate the channel from the status
+    let channel = (status & 0x0F) + 1;
+    match status & 0xF0 {
+        // A note on with zero velocity is a note off
+        NOTE_OFF => Some(MidiMessage::NoteOff {
+            channel,
+            note: data[0],
+        }),
+        NOTE_ON if data[1] == 0 => Some(MidiMessage::NoteOff {
+            channel,
+            note: data[0],
+        }),
+        NOTE_ON => Some(MidiMessage::NoteOn {
+            channel,
+            note: data[0],
+            velocity: Some(data[1]),
+        }),
+        CONTROL_CHANGE => Some(MidiMessage::ControlChange {
+            channel,
+            controller: data[0],
+            value: Some(data[1]),
+        }),
+        PROGRAM_CHANGE => Some(MidiMessage::ProgramChange {
+            channel,
+            program: data[0],
+        }),
+
+        // Ignore the other channel messages
+        _ => None,
+    }
+}
+
+// A helper function to check an incoming message against a map entry,
+// returning the value to use as data1 if they match
+fn match_message(entry: &MidiMessage, message: &MidiMessage) -> Option<u32> {
+    match (entry, message) {
+        (
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            },
+            MidiMessage::NoteOn {
+                channel: new_channel,
+                note: new_note,
+                velocity: Some(new_velocity),
+            },
+        ) if channel == new_channel
+            && note == new_note
+            && velocity.unwrap_or(*new_velocity) == *new_velocity =>
+        {
+            Some(*new_velocity as u32)
+        }
+        (
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            },
+            MidiMessage::ControlChange {
+                channel: new_channel,
+                controller: new_controller,
+                value: Some(new_value),
+            },
+        ) if channel == new_channel
+            && controller == new_controller
+            && value.unwrap_or(*new_value) == *new_value =>
+        {
+            Some(*new_value as u32)
+        }
+        (MidiMessage::NoteOff { .. }, MidiMessage::NoteOff { .. })
+        | (MidiMessage::ProgramChange { .. }, MidiMessage::ProgramChange { .. })
+            if entry == message =>
+        {
+            Some(0)
+        }
+        _ => None,
+    }
+}
+
+// A helper function to encode a message, using data1 for any missing value
+fn encode_message(message: &MidiMessage, data1: u32) -> Result<Vec<u8>, Error> {
+    // Use data1 as the value, if valid
+    let fill = |value: Option<u8>| {
+        value.unwrap_or(if data1 <= MAX_DATA as u32 {
+            data1 as u8
+        } else {
+            MAX_DATA
+        })
+    };
+
+    // Compose the status and data bytes
+    let (status, channel, data) = match *message {
+        MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        } => (NOTE_ON, channel, vec![note, fill(velocity)]),
+        MidiMessage::NoteOff { channel, note } => (NOTE_OFF, channel, vec![note, 0]),
+        MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        } => (CONTROL_CHANGE, channel, vec![controller, fill(value)]),
+        MidiMessage::ProgramChange { channel, program } => (PROGRAM_CHANGE, channel, vec![program]),
+    };
+
+    // Check the channel and the data bytes
+    if !(1..=16).contains(&channel) {
+        return Err(format_err!("Invalid MIDI channel: {}", channel));
+    }
+    if data.iter().any(|byte| *byte > MAX_DATA) {
+        return Err(format_err!("Invalid MIDI message: {:?}", message));
+    }
+
+    // Return the complete message
+    let mut bytes = vec![status | (channel - 1)];
+    bytes.extend(data);
+    Ok(bytes)
+}
+
+// Tests of the MIDI module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Verify that messages are parsed, including running status
+    #[test]
+    fn parse_messages() {
+        let mut parser = MidiParser::default();
+        let messages = parser.parse(&[
+            0x90, 60, 100, 0xF8, 62, 0, // note on, clock, then running status note off
+            0xF0, 1, 2, 0xF7, // system exclusive
+            0xB1, 7, // an incomplete control change ...
+        ]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::NoteOn {
+                    channel: 1,
+                    note: 60,
+                    velocity: Some(100)
+                },
+                MidiMessage::NoteOff {
+                    channel: 1,
+                    note: 62
+                },
+            ]
+        );
+
+        // ... which is completed by the next read
+        let messages = parser.parse(&[64, 0xC2, 5]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::ControlChange {
+                    channel: 2,
+                    controller: 7,
+                    value: Some(64)
+                },
+                MidiMessage::ProgramChange {
+                    channel: 3,
+                    program: 5
+                },
+            ]
+        );
+    }
+
+    // Verify the matching and encoding of map entries
+    #[test]
+    fn match_and_encode() {
+        let entry = MidiMessage::ControlChange {
+            channel: 16,
+            controller: 1,
+            value: None,
+        };
+        let message = MidiMessage::ControlChange {
+            channel: 16,
+            controller: 1,
+            value: Some(90),
+        };
+        assert_eq!(match_message(&entry, &message), Some(90));
+        assert_eq!(encode_message(&entry, 90).unwrap(), vec![0xBF, 1, 90]);
+        assert_eq!(encode_message(&entry, 500).unwrap(), vec![0xBF, 1, 127]);
+
+        // Reject mismatched and invalid messages
+        let fixed = MidiMessage::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: Some(127),
+        };
+        let message = MidiMessage::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: Some(100),
+        };
+        assert_eq!(match_message(&fixed, &message), None);
+        assert!(encode_message(&MidiMessage::ProgramChange { channel: 0, program: 1 }, 0).is_err());
+    }
+
+    // Verify that events pass through a loopback device
+    #[test]
+    fn loopback() {
+        // Create a named pipe to stand in for a virtual MIDI device
+        let path = std::env::temp_dir().join(format!("minerva_midi_{}", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let status = std::process::Command::new("mkfifo").arg(&path).status().unwrap();
+        assert!(status.success());
+
+        // Connect to both ends of the device
+        let mut midi_map = MidiMap::default();
+        midi_map.insert(
+            ItemId::new_unchecked(10),
+            MidiMessage::NoteOn {
+                channel: 10,
+                note: 36,
+                velocity: None,
+            },
+        );
+        let mut midi = MidiComm::new(&Some(path.clone()), &Some(path.clone()), midi_map).unwrap();
+
+        // Send an event and wait for it to return
+        midi.write_event(ItemId::new_unchecked(10), 80, 0).unwrap();
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            for result in midi.read_events() {
+                if let ReadResult::Normal(id, data1, data2) = result {
+                    events.push((id, data1, data2));
+                }
+            }
+            if !events.is_empty() {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events, vec![(ItemId::new_unchecked(10), 80, 0)]);
+    }
+}
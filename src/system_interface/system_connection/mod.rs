@@ -24,8 +24,14 @@
 
 // Define private submodules
 mod comedy_comm;
+mod dmx_net;
 mod dmx_out;
+mod line_comm;
 mod media_out;
+mod midi_comm;
+mod mqtt_comm;
+mod osc_comm;
+mod webhook;
 mod zmq_comm;
 
 // Import crate definitions
@@ -33,8 +39,13 @@ use crate::definitions::*;
 
 // Import other definitions
 use self::comedy_comm::ComedyComm;
-use self::dmx_out::DmxOut;
+use self::dmx_out::{DmxOut, DmxOutput};
+use self::line_comm::{TcpComm, UdpComm};
 use self::media_out::{MediaOut};
+use self::midi_comm::MidiComm;
+use self::mqtt_comm::MqttComm;
+use self::osc_comm::OscComm;
+use self::webhook::Webhook;
 use self::zmq_comm::{ZmqBind, ZmqConnect};
 
 // Import standard library features
@@ -48,6 +59,20 @@ use failure::Error;
 // Import program constants
 use super::POLLING_RATE; // the polling rate for the system
 
+// Define module constants
+const FILTER_LIMIT: usize = 100; // the maximum number of events in each echo filter
+
+/// A struct to hold the state of the program when an event is broadcast, for
+/// connections which report more than the event id and data.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BroadcastContext {
+    pub event_id: Option<ItemId>,            // the event which was broadcast
+    pub description: String,                 // the description of the event
+    pub scene: Option<ItemPair>,             // the current scene, if any
+    pub statuses: Vec<(ItemPair, ItemPair)>, // the current state of each status
+}
+
 // Define communication constants
 enum ReadResult {
     // A variant for a successful event read
@@ -103,10 +128,38 @@ impl ConnectionType {
                 ref dmx_map,
             } => {
                 // Create the new dmx connection
-                let connection = DmxOut::new(path, all_stop_dmx.clone(), dmx_map.clone())?;
+                let output = DmxOutput::serial(path)?;
+                let connection = DmxOut::new(output, all_stop_dmx.clone(), dmx_map.clone())?;
                 Ok(LiveConnection::DmxSerial { connection })
             }
 
+            // Connect to a live version of the Art-Net output
+            &ConnectionType::ArtNet {
+                ref target,
+                ref universes,
+                ref all_stop_dmx,
+                ref dmx_map,
+            } => {
+                // Create the new dmx connection
+                let output = DmxOutput::art_net(target, universes.clone())?;
+                let connection = DmxOut::new(output, all_stop_dmx.clone(), dmx_map.clone())?;
+                Ok(LiveConnection::ArtNet { connection })
+            }
+
+            // Connect to a live version of the sACN output
+            &ConnectionType::Sacn {
+                ref target,
+                ref source_name,
+                ref universes,
+                ref all_stop_dmx,
+                ref dmx_map,
+            } => {
+                // Create the new dmx connection
+                let output = DmxOutput::sacn(target, source_name, universes.clone())?;
+                let connection = DmxOut::new(output, all_stop_dmx.clone(), dmx_map.clone())?;
+                Ok(LiveConnection::Sacn { connection })
+            }
+
             // Connect to a live version of the Media output
             &ConnectionType::Media {
                 ref all_stop_media,
@@ -126,6 +179,75 @@ impl ConnectionType {
                 ).await?;
                 Ok(LiveConnection::Media { connection })
             }
+
+            // Connect to a live version of the OSC connection
+            &ConnectionType::Osc {
+                ref bind_address,
+                ref send_address,
+                ref address_map,
+            } => {
+                // Create the new OSC connection
+                let connection = OscComm::new(bind_address, send_address, address_map.clone())?;
+                Ok(LiveConnection::Osc { connection })
+            }
+
+            // Connect to a live version of the MQTT connection
+            &ConnectionType::Mqtt {
+                ref broker,
+                ref client_id,
+                ref topic_prefix,
+                ref qos,
+                ref topic_map,
+            } => {
+                // Create the new MQTT connection
+                let connection =
+                    MqttComm::new(broker, client_id, topic_prefix, *qos, topic_map.clone())?;
+                Ok(LiveConnection::Mqtt { connection })
+            }
+
+            // Connect to a live version of the TCP connection
+            &ConnectionType::Tcp {
+                ref address,
+                ref is_server,
+                ref format,
+            } => {
+                // Create the new TCP connection
+                let connection = TcpComm::new(address, *is_server, format.clone())?;
+                Ok(LiveConnection::Tcp { connection })
+            }
+
+            // Connect to a live version of the UDP connection
+            &ConnectionType::Udp {
+                ref bind_address,
+                ref send_address,
+                ref format,
+            } => {
+                // Create the new UDP connection
+                let connection = UdpComm::new(bind_address, send_address, format.clone())?;
+                Ok(LiveConnection::Udp { connection })
+            }
+
+            // Connect to a live version of the webhook connection
+            &ConnectionType::Webhook {
+                ref request_map,
+                ref retries,
+                ref backoff,
+            } => {
+                // Create the new webhook connection
+                let connection = Webhook::new(request_map.clone(), *retries, *backoff)?;
+                Ok(LiveConnection::Webhook { connection })
+            }
+
+            // Connect to a live version of the MIDI connection
+            &ConnectionType::Midi {
+                ref input,
+                ref output,
+                ref midi_map,
+            } => {
+                // Create the new MIDI connection
+                let connection = MidiComm::new(input, output, midi_map.clone())?;
+                Ok(LiveConnection::Midi { connection })
+            }
         }
     }
 }
@@ -161,11 +283,99 @@ enum LiveConnection {
         connection: DmxOut, // the DMX serial connection
     },
 
+    /// A variant to send DMX over the network with Art-Net. This connection
+    /// type only allows messages to be sent.
+    ArtNet {
+        connection: DmxOut, // the DMX Art-Net connection
+    },
+
+    /// A variant to send DMX over the network with sACN (E1.31). This
+    /// connection type only allows messages to be sent.
+    Sacn {
+        connection: DmxOut, // the DMX sACN connection
+    },
+
     /// A variant to connect with system media. This connection type only allows
     /// messages to be sent.
     Media {
         connection: MediaOut, // the system media connection
     },
+
+    /// A variant to send and receive Open Sound Control messages over UDP.
+    Osc {
+        connection: OscComm, // the OSC connection
+    },
+
+    /// A variant to publish and subscribe to topics on an MQTT broker.
+    Mqtt {
+        connection: MqttComm, // the MQTT connection
+    },
+
+    /// A variant to exchange newline-delimited events over TCP.
+    Tcp {
+        connection: TcpComm, // the TCP connection
+    },
+
+    /// A variant to exchange newline-delimited events over UDP.
+    Udp {
+        connection: UdpComm, // the UDP connection
+    },
+
+    /// A variant to send HTTP requests when events are broadcast.
+    Webhook {
+        connection: Webhook, // the webhook connection
+    },
+
+    /// A variant to connect with a MIDI device.
+    Midi {
+        connection: MidiComm, // the MIDI connection
+    },
+}
+
+// Implement key features of the live connection
+impl LiveConnection {
+    /// A method to provide the context of the next broadcast event to the
+    /// connections which use it
+    ///
+    fn set_context(&mut self, context: &BroadcastContext) {
+        if let &mut LiveConnection::Webhook { ref mut connection } = self {
+            connection.set_context(context);
+        }
+    }
+
+    /// A method to add the current state of the outputs to the provided
+    /// output state, for the connections which have outputs to restore
+    ///
+    fn add_output_state(&self, output_state: &mut OutputState) {
+        match self {
+            LiveConnection::DmxSerial { connection }
+            | LiveConnection::ArtNet { connection }
+            | LiveConnection::Sacn { connection } => {
+                output_state.dmx_levels.extend(connection.output_state())
+            }
+            LiveConnection::Media { connection } => {
+                output_state.media_cues.extend(connection.output_state())
+            }
+            _ => (),
+        }
+    }
+
+    /// A method to restore the outputs of the connection from a previous
+    /// output state
+    ///
+    fn restore_output(&mut self, output_state: &OutputState) {
+        match self {
+            LiveConnection::DmxSerial { connection }
+            | LiveConnection::ArtNet { connection }
+            | LiveConnection::Sacn { connection } => {
+                connection.restore_levels(&output_state.dmx_levels)
+            }
+            LiveConnection::Media { connection } => {
+                connection.restore_cues(&output_state.media_cues)
+            }
+            _ => (),
+        }
+    }
 }
 
 // Implement event connection for LiveConnection
@@ -178,7 +388,15 @@ impl EventConnection for LiveConnection {
             &mut LiveConnection::ZmqPrimary { ref mut connection } => connection.read_events(),
             &mut LiveConnection::ZmqSecondary { ref mut connection } => connection.read_events(),
             &mut LiveConnection::DmxSerial { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::ArtNet { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Sacn { ref mut connection } => connection.read_events(),
             &mut LiveConnection::Media { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Osc { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Mqtt { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Tcp { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Udp { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Webhook { ref mut connection } => connection.read_events(),
+            &mut LiveConnection::Midi { ref mut connection } => connection.read_events(),
         }
     }
 
@@ -198,9 +416,33 @@ impl EventConnection for LiveConnection {
             &mut LiveConnection::DmxSerial { ref mut connection } => {
                 connection.write_event(id, data1, data2)
             }
+            &mut LiveConnection::ArtNet { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
+            &mut LiveConnection::Sacn { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
             &mut LiveConnection::Media { ref mut connection } => {
                 connection.write_event(id, data1, data2)
             }
+            &mut LiveConnection::Osc { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
+            &mut LiveConnection::Mqtt { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
+            &mut LiveConnection::Tcp { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
+            &mut LiveConnection::Udp { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
+            &mut LiveConnection::Webhook { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
+            &mut LiveConnection::Midi { ref mut connection } => {
+                connection.write_event(id, data1, data2)
+            }
         }
     }
 
@@ -220,9 +462,33 @@ impl EventConnection for LiveConnection {
             &mut LiveConnection::DmxSerial { ref mut connection } => {
                 connection.echo_event(id, data1, data2)
             }
+            &mut LiveConnection::ArtNet { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
+            &mut LiveConnection::Sacn { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
             &mut LiveConnection::Media { ref mut connection } => {
                 connection.echo_event(id, data1, data2)
             }
+            &mut LiveConnection::Osc { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
+            &mut LiveConnection::Mqtt { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
+            &mut LiveConnection::Tcp { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
+            &mut LiveConnection::Udp { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
+            &mut LiveConnection::Webhook { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
+            &mut LiveConnection::Midi { ref mut connection } => {
+                connection.echo_event(id, data1, data2)
+            }
         }
     }
 }
@@ -230,9 +496,14 @@ impl EventConnection for LiveConnection {
 /// An private enum to send broadcast events to the system connection
 ///
 enum ConnectionUpdate {
-    /// A variant to indicate an event should be broadcast
+    /// A variant to indicate an event should be broadcast, with the context
+    /// of the event if requested
     ///
-    Broadcast(ItemId, Option<u32>),
+    Broadcast(ItemId, Option<u32>, Option<BroadcastContext>),
+
+    /// A variant to restore the outputs of the connections from a previous
+    /// output state
+    Restore(OutputState),
 
     /// A variant to indicate that the connection process should stop
     Stop,
@@ -245,6 +516,7 @@ pub struct SystemConnection {
     connection_send: Option<mpsc::Sender<ConnectionUpdate>>, // receiving structure for new events from the program
                                                              //connection: Option<LiveConnection>, // an element that implements both read and write
     is_broken: bool, // flag to indicate if one or more connections failed to establish
+    needs_context: bool, // flag to indicate if one or more connections use the broadcast context
 }
 
 // Implement key Logger struct features
@@ -271,6 +543,7 @@ impl SystemConnection {
             internal_send,
             connection_send: None,
             is_broken: false,
+            needs_context: false,
         };
 
         // Try to update the system connection using the provided connection type(s)
@@ -297,9 +570,15 @@ impl SystemConnection {
         // Reset the connection
         self.connection_send = None;
         self.is_broken = false;
+        self.needs_context = false;
 
         // Check to see if there is a provided connection set
         if let Some((conn_set, identifier)) = connections {
+            // Note whether any connection uses the broadcast context
+            self.needs_context = conn_set
+                .iter()
+                .any(|connection| matches!(connection, ConnectionType::Webhook { .. }));
+
             // Initialize the system connections
             let mut live_connections = Vec::new();
             for connection in conn_set {
@@ -334,13 +613,24 @@ impl SystemConnection {
         true
     }
 
+    /// A method to indicate whether the connections use the broadcast context.
+    ///
+    pub fn needs_context(&self) -> bool {
+        self.needs_context
+    }
+
     /// A method to send messages between the underlying system and the program.
     ///
-    pub async fn broadcast(&mut self, new_event: ItemId, data: Option<u32>) {
+    pub async fn broadcast(
+        &mut self,
+        new_event: ItemId,
+        data: Option<u32>,
+        context: Option<BroadcastContext>,
+    ) {
         // Extract the connection, if it exists
         if let Some(ref mut conn) = self.connection_send {
             // Send the new event
-            let result = conn.send(ConnectionUpdate::Broadcast(new_event, data));
+            let result = conn.send(ConnectionUpdate::Broadcast(new_event, data, context));
             if let Err(e) = result {
                 log!(err &self.internal_send => "Unable To Connect: {}", e);
             }
@@ -352,6 +642,19 @@ impl SystemConnection {
         }
     }
 
+    /// A method to restore the outputs of the connections (such as DMX
+    /// levels and media cues) from a previous output state.
+    ///
+    pub async fn restore_output(&mut self, output_state: OutputState) {
+        // Extract the connection, if it exists
+        if let Some(ref conn) = self.connection_send {
+            // Send the output state
+            if let Err(e) = conn.send(ConnectionUpdate::Restore(output_state)) {
+                log!(err &self.internal_send => "Unable To Connect: {}", e);
+            }
+        }
+    }
+
     /// An internal function to run a loop of the system connection
     ///
     fn run_loop(
@@ -361,9 +664,11 @@ impl SystemConnection {
         identifier: Identifier,
     ) {
         // Run the loop until there is an error or instructed to quit
+        let mut output_state = OutputState::default();
         loop {
             // Save the start time of the loop
             let loop_start = Instant::now();
+            let mut is_written = false;
             
             // Read all results from the system connections
             let mut results = Vec::new();
@@ -383,6 +688,7 @@ impl SystemConnection {
                                 .echo_event(id.clone(), game_id.clone(), data2.clone())
                                 .unwrap_or(());
                         }
+                        is_written = true;
 
                         // If an identifier was specified
                         if let Some(identity) = identifier.id {
@@ -448,7 +754,14 @@ impl SystemConnection {
             let update = conn_recv.try_recv();
             match update {
                 // Send the new event
-                Ok(ConnectionUpdate::Broadcast(id, data)) => {
+                Ok(ConnectionUpdate::Broadcast(id, data, context)) => {
+                    // Pass the context to the connections, if provided
+                    if let Some(context) = context {
+                        for connection in connections.iter_mut() {
+                            connection.set_context(&context);
+                        }
+                    }
+
                     // Use the identifier or zero for the game id
                     let game_id = match identifier.id {
                         Some(id) => id,
@@ -490,6 +803,15 @@ impl SystemConnection {
                             }
                         }
                     }
+                    is_written = true;
+                }
+
+                // Restore the outputs of every connection
+                Ok(ConnectionUpdate::Restore(previous_state)) => {
+                    for connection in connections.iter_mut() {
+                        connection.restore_output(&previous_state);
+                    }
+                    is_written = true;
                 }
 
                 // Quit when instructed or when there is an error
@@ -500,6 +822,18 @@ impl SystemConnection {
                 _ => (),
             }
 
+            // If the outputs have changed, send the new output state to the system
+            if is_written {
+                let mut new_state = OutputState::default();
+                for connection in connections.iter() {
+                    connection.add_output_state(&mut new_state);
+                }
+                if new_state != output_state {
+                    output_state = new_state;
+                    internal_send.blocking_send(InternalUpdate::OutputState(output_state.clone()));
+                }
+            }
+
             // Make sure that some time elapses in each loop
             if Duration::from_millis(POLLING_RATE) > loop_start.elapsed() {
                 thread::sleep(Duration::from_millis(POLLING_RATE));
@@ -508,6 +842,15 @@ impl SystemConnection {
     }
 }
 
+// A helper function to add an event to an echo filter, forgetting the oldest
+// event if the filter is full (for example, if the events are never echoed)
+fn push_filter(filter: &mut Vec<(ItemId, u32, u32)>, event: (ItemId, u32, u32)) {
+    if filter.len() >= FILTER_LIMIT {
+        filter.remove(0);
+    }
+    filter.push(event);
+}
+
 /// Define the EventConnection Trait
 ///
 /// This is a convience trait to standardize reading from and writing to the
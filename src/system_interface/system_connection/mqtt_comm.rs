@@ -0,0 +1,795 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to communicate with an MQTT broker
+//!
+//! # Note
+//!
+//! This module implements a minimal MQTT 3.1.1 client with support for QoS
+//! levels 0 and 1. Messages published at QoS 1 are kept until the broker
+//! acknowledges them, and are sent again after reconnecting. Events in the
+//! topic map are published to and received
+//! from their topic (after the topic prefix). All other events are published
+//! to and received from the topic prefix followed by the event id.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::{push_filter, EventConnection, ReadResult};
+
+// Import standard library modules and traits
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+// Import the failure features
+use failure::Error;
+
+// Define the communication constants
+const DEFAULT_PORT: u16 = 1883; // the default port of an MQTT broker
+const KEEP_ALIVE: u64 = 30; // the keep alive interval, in seconds
+const CONNECT_TIMEOUT: u64 = 1; // the time to wait for the broker to respond, in seconds
+const RECONNECT_DELAY: u64 = 5; // the initial time between attempts to reconnect, in seconds
+const MAX_RECONNECT_DELAY: u64 = 60; // the maximum time between attempts to reconnect, in seconds
+const MAX_LENGTH_BYTES: usize = 4; // the maximum number of bytes in the remaining length
+const UNACKED_LIMIT: usize = 100; // the maximum number of unacknowledged messages to keep
+const CONNECT: u8 = 0x10; // the connect packet type
+const CONNACK: u8 = 0x20; // the connect acknowledgement packet type
+const PUBLISH: u8 = 0x30; // the publish packet type
+const PUBACK: u8 = 0x40; // the publish acknowledgement packet type
+const SUBSCRIBE: u8 = 0x82; // the subscribe packet type (with required flags)
+const DUPLICATE: u8 = 0x08; // the flag for a publish packet which is sent again
+const PINGREQ: u8 = 0xC0; // the ping request packet type
+
+/// A structure to hold and manipulate the connection to an MQTT broker
+///
+pub struct MqttComm {
+    address: String,                    // the address of the broker
+    client_id: String,                  // the client id for this connection
+    topic_prefix: String,               // the prefix for every topic
+    qos: u8,                            // the quality of service for publish and subscribe
+    topic_map: MqttMap,                 // the map of event ids to topics
+    stream: Option<TcpStream>,          // the connection to the broker, if connected
+    buffer: Vec<u8>,                    // the current input buffer
+    packet_id: u16,                     // the most recent packet id
+    last_send: Instant,                 // the time of the most recent packet sent
+    last_attempt: Instant,              // the time of the most recent connection attempt
+    reconnect_delay: Duration,          // the current time between attempts to reconnect
+    filter_in: Vec<(ItemId, u32, u32)>, // events to filter, incoming
+    filter_out: Vec<(ItemId, u32, u32)>, // events to filter, outgoing
+    unacked: Vec<(u16, Vec<u8>)>,       // the published messages waiting for acknowledgement (the packet id and body)
+}
+
+// Implement key functionality for the MQTT structure
+impl MqttComm {
+    /// A function to create a new instance of the MqttComm and connect to the
+    /// broker.
+    ///
+    pub fn new(
+        broker: &str,
+        client_id: &str,
+        topic_prefix: &str,
+        qos: u8,
+        topic_map: MqttMap,
+    ) -> Result<MqttComm, Error> {
+        // Verify the quality of service
+        if qos > 1 {
+            return Err(format_err!("MQTT QoS {} is not supported.", qos));
+        }
+
+        // Remove the scheme from the broker address and add the port, if needed
+        let address = broker
+            .trim_start_matches("mqtt://")
+            .trim_start_matches("tcp://")
+            .trim_end_matches('/');
+        let address = match address.contains(':') {
+            true => address.to_string(),
+            false => format!("{}:{}", address, DEFAULT_PORT),
+        };
+
+        // Create the connection and connect to the broker
+        let mut mqtt = MqttComm {
+            address,
+            client_id: client_id.to_string(),
+            topic_prefix: topic_prefix.to_string(),
+            qos,
+            topic_map,
+            stream: None,
+            buffer: Vec::new(),
+            packet_id: 0,
+            last_send: Instant::now(),
+            last_attempt: Instant::now(),
+            reconnect_delay: Duration::from_secs(RECONNECT_DELAY),
+            filter_in: Vec::new(),
+            filter_out: Vec::new(),
+            unacked: Vec::new(),
+        };
+        mqtt.connect()?;
+
+        // Return the new connection
+        Ok(mqtt)
+    }
+
+    /// A helper method to try to reconnect to the broker, waiting longer
+    /// between each failed attempt
+    ///
+    fn reconnect(&mut self) -> Result<(), Error> {
+        // Try to connect to the broker
+        let result = self.connect();
+
+        // Reset the delay on success, or double the delay on failure
+        self.reconnect_delay = match result {
+            Ok(()) => Duration::from_secs(RECONNECT_DELAY),
+            Err(_) => (self.reconnect_delay * 2).min(Duration::from_secs(MAX_RECONNECT_DELAY)),
+        };
+        result
+    }
+
+    /// A helper method to connect to the broker and subscribe to the topics
+    ///
+    fn connect(&mut self) -> Result<(), Error> {
+        // Note the connection attempt
+        self.last_attempt = Instant::now();
+        self.stream = None;
+        self.buffer.clear();
+
+        // Connect to the broker (waiting at most the connect timeout)
+        let timeout = Duration::from_secs(CONNECT_TIMEOUT);
+        let address = self
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format_err!("Invalid MQTT broker address: {}", self.address))?;
+        let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+
+        // Send the connect packet (clean session)
+        let mut body = Vec::new();
+        write_string(&mut body, "MQTT");
+        body.push(4); // the protocol level
+        body.push(0x02); // the connect flags
+        body.extend_from_slice(&(KEEP_ALIVE as u16).to_be_bytes());
+        write_string(&mut body, &self.client_id);
+        stream.write_all(&packet(CONNECT, &body))?;
+
+        // Wait for the acknowledgement
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply)?;
+        if (reply[0] != CONNACK) || (reply[3] != 0) {
+            return Err(format_err!("MQTT broker refused connection: {}", reply[3]));
+        }
+
+        // Subscribe to every mapped topic and to the event id topics
+        let mut topics: Vec<String> = self
+            .topic_map
+            .values()
+            .map(|topic| format!("{}{}", self.topic_prefix, topic.topic))
+            .collect();
+        topics.push(format!("{}+", self.topic_prefix));
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.next_packet_id().to_be_bytes());
+        for topic in topics.iter() {
+            write_string(&mut body, topic);
+            body.push(self.qos);
+        }
+        stream.write_all(&packet(SUBSCRIBE, &body))?;
+
+        // Send any unacknowledged messages again
+        for (_, body) in self.unacked.iter() {
+            stream.write_all(&packet(PUBLISH | DUPLICATE | (self.qos << 1), body))?;
+        }
+
+        // Allow the connection to be polled without blocking
+        stream.set_nonblocking(true)?;
+        self.stream = Some(stream);
+        self.last_send = Instant::now();
+        Ok(())
+    }
+
+    /// A helper method to return a new packet id
+    ///
+    fn next_packet_id(&mut self) -> u16 {
+        self.packet_id = self.packet_id.wrapping_add(1).max(1);
+        self.packet_id
+    }
+
+    /// A helper method to send a packet to the broker, dropping the
+    /// connection if the send fails
+    ///
+    fn send(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        // Try to send the packet
+        let result = match self.stream {
+            Some(ref mut stream) => stream.write_all(bytes).map_err(|error| error.into()),
+            None => Err(format_err!("Not connected to the MQTT broker.")),
+        };
+
+        // Drop the connection on failure
+        match result {
+            Ok(()) => self.last_send = Instant::now(),
+            Err(_) => self.stream = None,
+        }
+        result
+    }
+
+    /// A helper method to find the event that corresponds to a topic
+    ///
+    fn find_event(&self, topic: &str) -> Option<(ItemId, MqttPayload)> {
+        // Remove the topic prefix
+        let topic = topic.strip_prefix(self.topic_prefix.as_str())?;
+
+        // Look for the topic in the topic map
+        for (id, mqtt_topic) in self.topic_map.iter() {
+            if mqtt_topic.topic == topic {
+                return Some((*id, mqtt_topic.payload.clone()));
+            }
+        }
+
+        // Otherwise, try to read the topic as an event id
+        match topic.parse::<u32>() {
+            Ok(id) => ItemId::new(id).map(|id| (id, MqttPayload::Numbers)),
+            Err(_) => None,
+        }
+    }
+
+    /// A helper method to process a single packet from the broker
+    ///
+    fn process_packet(&mut self, header: u8, body: &[u8], results: &mut Vec<ReadResult>) {
+        // Forget any published message which was acknowledged
+        if header & 0xF0 == PUBACK {
+            if let Some(bytes) = body.get(0..2) {
+                let packet_id = u16::from_be_bytes([bytes[0], bytes[1]]);
+                self.unacked.retain(|(id, _)| *id != packet_id);
+            }
+            return;
+        }
+
+        // Otherwise, only publish packets are of interest
+        if header & 0xF0 != PUBLISH {
+            return;
+        }
+
+        // Read the topic and the packet id (if needed)
+        let qos = (header >> 1) & 0x03;
+        let topic_length = match body.get(0..2) {
+            Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]) as usize,
+            None => return,
+        };
+        let mut position = 2 + topic_length;
+        let topic = match body.get(2..position) {
+            Some(bytes) => String::from_utf8_lossy(bytes).to_string(),
+            None => return,
+        };
+        if qos > 0 {
+            // Acknowledge the message
+            if let Some(bytes) = body.get(position..position + 2) {
+                let ack = packet(PUBACK, bytes);
+                self.send(&ack).unwrap_or(());
+            }
+            position += 2;
+        }
+        let payload = String::from_utf8_lossy(body.get(position..).unwrap_or(&[])).to_string();
+
+        // Convert the topic and payload to an event
+        match self.find_event(&topic) {
+            Some((id, payload_type)) => match payload_type.decode(&payload) {
+                Some((data1, data2)) => results.push(ReadResult::Normal(id, data1, data2)),
+                None => results.push(ReadResult::ReadError(format_err!(
+                    "Invalid MQTT payload for {}: {}",
+                    topic,
+                    payload
+                ))),
+            },
+            None => results.push(ReadResult::ReadError(format_err!(
+                "Unrecognized MQTT topic: {}",
+                topic
+            ))),
+        }
+    }
+}
+
+// Implement the event connection trait for MqttComm
+impl EventConnection for MqttComm {
+    /// A method to receive new events from the MQTT connection
+    ///
+    fn read_events(&mut self) -> Vec<ReadResult> {
+        // Reconnect to the broker if the connection was lost
+        let mut results = Vec::new();
+        if self.stream.is_none() {
+            // Wait between attempts
+            if self.last_attempt.elapsed() < self.reconnect_delay {
+                return results;
+            }
+            if let Err(error) = self.reconnect() {
+                results.push(ReadResult::ReadError(error));
+                return results;
+            }
+        }
+
+        // Keep the connection alive
+        if self.last_send.elapsed() > Duration::from_secs(KEEP_ALIVE / 2) {
+            if let Err(error) = self.send(&[PINGREQ, 0]) {
+                results.push(ReadResult::ReadError(error));
+                return results;
+            }
+        }
+
+        // Read any new bytes from the broker
+        let mut buffer = [0; 1024];
+        loop {
+            let result = match self.stream {
+                Some(ref mut stream) => stream.read(&mut buffer),
+                None => break,
+            };
+            match result {
+                // The broker closed the connection
+                Ok(0) => {
+                    self.stream = None;
+                    results.push(ReadResult::ReadError(format_err!(
+                        "MQTT broker closed the connection."
+                    )));
+                    break;
+                }
+
+                // Add the bytes to the buffer
+                Ok(count) => self.buffer.extend_from_slice(&buffer[..count]),
+
+                // Stop when there are no more bytes
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => break,
+
+                // Otherwise, drop the connection and notify the system
+                Err(error) => {
+                    self.stream = None;
+                    results.push(ReadResult::ReadError(error.into()));
+                    break;
+                }
+            }
+        }
+
+        // Process every complete packet in the buffer
+        loop {
+            match split_packet(&self.buffer) {
+                // Process the packet
+                Ok(Some((header, start, end))) => {
+                    let body = self.buffer[start..end].to_vec();
+                    self.buffer.drain(..end);
+                    self.process_packet(header, &body, &mut results);
+                }
+
+                // Wait for the rest of the packet
+                Ok(None) => break,
+
+                // Drop the connection if the packet is malformed
+                Err(error) => {
+                    self.stream = None;
+                    self.buffer.clear();
+                    results.push(ReadResult::ReadError(error));
+                    break;
+                }
+            }
+        }
+
+        // Filter out the events that were sent by this connection
+        let mut filtered = Vec::new();
+        for result in results.drain(..) {
+            if let ReadResult::Normal(id, data1, data2) = result {
+                // Remove the event from the outgoing filter, if found
+                if let Some(index) = self
+                    .filter_out
+                    .iter()
+                    .position(|filter| *filter == (id, data1, data2))
+                {
+                    self.filter_out.remove(index);
+                    continue;
+                }
+
+                // Otherwise, add the event to the incoming filter
+                push_filter(&mut self.filter_in, (id, data1, data2));
+            }
+            filtered.push(result);
+        }
+
+        // Return the list of results
+        filtered
+    }
+
+    /// A method to publish a new event to the MQTT connection
+    ///
+    fn write_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Find the topic and payload of the event
+        let (topic, payload) = match self.topic_map.get(&id) {
+            Some(mqtt_topic) => (
+                format!("{}{}", self.topic_prefix, mqtt_topic.topic),
+                mqtt_topic.payload.encode(data1, data2),
+            ),
+            None => (
+                format!("{}{}", self.topic_prefix, id),
+                MqttPayload::Numbers.encode(data1, data2),
+            ),
+        };
+
+        // Compose the publish packet
+        let mut body = Vec::new();
+        write_string(&mut body, &topic);
+        let packet_id = match self.qos > 0 {
+            true => Some(self.next_packet_id()),
+            false => None,
+        };
+        if let Some(packet_id) = packet_id {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+        }
+        body.extend_from_slice(payload.as_bytes());
+
+        // Keep the message until it is acknowledged (forgetting the oldest if full)
+        if let Some(packet_id) = packet_id {
+            if self.unacked.len() >= UNACKED_LIMIT {
+                self.unacked.remove(0);
+            }
+            self.unacked.push((packet_id, body.clone()));
+        }
+
+        // Send the packet and add the event to the filter (if it was sent or will be sent again)
+        let result = self.send(&packet(PUBLISH | (self.qos << 1), &body));
+        if result.is_ok() || packet_id.is_some() {
+            push_filter(&mut self.filter_out, (id, data1, data2));
+        }
+        result
+    }
+
+    /// A method to echo events back to the MQTT connection. This method
+    /// filters out events that were received on the MQTT connection.
+    ///
+    fn echo_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        match self
+            .filter_in
+            .iter()
+            .position(|filter| *filter == (id, data1, data2))
+        {
+            // Remove that event from the filter
+            Some(index) => {
+                self.filter_in.remove(index);
+                Ok(())
+            }
+
+            // Otherwise, send the event
+            None => self.write_event(id, data1, data2),
+        }
+    }
+}
+
+// Implement the payload conversions
+impl MqttPayload {
+    /// A method to decode a payload into data1 and data2. Returns None if the
+    /// payload is invalid.
+    ///
+    fn decode(&self, payload: &str) -> Option<(u32, u32)> {
+        match self {
+            // Ignore the payload
+            MqttPayload::Empty => Some((0, 0)),
+
+            // Read up to two numbers from the payload
+            MqttPayload::Numbers => {
+                let mut numbers = payload.split_whitespace().map(|number| number.parse::<u32>());
+                let data1 = numbers.next().unwrap_or(Ok(0)).ok()?;
+                let data2 = numbers.next().unwrap_or(Ok(0)).ok()?;
+                Some((data1, data2))
+            }
+
+            // Read the numbers from the fields of the JSON object
+            MqttPayload::Json { data1, data2 } => {
+                let object: serde_json::Value = serde_json::from_str(payload).ok()?;
+                let read_field = |field: &Option<String>| match field {
+                    Some(field) => object.get(field).and_then(|value| value.as_u64()).map(|value| value as u32),
+                    None => Some(0),
+                };
+                Some((read_field(data1)?, read_field(data2)?))
+            }
+        }
+    }
+
+    /// A method to encode data1 and data2 as a payload
+    ///
+    fn encode(&self, data1: u32, data2: u32) -> String {
+        match self {
+            // Send an empty payload
+            MqttPayload::Empty => String::new(),
+
+            // Send both numbers
+            MqttPayload::Numbers => format!("{} {}", data1, data2),
+
+            // Send the numbers in the fields of a JSON object
+            MqttPayload::Json {
+                data1: field1,
+                data2: field2,
+            } => {
+                let mut object = serde_json::Map::new();
+                if let Some(field) = field1 {
+                    object.insert(field.clone(), data1.into());
+                }
+                if let Some(field) = field2 {
+                    object.insert(field.clone(), data2.into());
+                }
+                serde_json::Value::Object(object).to_string()
+            }
+        }
+    }
+}
+
+// A helper function to write a length-prefixed string
+fn write_string(buffer: &mut Vec<u8>, string: &str) {
+    buffer.extend_from_slice(&(string.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(string.as_bytes());
+}
+
+// A helper function to compose a packet with the remaining length
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    // Add the header
+    let mut bytes = vec![header];
+
+    // Encode the remaining length
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        bytes.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+
+    // Add the body
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+// A helper function to find the first complete packet in the buffer. Returns
+// the header and the start and end of the body, or None if the packet is
+// not complete.
+fn split_packet(buffer: &[u8]) -> Result<Option<(u8, usize, usize)>, Error> {
+    // Read the header
+    let header = match buffer.first() {
+        Some(header) => *header,
+        None => return Ok(None),
+    };
+
+    // Decode the remaining length (at most four bytes)
+    let mut length = 0;
+    let mut multiplier = 1;
+    let mut position = 1;
+    loop {
+        if position > MAX_LENGTH_BYTES {
+            return Err(format_err!("Invalid MQTT packet length."));
+        }
+        let byte = match buffer.get(position) {
+            Some(byte) => *byte,
+            None => return Ok(None),
+        };
+        length += (byte & 0x7F) as usize * multiplier;
+        multiplier *= 128;
+        position += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    // Verify the complete packet has arrived
+    if buffer.len() < position + length {
+        return Ok(None);
+    }
+    Ok(Some((header, position, position + length)))
+}
+
+// Tests of the MQTT module
+#[cfg(test)]
+mod tests {
+    use super::super::FILTER_LIMIT;
+    use super::*;
+
+    // Test payload decoding and encoding
+    #[test]
+    fn payloads() {
+        // Check each type of payload
+        assert_eq!(MqttPayload::Empty.decode("anything"), Some((0, 0)));
+        assert_eq!(MqttPayload::Numbers.decode("3 4"), Some((3, 4)));
+        assert_eq!(MqttPayload::Numbers.decode("7"), Some((7, 0)));
+        assert_eq!(MqttPayload::Numbers.decode("on"), None);
+        let json = MqttPayload::Json {
+            data1: None,
+            data2: Some("level".to_string()),
+        };
+        assert_eq!(json.decode("{\"level\": 12}"), Some((0, 12)));
+        assert_eq!(json.decode("{\"other\": 12}"), None);
+        assert_eq!(json.encode(1, 12), "{\"level\":12}");
+        assert_eq!(MqttPayload::Numbers.encode(1, 2), "1 2");
+    }
+
+    // Test splitting complete, partial, and malformed packets
+    #[test]
+    fn split_packets() {
+        // Check a complete packet and a partial packet
+        let bytes = packet(PUBLISH, &[1; 200]);
+        assert_eq!(split_packet(&bytes).unwrap(), Some((PUBLISH, 3, 203)));
+        assert_eq!(split_packet(&bytes[..100]).unwrap(), None);
+        assert_eq!(split_packet(&[PUBLISH, 0x80]).unwrap(), None);
+
+        // Check that a remaining length longer than four bytes is rejected
+        assert!(split_packet(&[PUBLISH, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]).is_err());
+
+        // Check that the filter is bounded
+        let mut filter = Vec::new();
+        for count in 0..(FILTER_LIMIT as u32 + 5) {
+            push_filter(&mut filter, (ItemId::new_unchecked(1), count, 0));
+        }
+        assert_eq!(filter.len(), FILTER_LIMIT);
+        assert_eq!(filter[0].1, 5);
+    }
+
+    // Send events to and receive events from a stand-in broker
+    #[test]
+    fn publish_and_receive() {
+        // Import std library features
+        use std::net::TcpListener;
+        use std::thread;
+
+        // Create the stand-in broker
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let broker = thread::spawn(move || {
+            // Accept the connection and read the connect packet
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = Vec::new();
+            let mut read_packet = |stream: &mut TcpStream| loop {
+                if let Some((header, start, end)) = split_packet(&buffer).unwrap() {
+                    let body = buffer[start..end].to_vec();
+                    buffer.drain(..end);
+                    return (header, body);
+                }
+                let mut bytes = [0; 256];
+                let count = stream.read(&mut bytes).unwrap();
+                buffer.extend_from_slice(&bytes[..count]);
+            };
+            let (header, body) = read_packet(&mut stream);
+            assert_eq!(header, CONNECT);
+            assert_eq!(&body[10..], b"\x00\x04door");
+            stream.write_all(&[CONNACK, 2, 0, 0]).unwrap();
+
+            // Read the subscription
+            let (header, _) = read_packet(&mut stream);
+            assert_eq!(header, SUBSCRIBE);
+
+            // Publish a message on the mapped topic
+            let mut body = Vec::new();
+            write_string(&mut body, "props/door/open");
+            body.extend_from_slice(b"{\"code\": 42}");
+            stream.write_all(&packet(PUBLISH, &body)).unwrap();
+
+            // Read the published event
+            let (header, body) = read_packet(&mut stream);
+            assert_eq!(header, PUBLISH);
+            let mut expected = Vec::new();
+            write_string(&mut expected, "props/205");
+            expected.extend_from_slice(b"0 7");
+            assert_eq!(body, expected);
+        });
+
+        // Create the connection
+        let id_ref = ItemId::new_unchecked(20);
+        let mut topic_map = MqttMap::default();
+        topic_map.insert(
+            id_ref,
+            MqttTopic {
+                topic: "door/open".to_string(),
+                payload: MqttPayload::Json {
+                    data1: None,
+                    data2: Some("code".to_string()),
+                },
+            },
+        );
+        let mut mqtt = MqttComm::new(&address, "door", "props/", 0, topic_map).unwrap();
+
+        // Wait for the published message
+        let mut results = Vec::new();
+        for _ in 0..100 {
+            results.append(&mut mqtt.read_events());
+            if !results.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(results.len(), 1);
+        if let ReadResult::Normal(id, data1, data2) = results.remove(0) {
+            assert_eq!((id, data1, data2), (id_ref, 0, 42));
+        } else {
+            panic!("Read error in the MQTT connection.");
+        }
+
+        // Verify the event is not echoed back, then publish a new event
+        mqtt.echo_event(id_ref, 0, 42).unwrap();
+        mqtt.write_event(ItemId::new_unchecked(205), 0, 7).unwrap();
+        broker.join().unwrap();
+    }
+
+    // Check that unacknowledged messages are sent again after reconnecting
+    #[test]
+    fn resend_unacknowledged() {
+        // Import std library features
+        use std::net::TcpListener;
+        use std::thread;
+
+        // A helper function to accept a connection and read the next publish packet
+        fn accept_publish(listener: &TcpListener) -> (TcpStream, u8, Vec<u8>) {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+            let mut buffer = Vec::new();
+            loop {
+                if let Some((header, start, end)) = split_packet(&buffer).unwrap() {
+                    let body = buffer[start..end].to_vec();
+                    buffer.drain(..end);
+                    match header & 0xF0 {
+                        CONNECT => stream.write_all(&[CONNACK, 2, 0, 0]).unwrap(),
+                        PUBLISH => return (stream, header, body),
+                        _ => (),
+                    }
+                    continue;
+                }
+                let mut bytes = [0; 256];
+                let count = stream.read(&mut bytes).unwrap();
+                buffer.extend_from_slice(&bytes[..count]);
+            }
+        }
+
+        // Create the stand-in broker which drops the first connection without acknowledging
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let broker = thread::spawn(move || {
+            let (stream, header, first) = accept_publish(&listener);
+            assert_eq!(header, PUBLISH | 0x02);
+            drop(stream);
+
+            // Check that the message is sent again and acknowledge it
+            let (mut stream, header, second) = accept_publish(&listener);
+            assert_eq!(header, PUBLISH | DUPLICATE | 0x02);
+            assert_eq!(first, second);
+            let topic_length = u16::from_be_bytes([second[0], second[1]]) as usize;
+            let packet_id = &second[2 + topic_length..4 + topic_length];
+            stream.write_all(&packet(PUBACK, packet_id)).unwrap();
+            thread::sleep(Duration::from_millis(200));
+        });
+
+        // Publish an event at QoS 1
+        let mut mqtt = MqttComm::new(&address, "door", "props/", 1, MqttMap::default()).unwrap();
+        mqtt.write_event(ItemId::new_unchecked(205), 0, 7).unwrap();
+        assert_eq!(mqtt.unacked.len(), 1);
+
+        // Reconnect without waiting and wait for the acknowledgement
+        mqtt.reconnect_delay = Duration::from_secs(0);
+        for _ in 0..100 {
+            mqtt.read_events();
+            if mqtt.unacked.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(mqtt.unacked.is_empty());
+        broker.join().unwrap();
+    }
+}
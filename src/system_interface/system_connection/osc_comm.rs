@@ -0,0 +1,554 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to communicate using Open Sound Control (OSC) messages over UDP
+//!
+//! # Note
+//!
+//! Outgoing events are sent to the address listed in the address map with
+//! two integer arguments (data1 and data2). Incoming messages are matched
+//! against the address map (including OSC address patterns) and the first
+//! two numeric arguments are used as data1 and data2. Events without an
+//! address in the map are not sent.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::{EventConnection, ReadResult};
+
+// Import standard library modules and traits
+use std::io::{Cursor, ErrorKind};
+use std::net::UdpSocket;
+
+// Import the failure features
+use failure::Error;
+
+// Import the byteorder module for converting between types
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+// Define the communication constants
+const MAX_PACKET: usize = 65507; // the largest possible UDP payload
+const BUNDLE_TAG: &str = "#bundle"; // the identifier of an OSC bundle
+const MAX_DEPTH: usize = 8; // the deepest nesting of bundles that will be decoded
+
+/// A structure to hold and manipulate the connection over OSC
+///
+pub struct OscComm {
+    socket: UdpSocket,                      // the UDP socket of the connection
+    send_address: String,                   // the remote address for outgoing messages
+    address_map: OscMap,                    // the map of event ids to OSC addresses
+    filter_events: Vec<(ItemId, u32, u32)>, // events to filter out
+}
+
+// Implement key functionality for the OSC structure
+impl OscComm {
+    /// A function to create a new instance of the OscComm
+    ///
+    pub fn new(
+        bind_address: &str,
+        send_address: &str,
+        address_map: OscMap,
+    ) -> Result<OscComm, Error> {
+        // Bind to the local address
+        let socket = UdpSocket::bind(bind_address)?;
+
+        // Allow the socket to be polled without blocking
+        socket.set_nonblocking(true)?;
+
+        // Return the new OscComm instance
+        Ok(OscComm {
+            socket,
+            send_address: send_address.to_string(),
+            address_map,
+            filter_events: Vec::new(),
+        })
+    }
+
+    /// A helper method to find the event that corresponds to the provided
+    /// address pattern. Returns None if no event matches.
+    ///
+    fn find_event(&self, pattern: &str) -> Option<ItemId> {
+        // Look for an address that matches the pattern
+        for (id, address) in self.address_map.iter() {
+            if pattern_matches(pattern.as_bytes(), address.as_bytes()) {
+                return Some(*id);
+            }
+        }
+
+        // Otherwise, indicate there was no match
+        None
+    }
+
+    /// A helper method to convert a decoded packet into read results
+    ///
+    fn unpack_messages(&self, messages: Vec<(String, Vec<u32>)>, results: &mut Vec<ReadResult>) {
+        // Convert each message to an event
+        for (address, arguments) in messages {
+            // Try to find the matching event
+            if let Some(id) = self.find_event(&address) {
+                // Use the first two arguments as data, or default to zero
+                let data1 = arguments.first().cloned().unwrap_or(0);
+                let data2 = arguments.get(1).cloned().unwrap_or(0);
+                results.push(ReadResult::Normal(id, data1, data2));
+
+            // Otherwise, notify the system
+            } else {
+                results.push(ReadResult::ReadError(format_err!(
+                    "Unrecognized OSC address: {}",
+                    address
+                )));
+            }
+        }
+    }
+}
+
+// Implement the event connection trait for OscComm
+impl EventConnection for OscComm {
+    /// A method to receive new events from the OSC connection
+    ///
+    fn read_events(&mut self) -> Vec<ReadResult> {
+        // Read all the packets available on the socket
+        let mut results = Vec::new();
+        let mut buffer = vec![0; MAX_PACKET];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                // Decode the packet and convert the messages
+                Ok((length, _)) => match decode_packet(&buffer[..length]) {
+                    Ok(messages) => self.unpack_messages(messages, &mut results),
+                    Err(error) => results.push(ReadResult::ReadError(error)),
+                },
+
+                // Stop when there are no more packets
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => break,
+
+                // Otherwise, notify the system of the error
+                Err(error) => {
+                    results.push(ReadResult::ReadError(error.into()));
+                    break;
+                }
+            }
+        }
+
+        // Add the incoming events to the filter
+        for result in results.iter() {
+            // Check to make sure it's a valid event
+            if let ReadResult::Normal(id, data1, data2) = result {
+                self.filter_events.push((*id, *data1, *data2));
+            }
+        }
+
+        // Return the resulting events
+        results
+    }
+
+    /// A method to send a new event to the OSC connection
+    ///
+    fn write_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Check to see if the event has an address
+        if let Some(address) = self.address_map.get(&id) {
+            // Encode the message and send it to the remote address
+            let message = encode_message(address, data1, data2)?;
+            self.socket.send_to(&message, self.send_address.as_str())?;
+        }
+
+        // Ignore events without an address
+        Ok(())
+    }
+
+    /// A method to echo an event to the OSC connection
+    ///
+    fn echo_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Filter each event before echoing it to the system
+        match self
+            .filter_events
+            .iter()
+            .position(|filter| *filter == (id, data1, data2))
+        {
+            // Remove that event from the filter
+            Some(count) => {
+                self.filter_events.remove(count);
+                Ok(())
+            }
+
+            // Otherwise, echo the event to the system
+            None => self.write_event(id, data1, data2),
+        }
+    }
+}
+
+// A helper function to write an OSC string, padded to four bytes
+fn write_string(buffer: &mut Vec<u8>, string: &str) {
+    // Write the string and the null terminator
+    buffer.extend_from_slice(string.as_bytes());
+    buffer.push(0);
+
+    // Pad the string to a multiple of four bytes
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+}
+
+// A helper function to read an OSC string, padded to four bytes
+fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String, Error> {
+    // Read until the null terminator
+    let mut bytes = Vec::new();
+    loop {
+        match cursor.read_u8()? {
+            0 => break,
+            byte => bytes.push(byte),
+        }
+    }
+
+    // Skip the remaining padding
+    while !cursor.position().is_multiple_of(4) {
+        cursor.read_u8()?;
+    }
+
+    // Convert the bytes to a string
+    Ok(String::from_utf8(bytes)?)
+}
+
+// A helper function to read the size of a bundle element or blob, verifying
+// that it fits within the remaining packet
+fn read_size(cursor: &mut Cursor<&[u8]>) -> Result<usize, Error> {
+    // Read the size and the remaining length of the packet
+    let size = cursor.read_i32::<BigEndian>()?;
+    let remaining = cursor.get_ref().len().saturating_sub(cursor.position() as usize);
+
+    // Reject a negative size or one larger than the rest of the packet
+    if size < 0 || size as usize > remaining {
+        return Err(format_err!("Invalid OSC size: {}", size));
+    }
+    Ok(size as usize)
+}
+
+// A helper function to encode an event as an OSC message with two integers
+fn encode_message(address: &str, data1: u32, data2: u32) -> Result<Vec<u8>, Error> {
+    // Write the address and the type tags
+    let mut buffer = Vec::new();
+    write_string(&mut buffer, address);
+    write_string(&mut buffer, ",ii");
+
+    // Write the arguments
+    buffer.write_i32::<BigEndian>(data1 as i32)?;
+    buffer.write_i32::<BigEndian>(data2 as i32)?;
+    Ok(buffer)
+}
+
+// A helper function to decode an OSC packet (a message or a bundle) into
+// a list of addresses and numeric arguments
+fn decode_packet(packet: &[u8]) -> Result<Vec<(String, Vec<u32>)>, Error> {
+    decode_element(packet, 0)
+}
+
+// A helper function to decode an OSC message or a bundle nested within
+// the provided number of bundles
+fn decode_element(packet: &[u8], depth: usize) -> Result<Vec<(String, Vec<u32>)>, Error> {
+    // Read the address (or bundle tag) of the packet
+    let mut cursor = Cursor::new(packet);
+    let address = read_string(&mut cursor)?;
+
+    // If this is a bundle, decode each element
+    if address == BUNDLE_TAG {
+        // Limit the nesting of bundles
+        if depth >= MAX_DEPTH {
+            return Err(format_err!("OSC bundles nested too deeply."));
+        }
+
+        // Skip the time tag
+        cursor.read_u64::<BigEndian>()?;
+
+        // Decode each element of the bundle
+        let mut messages = Vec::new();
+        while (cursor.position() as usize) < packet.len() {
+            // Read the size of the element (already checked against the packet)
+            let size = read_size(&mut cursor)?;
+            let start = cursor.position() as usize;
+            cursor.set_position((start + size) as u64);
+
+            // Decode the element in place
+            messages.append(&mut decode_element(&packet[start..start + size], depth + 1)?);
+        }
+        return Ok(messages);
+    }
+
+    // Verify that the address is valid
+    if !address.starts_with('/') {
+        return Err(format_err!("Invalid OSC address: {}", address));
+    }
+
+    // Read the type tags, if they exist
+    let mut arguments = Vec::new();
+    if (cursor.position() as usize) < packet.len() {
+        let tags = read_string(&mut cursor)?;
+
+        // Read each argument based on the type tag
+        for tag in tags.chars().skip(1) {
+            match tag {
+                // Convert integers and floats to data
+                'i' => arguments.push(cursor.read_i32::<BigEndian>()?.max(0) as u32),
+                'f' => arguments.push(cursor.read_f32::<BigEndian>()?.max(0.0).round() as u32),
+                'h' => {
+                    let value = cursor.read_i64::<BigEndian>()?;
+                    arguments.push(value.clamp(0, u32::MAX as i64) as u32);
+                }
+                'd' => arguments.push(cursor.read_f64::<BigEndian>()?.max(0.0).round() as u32),
+
+                // Convert booleans to data
+                'T' => arguments.push(1),
+                'F' => arguments.push(0),
+
+                // Skip strings, blobs, and other fixed size arguments
+                's' | 'S' => {
+                    read_string(&mut cursor)?;
+                }
+                'b' => {
+                    let size = read_size(&mut cursor)? as u64;
+                    cursor.set_position(cursor.position() + size.div_ceil(4) * 4);
+                }
+                'c' | 'r' | 'm' => {
+                    cursor.read_u32::<BigEndian>()?;
+                }
+                't' => {
+                    cursor.read_u64::<BigEndian>()?;
+                }
+
+                // Ignore arguments without a payload (and array markers)
+                'N' | 'I' | '[' | ']' => (),
+
+                // Otherwise, stop at an unknown argument (its size is unknown)
+                _ => break,
+            }
+        }
+    }
+
+    // Return the completed message
+    Ok(vec![(address, arguments)])
+}
+
+// A helper function to match an OSC address pattern against an address
+fn pattern_matches(pattern: &[u8], address: &[u8]) -> bool {
+    match pattern.first() {
+        // An empty pattern only matches an empty address
+        None => address.is_empty(),
+
+        // Match any sequence of characters within one part of the address
+        Some(b'*') => {
+            // Try every possible length of the sequence
+            let mut index = 0;
+            loop {
+                if pattern_matches(&pattern[1..], &address[index..]) {
+                    return true;
+                }
+                if index >= address.len() || address[index] == b'/' {
+                    return false;
+                }
+                index += 1;
+            }
+        }
+
+        // Match any single character
+        Some(b'?') => match address.first() {
+            Some(&character) if character != b'/' => pattern_matches(&pattern[1..], &address[1..]),
+            _ => false,
+        },
+
+        // Match any character in the list or range
+        Some(b'[') => {
+            // Find the end of the list
+            let end = match pattern.iter().position(|&character| character == b']') {
+                Some(end) => end,
+                None => return false,
+            };
+            let character = match address.first() {
+                Some(&character) => character,
+                None => return false,
+            };
+
+            // Check for negation of the list
+            let (negate, list) = match pattern.get(1) {
+                Some(b'!') => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+
+            // Check each character or range in the list
+            let mut found = false;
+            let mut index = 0;
+            while index < list.len() {
+                if (index + 2 < list.len()) && (list[index + 1] == b'-') {
+                    found |= (list[index] <= character) & (character <= list[index + 2]);
+                    index += 3;
+                } else {
+                    found |= list[index] == character;
+                    index += 1;
+                }
+            }
+
+            // Continue if the character matched
+            (found != negate) && pattern_matches(&pattern[end + 1..], &address[1..])
+        }
+
+        // Match any of the strings in the list
+        Some(b'{') => {
+            // Find the end of the list
+            let end = match pattern.iter().position(|&character| character == b'}') {
+                Some(end) => end,
+                None => return false,
+            };
+
+            // Check each of the options
+            pattern[1..end].split(|&character| character == b',').any(|option| {
+                address.starts_with(option)
+                    && pattern_matches(&pattern[end + 1..], &address[option.len()..])
+            })
+        }
+
+        // Otherwise, match the character exactly
+        Some(&character) => match address.first() {
+            Some(&other) if other == character => pattern_matches(&pattern[1..], &address[1..]),
+            _ => false,
+        },
+    }
+}
+
+// Tests of the OSC module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test matching of address patterns
+    #[test]
+    fn match_patterns() {
+        assert!(pattern_matches(b"/cue/go", b"/cue/go"));
+        assert!(pattern_matches(b"/cue/*", b"/cue/go"));
+        assert!(pattern_matches(b"/*/go", b"/cue/go"));
+        assert!(!pattern_matches(b"/*", b"/cue/go"));
+        assert!(pattern_matches(b"/cue/g?", b"/cue/go"));
+        assert!(pattern_matches(b"/cue/[a-h]o", b"/cue/go"));
+        assert!(!pattern_matches(b"/cue/[!a-h]o", b"/cue/go"));
+        assert!(pattern_matches(b"/cue/{stop,go}", b"/cue/go"));
+        assert!(!pattern_matches(b"/cue/{stop,pause}", b"/cue/go"));
+    }
+
+    // Test that malformed sizes are rejected without panicking
+    #[test]
+    fn reject_malformed() {
+        // Create a bundle with an element larger than the packet
+        let mut bundle = Vec::new();
+        write_string(&mut bundle, BUNDLE_TAG);
+        bundle.write_u64::<BigEndian>(1).unwrap();
+        bundle.write_i32::<BigEndian>(i32::MAX).unwrap();
+        assert!(decode_packet(&bundle).is_err());
+
+        // Create a bundle with a negative element size
+        bundle.truncate(bundle.len() - 4);
+        bundle.write_i32::<BigEndian>(-4).unwrap();
+        assert!(decode_packet(&bundle).is_err());
+
+        // Create a message with a negative blob size
+        let mut message = Vec::new();
+        write_string(&mut message, "/minerva/go");
+        write_string(&mut message, ",b");
+        message.write_i32::<BigEndian>(-1).unwrap();
+        assert!(decode_packet(&message).is_err());
+
+        // Create a message with a blob larger than the packet
+        message.truncate(message.len() - 4);
+        message.write_i32::<BigEndian>(64).unwrap();
+        assert!(decode_packet(&message).is_err());
+
+        // Verify that a valid bundle is still decoded
+        let mut bundle = Vec::new();
+        write_string(&mut bundle, BUNDLE_TAG);
+        bundle.write_u64::<BigEndian>(1).unwrap();
+        let element = encode_message("/minerva/go", 1, 2).unwrap();
+        bundle.write_i32::<BigEndian>(element.len() as i32).unwrap();
+        bundle.extend_from_slice(&element);
+        assert_eq!(
+            decode_packet(&bundle).unwrap(),
+            vec![("/minerva/go".to_string(), vec![1, 2])]
+        );
+
+        // Verify that deeply nested bundles are rejected
+        let mut nested = element;
+        for _ in 0..=MAX_DEPTH {
+            let mut bundle = Vec::new();
+            write_string(&mut bundle, BUNDLE_TAG);
+            bundle.write_u64::<BigEndian>(1).unwrap();
+            bundle.write_i32::<BigEndian>(nested.len() as i32).unwrap();
+            bundle.extend_from_slice(&nested);
+            nested = bundle;
+        }
+        assert!(decode_packet(&nested).is_err());
+    }
+
+    // Test that other argument types are converted or skipped
+    #[test]
+    fn other_arguments() {
+        // Create a message with 64-bit numbers, a timetag, and an unknown type
+        let mut message = Vec::new();
+        write_string(&mut message, "/minerva/go");
+        write_string(&mut message, ",hdtiXi");
+        message.write_i64::<BigEndian>(5).unwrap();
+        message.write_f64::<BigEndian>(2.6).unwrap();
+        message.write_u64::<BigEndian>(1).unwrap();
+        message.write_i32::<BigEndian>(7).unwrap();
+        message.write_i32::<BigEndian>(9).unwrap();
+        assert_eq!(
+            decode_packet(&message).unwrap(),
+            vec![("/minerva/go".to_string(), vec![5, 3, 7])]
+        );
+    }
+
+    // Send events to and receive events from a local OSC connection
+    #[test]
+    fn write_and_read() {
+        // Import std library features
+        use std::thread;
+        use std::time::Duration;
+
+        // Create the address map
+        let id_ref = ItemId::new_unchecked(205);
+        let mut address_map = OscMap::default();
+        address_map.insert(id_ref, "/minerva/go".to_string());
+
+        // Create a new connection which sends to itself
+        let mut osc = OscComm::new("127.0.0.1:0", "127.0.0.1:9", address_map).unwrap();
+        osc.send_address = osc.socket.local_addr().unwrap().to_string();
+
+        // Write a message and wait for it to arrive
+        osc.write_event(id_ref, 1, 29387).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // Read the message and verify that it is correct
+        let mut results = osc.read_events();
+        assert_eq!(results.len(), 1);
+        if let ReadResult::Normal(id, data1, data2) = results.remove(0) {
+            assert_eq!(id, id_ref);
+            assert_eq!(data1, 1);
+            assert_eq!(data2, 29387);
+        } else {
+            panic!("Read error in the OSC connection.");
+        }
+
+        // Verify that the incoming event is not echoed back
+        osc.echo_event(id_ref, 1, 29387).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(osc.read_events().is_empty());
+    }
+}
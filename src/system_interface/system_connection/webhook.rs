@@ -0,0 +1,461 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to send outbound HTTP requests (webhooks) when events are
+//! broadcast.
+//!
+//! # Note
+//!
+//! The url, header values, and body of each request may contain the
+//! placeholders {{id}}, {{description}}, {{data1}}, {{data2}}, {{scene}},
+//! {{scene_id}}, {{statuses}} and {{status.<status id>}}. A string in the
+//! body which is exactly one placeholder is replaced with a value of the
+//! matching type (e.g. a number or an object).
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other definitions
+use super::{BroadcastContext, EventConnection, ReadResult};
+
+// Import standard library features
+#[cfg(feature = "webhook")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+#[cfg(feature = "webhook")]
+use std::sync::Arc;
+#[cfg(feature = "webhook")]
+use std::thread;
+use std::time::Duration;
+
+// Import the failure features
+use failure::Error;
+
+// Import the serde json features
+use serde_json::Value;
+
+// Import reqwest elements
+#[cfg(feature = "webhook")]
+use reqwest::blocking::Client;
+#[cfg(feature = "webhook")]
+use reqwest::Method;
+
+// Define module constants
+#[cfg(feature = "webhook")]
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60); // the longest delay between retries of a request
+#[cfg(feature = "webhook")]
+const MAX_PENDING: usize = 32; // the most requests which may be sending or retrying at once
+
+/// A helper struct to hold a request which is ready to send
+///
+#[derive(Clone, Debug, PartialEq)]
+struct PreparedRequest {
+    method: String,                 // the method of the request
+    url: String,                    // the completed url
+    headers: Vec<(String, String)>, // the completed headers
+    body: Option<Value>,            // the completed body
+}
+
+/// A structure to hold and manipulate the webhook connection
+///
+pub struct Webhook {
+    request_map: WebhookMap,                    // the map of event ids to requests
+    context: BroadcastContext,                  // the context of the most recent broadcast
+    #[cfg_attr(not(feature = "webhook"), allow(dead_code))]
+    request_send: mpsc::Sender<PreparedRequest>, // the line to pass requests to the background thread
+    error_receive: mpsc::Receiver<Error>,       // the line to receive errors from the background thread
+}
+
+// Implement key functionality for the webhook structure
+impl Webhook {
+    /// A function to create a new instance of the Webhook, active version
+    ///
+    #[cfg(feature = "webhook")]
+    pub fn new(request_map: WebhookMap, retries: u32, backoff: Duration) -> Result<Webhook, Error> {
+        // Create the client and the communication lines
+        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        let (request_send, request_receive) = mpsc::channel::<PreparedRequest>();
+        let (error_send, error_receive) = mpsc::channel();
+
+        // Send the requests in the background
+        thread::spawn(move || {
+            // Send each request on its own thread (so that retries to one
+            // endpoint do not delay the other requests)
+            let pending = Arc::new(AtomicUsize::new(0));
+            for request in request_receive.iter() {
+                // Drop the request if too many requests are still pending
+                if pending.load(Ordering::SeqCst) >= MAX_PENDING {
+                    let error = format_err!(
+                        "{} {} dropped: too many pending requests",
+                        request.method,
+                        request.url
+                    );
+                    if error_send.send(error).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+
+                // Send the request, retrying with backoff
+                let client = client.clone();
+                let error_send = error_send.clone();
+                let pending = pending.clone();
+                pending.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || {
+                    if let Err(error) = send_with_retry(&client, &request, retries, backoff) {
+                        error_send.send(error).unwrap_or(());
+                    }
+                    pending.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        // Return the new connection
+        Ok(Webhook {
+            request_map,
+            context: BroadcastContext::default(),
+            request_send,
+            error_receive,
+        })
+    }
+
+    /// A function to create a new instance of the Webhook, inactive version
+    ///
+    #[cfg(not(feature = "webhook"))]
+    pub fn new(request_map: WebhookMap, _retries: u32, _backoff: Duration) -> Result<Webhook, Error> {
+        // Create placeholder communication lines
+        let (request_send, _) = mpsc::channel();
+        let (_, error_receive) = mpsc::channel();
+
+        // Return a partial connection
+        Ok(Webhook {
+            request_map,
+            context: BroadcastContext::default(),
+            request_send,
+            error_receive,
+        })
+    }
+
+    /// A method to update the context for the following events
+    ///
+    pub fn set_context(&mut self, context: &BroadcastContext) {
+        self.context = context.clone();
+    }
+}
+
+// Implement the event connection trait for the webhook
+impl EventConnection for Webhook {
+    /// A method to report any failed requests
+    ///
+    fn read_events(&mut self) -> Vec<ReadResult> {
+        self.error_receive
+            .try_iter()
+            .map(ReadResult::WriteError)
+            .collect()
+    }
+
+    /// A method to send a new event to the webhook connection, active version
+    ///
+    #[cfg(feature = "webhook")]
+    fn write_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        // Check to see if the event has a request
+        if let Some(request) = self.request_map.get(&id) {
+            // Ignore context which is for a different event
+            let context = match self.context.event_id == Some(id) {
+                true => self.context.clone(),
+                false => BroadcastContext::default(),
+            };
+
+            // Fill the template and pass it to the background thread
+            let prepared = prepare(request, id, data1, data2, &context);
+            if self.request_send.send(prepared).is_err() {
+                return Err(format_err!("Background webhook thread has crashed."));
+            }
+        }
+
+        // Indicate success
+        Ok(())
+    }
+
+    /// A method to send a new event to the webhook connection, inactive version
+    ///
+    #[cfg(not(feature = "webhook"))]
+    fn write_event(&mut self, id: ItemId, _data1: u32, _data2: u32) -> Result<(), Error> {
+        // Check to see if the event has a request
+        if self.request_map.contains_key(&id) {
+            return Err(format_err!(
+                "Program compiled without webhook support. See documentation."
+            ));
+        }
+
+        // Otherwise, ignore the event
+        Ok(())
+    }
+
+    /// A method to echo an event to the webhook connection
+    ///
+    fn echo_event(&mut self, id: ItemId, data1: u32, data2: u32) -> Result<(), Error> {
+        self.write_event(id, data1, data2)
+    }
+}
+
+// A helper function to send a request, retrying with increasing delays
+#[cfg(feature = "webhook")]
+fn send_with_retry(
+    client: &Client,
+    request: &PreparedRequest,
+    retries: u32,
+    backoff: Duration,
+) -> Result<(), Error> {
+    // Try the request until it succeeds or the retries are used
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        // Compose and send the request
+        let method = Method::from_bytes(request.method.to_uppercase().as_bytes())?;
+        let mut builder = client.request(method, &request.url);
+        for (key, value) in request.headers.iter() {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        if let Some(ref body) = request.body {
+            builder = builder.json(body);
+        }
+        let error = match builder.send() {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => format_err!("{} {} returned {}", request.method, request.url, response.status()),
+            Err(error) => format_err!("{} {} failed: {}", request.method, request.url, error),
+        };
+
+        // Report the error once the retries are used
+        if attempt >= retries {
+            return Err(error);
+        }
+
+        // Otherwise, wait and try again (limiting the delay)
+        thread::sleep(delay);
+        delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+        attempt += 1;
+    }
+}
+
+// A helper function to fill the templates of a request
+#[cfg(any(feature = "webhook", test))]
+fn prepare(
+    request: &WebhookRequest,
+    id: ItemId,
+    data1: u32,
+    data2: u32,
+    context: &BroadcastContext,
+) -> PreparedRequest {
+    // Create the replacement for each placeholder
+    let mut statuses = serde_json::Map::new();
+    let mut replacements = vec![
+        ("{{id}}".to_string(), Value::from(id.id())),
+        ("{{description}}".to_string(), Value::from(context.description.clone())),
+        ("{{data1}}".to_string(), Value::from(data1)),
+        ("{{data2}}".to_string(), Value::from(data2)),
+        (
+            "{{scene}}".to_string(),
+            Value::from(context.scene.as_ref().map(|scene| scene.description()).unwrap_or_default()),
+        ),
+        (
+            "{{scene_id}}".to_string(),
+            Value::from(context.scene.as_ref().map(|scene| scene.id()).unwrap_or_default()),
+        ),
+    ];
+    for (status, state) in context.statuses.iter() {
+        statuses.insert(status.description.clone(), Value::from(state.description.clone()));
+        replacements.push((
+            format!("{{{{status.{}}}}}", status.id()),
+            Value::from(state.description.clone()),
+        ));
+    }
+    replacements.push(("{{statuses}}".to_string(), Value::Object(statuses)));
+
+    // Fill the templates
+    PreparedRequest {
+        method: request.method.clone(),
+        url: fill_string(&request.url, &replacements),
+        headers: request
+            .headers
+            .iter()
+            .map(|(key, value)| (key.clone(), fill_string(value, &replacements)))
+            .collect(),
+        body: request.body.as_ref().map(|body| fill_value(body, &replacements)),
+    }
+}
+
+// A helper function to replace the placeholders in a string with text (in
+// one pass, so that placeholders within the replacements are left as is)
+#[cfg(any(feature = "webhook", test))]
+fn fill_string(template: &str, replacements: &[(String, Value)]) -> String {
+    let mut result = String::new();
+    let mut remaining = template;
+    while let Some(start) = remaining.find("{{") {
+        // Copy the text before the placeholder
+        result.push_str(&remaining[..start]);
+        remaining = &remaining[start..];
+
+        // Find the matching replacement, if it exists
+        let replacement = remaining.find("}}").and_then(|end| {
+            let placeholder = &remaining[..end + 2];
+            replacements
+                .iter()
+                .find(|(candidate, _)| candidate == placeholder)
+                .map(|(_, value)| (placeholder.len(), value))
+        });
+
+        // Replace the placeholder, or copy the braces as text
+        match replacement {
+            Some((length, value)) => {
+                match value {
+                    Value::String(string) => result.push_str(string),
+                    other => result.push_str(&other.to_string()),
+                }
+                remaining = &remaining[length..];
+            }
+            None => {
+                result.push_str("{{");
+                remaining = &remaining[2..];
+            }
+        }
+    }
+
+    // Copy the rest of the text
+    result.push_str(remaining);
+    result
+}
+
+// A helper function to replace the placeholders in a body template
+#[cfg(any(feature = "webhook", test))]
+fn fill_value(template: &Value, replacements: &[(String, Value)]) -> Value {
+    match template {
+        // Replace an exact placeholder with the value, otherwise fill the text
+        Value::String(string) => {
+            for (placeholder, value) in replacements.iter() {
+                if string == placeholder {
+                    return value.clone();
+                }
+            }
+            Value::String(fill_string(string, replacements))
+        }
+
+        // Fill each element of arrays and objects
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|value| fill_value(value, replacements))
+                .collect(),
+        ),
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), fill_value(value, replacements)))
+                .collect(),
+        ),
+
+        // Leave other values untouched
+        other => other.clone(),
+    }
+}
+
+// Tests of the webhook module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test filling the request templates
+    #[test]
+    fn fill_templates() {
+        // Create the request
+        let body: Value = serde_json::from_str(
+            "{\"text\": \"{{description}} in {{scene}}\", \"id\": \"{{id}}\", \"door\": \"{{status.5}}\", \"all\": \"{{statuses}}\"}",
+        )
+        .unwrap();
+        let mut headers = fnv::FnvHashMap::default();
+        headers.insert("X-Event".to_string(), "{{id}}-{{data2}}".to_string());
+        let request = WebhookRequest {
+            method: "POST".to_string(),
+            url: "http://localhost/hook/{{scene_id}}".to_string(),
+            headers,
+            body: Some(body),
+        };
+
+        // Create the context
+        let hidden = DisplayType::Hidden { edit_location: None };
+        let context = BroadcastContext {
+            event_id: Some(ItemId::new_unchecked(12)),
+            description: "Door Opened".to_string(),
+            scene: Some(ItemPair::new_unchecked(3, "Lobby", hidden)),
+            statuses: vec![(
+                ItemPair::new_unchecked(5, "Door", hidden),
+                ItemPair::new_unchecked(6, "Open", hidden),
+            )],
+        };
+
+        // Verify the result
+        let prepared = prepare(&request, ItemId::new_unchecked(12), 0, 9, &context);
+        assert_eq!(prepared.url, "http://localhost/hook/3");
+        assert_eq!(prepared.headers, vec![("X-Event".to_string(), "12-9".to_string())]);
+        let expected: Value = serde_json::from_str(
+            "{\"text\": \"Door Opened in Lobby\", \"id\": 12, \"door\": \"Open\", \"all\": {\"Door\": \"Open\"}}",
+        )
+        .unwrap();
+        assert_eq!(prepared.body, Some(expected));
+
+        // Verify that placeholders within the replacements are left as is
+        let replacements = vec![
+            ("{{description}}".to_string(), Value::from("{{data1}}")),
+            ("{{data1}}".to_string(), Value::from(4)),
+        ];
+        assert_eq!(
+            fill_string("{{ {{description}}: {{data1}} {{other}}", &replacements),
+            "{{ {{data1}}: 4 {{other}}"
+        );
+    }
+
+    // Test that failed requests are reported as write errors
+    #[cfg(feature = "webhook")]
+    #[test]
+    fn report_failure() {
+        // Create a webhook to an unused local port
+        let id = ItemId::new_unchecked(12);
+        let mut request_map = WebhookMap::default();
+        request_map.insert(
+            id,
+            WebhookRequest {
+                method: "POST".to_string(),
+                url: "http://127.0.0.1:9/hook".to_string(),
+                headers: fnv::FnvHashMap::default(),
+                body: None,
+            },
+        );
+        let mut webhook = Webhook::new(request_map, 1, Duration::from_millis(10)).unwrap();
+
+        // Send the event and wait for the failure
+        webhook.write_event(id, 0, 0).unwrap();
+        let mut results = Vec::new();
+        for _ in 0..200 {
+            results.append(&mut webhook.read_events());
+            if !results.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(results.first(), Some(ReadResult::WriteError(_))));
+    }
+}
@@ -22,11 +22,16 @@
 use crate::definitions::*;
 
 // Define private submodules
+mod trigger_api;
 mod web_definitions;
 
 // Import the web definitions
+use self::trigger_api::{handle_rejection, with_authorization, SignatureCache, TriggerSettings};
 use self::web_definitions::*;
 
+// Import standard library features
+use std::sync::{Arc, Mutex};
+
 // Import Tokio and warp features
 use tokio::sync::{mpsc, oneshot};
 use warp::{http, Filter};
@@ -40,7 +45,7 @@ use futures_util::StreamExt;
 use serde::de::DeserializeOwned;
 
 // Import constants
-use crate::USER_STYLE_SHEET;
+use crate::{TRIGGER_SETTINGS, USER_STYLE_SHEET};
 
 /// A structure to contain the web interface and handle all updates to the
 /// to the interface.
@@ -113,6 +118,50 @@ impl WebInterface {
             warp::serve(limited_cue_event).run(([127, 0, 0, 1], 64635)).await;
         });
 
+        // Spin up a thread for the trigger api, if configured
+        match TriggerSettings::from_file(TRIGGER_SETTINGS) {
+            // Serve the trigger api on the chosen interface
+            Ok(Some(settings)) => {
+                let clone_send = self.web_send.clone();
+                tokio::spawn(async move {
+                    // Share the settings and the signature cache between the filters
+                    let address = settings.address;
+                    let settings = Arc::new(settings);
+                    let seen = Arc::new(Mutex::new(SignatureCache::default()));
+
+                    // Create the process event filter
+                    let process_event = warp::post()
+                        .and(warp::path("processEvent"))
+                        .and(warp::path::end())
+                        .and(WebInterface::with_clone(clone_send.clone()))
+                        .and(with_authorization::<ProcessEvent>(settings.clone(), seen.clone()))
+                        .and_then(WebInterface::handle_request);
+
+                    // Create the status change filter
+                    let status_change = warp::post()
+                        .and(warp::path("statusChange"))
+                        .and(warp::path::end())
+                        .and(WebInterface::with_clone(clone_send.clone()))
+                        .and(with_authorization::<StatusChange>(settings.clone(), seen.clone()))
+                        .and_then(WebInterface::handle_request);
+
+                    // Combine the filters and explain any rejections
+                    let trigger_routes = process_event
+                        .or(status_change)
+                        .recover(handle_rejection);
+
+                    // Serve this route on the chosen interface
+                    warp::serve(trigger_routes).run(address).await;
+                });
+            }
+
+            // Leave the trigger api disabled if there are no settings
+            Ok(None) => (),
+
+            // Otherwise, note the error
+            Err(error) => tracing::error!("{}", error),
+        }
+
         // Spin up a thread for the run port (64636)
         let clone_send = self.web_send.clone();
         let clone_index = self.index_access.clone();
@@ -166,6 +215,14 @@ impl WebInterface {
                 .and(WebInterface::with_clone(UserRequest::ClearQueue))
                 .and_then(WebInterface::handle_request);
 
+            // Create the clock speed filter
+            let clock_speed = warp::post()
+                .and(warp::path("clockSpeed"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<ClockSpeed>())
+                .and_then(WebInterface::handle_request);
+
             // Create the close filter
             let close = warp::post()
                 .and(warp::path("close"))
@@ -198,6 +255,22 @@ impl WebInterface {
                 .and(WebInterface::with_json::<DebugMode>())
                 .and_then(WebInterface::handle_request);
 
+            // Create the enable schedule filter
+            let enable_schedule = warp::post()
+                .and(warp::path("enableSchedule"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<EnableSchedule>())
+                .and_then(WebInterface::handle_request);
+
+            // Create the end game filter
+            let end_game = warp::post()
+                .and(warp::path("endGame"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_clone(UserRequest::EndGame))
+                .and_then(WebInterface::handle_request);
+
             // Create the error log filter
             let error_log = warp::post()
                 .and(warp::path("errorLog"))
@@ -214,6 +287,14 @@ impl WebInterface {
                 .and(WebInterface::with_json::<EventChange>())
                 .and_then(WebInterface::handle_request);
 
+            // Create the fire event filter
+            let fire_event = warp::post()
+                .and(warp::path("fireEvent"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<FireEvent>())
+                .and_then(WebInterface::handle_request);
+
             // Create the game log filter
             let game_log = warp::post()
                 .and(warp::path("gameLog"))
@@ -244,6 +325,22 @@ impl WebInterface {
                 .and(warp::path::end())
                 .and_then(WebInterface::handle_request);
 
+            // Create the hold event filter
+            let hold_event = warp::post()
+                .and(warp::path("holdEvent"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<HoldEvent>())
+                .and_then(WebInterface::handle_request);
+
+            // Create the pause queue filter
+            let pause_queue = warp::post()
+                .and(warp::path("pauseQueue"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_clone(UserRequest::PauseQueue))
+                .and_then(WebInterface::handle_request);
+
             // Create the process event filter
             let process_event = warp::post()
                 .and(warp::path("processEvent"))
@@ -252,6 +349,54 @@ impl WebInterface {
                 .and(WebInterface::with_json::<ProcessEvent>())
                 .and_then(WebInterface::handle_request);
 
+            // Create the query log filter
+            let query_log = warp::post()
+                .and(warp::path("queryLog"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<QueryLog>())
+                .and_then(WebInterface::handle_request);
+
+            // Create the redo filter
+            let redo = warp::post()
+                .and(warp::path("redo"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<Redo>())
+                .and_then(WebInterface::handle_request);
+
+            // Create the redraw filter (also repeats any prompt to restore a saved game)
+            let redraw = warp::post()
+                .and(warp::path("redraw"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_clone(UserRequest::Redraw))
+                .and_then(WebInterface::handle_request);
+
+            // Create the release event filter
+            let release_event = warp::post()
+                .and(warp::path("releaseEvent"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<ReleaseEvent>())
+                .and_then(WebInterface::handle_request);
+
+            // Create the restore game filter
+            let restore_game = warp::post()
+                .and(warp::path("restoreGame"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<RestoreGame>())
+                .and_then(WebInterface::handle_request);
+
+            // Create the resume queue filter
+            let resume_queue = warp::post()
+                .and(warp::path("resumeQueue"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_clone(UserRequest::ResumeQueue))
+                .and_then(WebInterface::handle_request);
+
             // Create the scene change filter
             let scene_change = warp::post()
                 .and(warp::path("sceneChange"))
@@ -268,6 +413,14 @@ impl WebInterface {
                 .and(WebInterface::with_json::<StatusChange>())
                 .and_then(WebInterface::handle_request);
 
+            // Create the undo filter
+            let undo = warp::post()
+                .and(warp::path("undo"))
+                .and(warp::path::end())
+                .and(WebInterface::with_clone(clone_send.clone()))
+                .and(WebInterface::with_json::<Undo>())
+                .and_then(WebInterface::handle_request);
+
             // Create the main page filter
             let run_page = warp::get()
                 .and(warp::fs::dir("./public_run/")); 
@@ -279,19 +432,32 @@ impl WebInterface {
                 .or(all_stop)
                 .or(broadcast_event)
                 .or(clear_queue)
+                .or(clock_speed)
                 .or(close)
                 .or(config_file)
                 .or(cue_event)
                 .or(debug_mode)
+                .or(enable_schedule)
+                .or(end_game)
                 .or(error_log)
                 .or(event_change)
+                .or(fire_event)
                 .or(game_log)
                 .or(get_item)
                 .or(get_styles)
                 .or(get_type)
+                .or(hold_event)
+                .or(pause_queue)
                 .or(process_event)
+                .or(query_log)
+                .or(redo)
+                .or(redraw)
+                .or(release_event)
+                .or(restore_game)
+                .or(resume_queue)
                 .or(scene_change)
                 .or(status_change)
+                .or(undo)
                 .or(run_page);
 
             // Serve this route on a separate port
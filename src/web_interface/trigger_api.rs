@@ -0,0 +1,500 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to authenticate requests to the inbound trigger api. Each caller
+//! is identified by name and must provide either an API key or an HMAC
+//! signature of the request. Callers are only allowed to trigger the events
+//! and change the statuses listed in their settings.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import the web definitions
+use super::web_definitions::{ProcessEvent, StatusChange};
+
+// Import standard library features
+use std::fs::File;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// Import FNV HashMap
+use fnv::{FnvHashMap, FnvHashSet};
+
+// Import the failure features
+use failure::Error;
+
+// Import Chrono features
+use chrono::Utc;
+
+// Import the cryptographic features
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+// Import warp and serde features
+use serde::de::DeserializeOwned;
+use warp::hyper::body::Bytes;
+use warp::{http, Filter};
+
+// Define the header names
+const CALLER_HEADER: &str = "x-minerva-caller"; // the name of the caller
+const KEY_HEADER: &str = "x-minerva-key"; // the API key of the caller
+const SIGNATURE_HEADER: &str = "x-minerva-signature"; // the hex-encoded HMAC-SHA256 signature
+const TIMESTAMP_HEADER: &str = "x-minerva-timestamp"; // the unix time of the signature
+
+// Define the other trigger constants
+const MAX_CLOCK_SKEW: u64 = 300; // the maximum age of a signature, in seconds
+
+/// A structure to hold the settings for the inbound trigger api
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerSettings {
+    pub address: SocketAddr,                      // the interface and port to listen on
+    pub callers: FnvHashMap<String, TriggerCaller>, // the allowed callers, indexed by name
+}
+
+/// A structure to define the authentication and permissions of a caller
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerCaller {
+    pub authentication: TriggerAuthentication, // the method to authenticate the caller
+    #[serde(default)]
+    pub allowed_events: Vec<u32>, // the ids of the events the caller may trigger
+    #[serde(default)]
+    pub allowed_statuses: Vec<u32>, // the ids of the statuses the caller may change
+}
+
+/// An enum to specify how a caller proves their identity
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriggerAuthentication {
+    /// A variant for a shared key sent with each request
+    ApiKey { key: String },
+
+    /// A variant for a shared secret used to sign each request. The signature
+    /// covers the timestamp and the body of the request, joined by a period.
+    Hmac { secret: String },
+}
+
+/// A structure to remember the recently accepted signatures, so that a
+/// signed request cannot be replayed while its timestamp is still valid
+///
+#[derive(Clone, Debug, Default)]
+pub struct SignatureCache {
+    seen: FnvHashSet<(String, i64, String)>, // the caller, timestamp, and signature of each request
+}
+
+// Implement key features of the signature cache
+impl SignatureCache {
+    /// A method to record a signature, forgetting any signatures which have
+    /// expired. Returns false if the signature was already seen.
+    ///
+    pub fn insert(&mut self, caller: &str, timestamp: i64, signature: &str, now: i64) -> bool {
+        // Forget the expired signatures
+        self.seen
+            .retain(|(_, time, _)| now.abs_diff(*time) <= MAX_CLOCK_SKEW);
+
+        // Record the new signature
+        self.seen
+            .insert((caller.to_string(), timestamp, signature.to_lowercase()))
+    }
+}
+
+/// An enum to describe why a request was refused
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TriggerRejection {
+    /// A variant for missing or invalid credentials
+    Unauthorized(String),
+
+    /// A variant for valid credentials without permission for the request
+    Forbidden(String),
+
+    /// A variant for a request body which could not be read
+    Malformed(String),
+}
+
+// Allow the rejection to be passed through warp
+impl warp::reject::Reject for TriggerRejection {}
+
+// Implement key features of the trigger rejection
+impl TriggerRejection {
+    /// A method to return the status code and message of the rejection
+    ///
+    fn reply(&self) -> (http::StatusCode, &str) {
+        match self {
+            TriggerRejection::Unauthorized(message) => (http::StatusCode::UNAUTHORIZED, message),
+            TriggerRejection::Forbidden(message) => (http::StatusCode::FORBIDDEN, message),
+            TriggerRejection::Malformed(message) => (http::StatusCode::BAD_REQUEST, message),
+        }
+    }
+}
+
+/// A trait to check the allow-list of a caller for a request
+///
+pub trait TriggerRequest {
+    /// A method to indicate if the caller may make this request
+    ///
+    fn is_allowed(&self, caller: &TriggerCaller) -> bool;
+}
+
+// Implement the allow-list for each trigger request
+impl TriggerRequest for ProcessEvent {
+    fn is_allowed(&self, caller: &TriggerCaller) -> bool {
+        caller.allowed_events.contains(&self.event_id)
+    }
+}
+impl TriggerRequest for StatusChange {
+    fn is_allowed(&self, caller: &TriggerCaller) -> bool {
+        caller.allowed_statuses.contains(&self.status_id)
+    }
+}
+
+// Implement key features of the trigger settings
+impl TriggerSettings {
+    /// A function to load the trigger settings from a file. Returns None if
+    /// the file does not exist.
+    ///
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Option<TriggerSettings>, Error> {
+        // Try to open the file
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+
+        // Try to read the settings
+        let settings: TriggerSettings = serde_yaml::from_reader(file)
+            .map_err(|error| format_err!("Unable to read trigger settings: {}", error))?;
+
+        // Reject any empty keys or secrets
+        for (name, caller) in settings.callers.iter() {
+            match &caller.authentication {
+                TriggerAuthentication::ApiKey { key } if key.is_empty() => {
+                    return Err(format_err!("Trigger caller {} has an empty key.", name))
+                }
+                TriggerAuthentication::Hmac { secret } if secret.is_empty() => {
+                    return Err(format_err!("Trigger caller {} has an empty secret.", name))
+                }
+                _ => (),
+            }
+        }
+        Ok(Some(settings))
+    }
+
+    /// A method to verify the credentials of a request, returning the caller
+    /// if the request is authentic. Signed requests are recorded in the
+    /// signature cache and rejected if they are repeated.
+    ///
+    pub fn authenticate(
+        &self,
+        caller: Option<String>,
+        key: Option<String>,
+        signature: Option<String>,
+        timestamp: Option<String>,
+        body: &[u8],
+        now: i64,
+        seen: &mut SignatureCache,
+    ) -> Result<&TriggerCaller, TriggerRejection> {
+        // Find the caller
+        let (name, caller) = caller
+            .and_then(|name| self.callers.get_key_value(&name))
+            .ok_or_else(|| TriggerRejection::Unauthorized("Unknown caller.".into()))?;
+
+        // Check the credentials of the caller
+        let is_authentic = match &caller.authentication {
+            // Compare the provided key (never accepting an empty key)
+            TriggerAuthentication::ApiKey { key: expected } => key
+                .filter(|_| !expected.is_empty())
+                .map(|key| constant_time_eq(key.as_bytes(), expected.as_bytes()))
+                .unwrap_or(false),
+
+            // Verify the signature and the age of the timestamp
+            TriggerAuthentication::Hmac { secret } => {
+                match (signature, timestamp) {
+                    (Some(signature), Some(timestamp)) => {
+                        // Reject stale or invalid timestamps
+                        let time = match timestamp.parse::<i64>() {
+                            Ok(time) if now.abs_diff(time) <= MAX_CLOCK_SKEW => time,
+                            _ => {
+                                return Err(TriggerRejection::Unauthorized(
+                                    "Invalid or expired timestamp.".into(),
+                                ))
+                            }
+                        };
+
+                        // Verify the signature and reject a repeated request
+                        if !verify_signature(secret, &timestamp, body, &signature) {
+                            false
+                        } else if !seen.insert(name, time, signature.trim(), now) {
+                            return Err(TriggerRejection::Unauthorized(
+                                "Repeated request.".into(),
+                            ));
+                        } else {
+                            true
+                        }
+                    }
+                    _ => false,
+                }
+            }
+        };
+
+        // Return the caller if authentic
+        if is_authentic {
+            Ok(caller)
+        } else {
+            Err(TriggerRejection::Unauthorized("Invalid credentials.".into()))
+        }
+    }
+
+    /// A method to authenticate and read a request, checking that the caller
+    /// is allowed to make it
+    ///
+    pub fn authorize<T>(
+        &self,
+        caller: Option<String>,
+        key: Option<String>,
+        signature: Option<String>,
+        timestamp: Option<String>,
+        body: &[u8],
+        seen: &Mutex<SignatureCache>,
+    ) -> Result<T, TriggerRejection>
+    where
+        T: DeserializeOwned + TriggerRequest,
+    {
+        // Verify the credentials
+        let caller = {
+            let mut seen = seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            self.authenticate(caller, key, signature, timestamp, body, Utc::now().timestamp(), &mut seen)?
+        };
+
+        // Read the request
+        let request: T = serde_json::from_slice(body)
+            .map_err(|error| TriggerRejection::Malformed(format!("Invalid request: {}", error)))?;
+
+        // Check the allow-list
+        if request.is_allowed(caller) {
+            Ok(request)
+        } else {
+            Err(TriggerRejection::Forbidden("Request not allowed for this caller.".into()))
+        }
+    }
+}
+
+/// A function to extract an authorized request from the headers and body of
+/// the message. The signature cache should be shared by every route.
+///
+pub fn with_authorization<T>(
+    settings: Arc<TriggerSettings>,
+    seen: Arc<Mutex<SignatureCache>>,
+) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+where
+    T: DeserializeOwned + TriggerRequest + Send,
+{
+    warp::header::optional::<String>(CALLER_HEADER)
+        .and(warp::header::optional::<String>(KEY_HEADER))
+        .and(warp::header::optional::<String>(SIGNATURE_HEADER))
+        .and(warp::header::optional::<String>(TIMESTAMP_HEADER))
+        .and(warp::body::content_length_limit(1024 * 16)) // reject large payloads
+        .and(warp::body::bytes())
+        .and_then(move |caller, key, signature, timestamp, body: Bytes| {
+            // Check the request against the settings
+            let result = settings
+                .authorize(caller, key, signature, timestamp, &body, &seen)
+                .map_err(warp::reject::custom);
+            async move { result }
+        })
+}
+
+/// A function to convert rejections into replies for the trigger api
+///
+pub async fn handle_rejection(
+    rejection: warp::Rejection,
+) -> Result<impl warp::Reply, std::convert::Infallible> {
+    // Reply with the reason for the trigger rejection, if found
+    let (status, message) = if let Some(trigger_rejection) = rejection.find::<TriggerRejection>() {
+        trigger_rejection.reply()
+
+    // Otherwise, note a missing route or a general failure
+    } else if rejection.is_not_found() {
+        (http::StatusCode::NOT_FOUND, "Unknown request.")
+    } else {
+        (http::StatusCode::BAD_REQUEST, "Unable to process request.")
+    };
+
+    // Return the reply
+    Ok(warp::reply::with_status(
+        warp::reply::json(&WebReply::failure(message)),
+        status,
+    ))
+}
+
+// A helper function to verify an HMAC-SHA256 signature of the timestamp and body
+fn verify_signature(secret: &str, timestamp: &str, body: &[u8], signature: &str) -> bool {
+    // Decode the signature
+    let signature = match hex::decode(signature.trim()) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    // Compute and compare the expected signature
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+// A helper function to compare two keys without leaking their contents through timing
+fn constant_time_eq(first: &[u8], second: &[u8]) -> bool {
+    // Reject keys of different length
+    if first.len() != second.len() {
+        return false;
+    }
+
+    // Compare every byte
+    first
+        .iter()
+        .zip(second.iter())
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+// Tests of the trigger api module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A helper function to create the test settings
+    fn settings() -> TriggerSettings {
+        serde_yaml::from_str(
+            "address: 0.0.0.0:64638
+callers:
+  box_office:
+    authentication:
+      ApiKey:
+        key: tickets
+    allowed_events: [10, 11]
+  game_master:
+    authentication:
+      Hmac:
+        secret: hidden
+    allowed_events: [20]
+    allowed_statuses: [30]
+",
+        )
+        .unwrap()
+    }
+
+    // A helper function to sign a request
+    fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    // Verify the API key authentication
+    #[test]
+    fn api_key() {
+        let settings = settings();
+        let mut seen = SignatureCache::default();
+        let body = b"{}";
+        let name = Some("box_office".to_string());
+        assert!(settings
+            .authenticate(name.clone(), Some("tickets".into()), None, None, body, 0, &mut seen)
+            .is_ok());
+        assert!(settings
+            .authenticate(name.clone(), Some("ticket".into()), None, None, body, 0, &mut seen)
+            .is_err());
+        assert!(settings.authenticate(name, None, None, None, body, 0, &mut seen).is_err());
+        assert!(settings
+            .authenticate(Some("stranger".into()), Some("tickets".into()), None, None, body, 0, &mut seen)
+            .is_err());
+
+        // Reject an empty key, even if it matches the settings
+        let mut settings = settings;
+        if let Some(caller) = settings.callers.get_mut("box_office") {
+            caller.authentication = TriggerAuthentication::ApiKey { key: String::new() };
+        }
+        assert!(settings
+            .authenticate(Some("box_office".into()), Some(String::new()), None, None, body, 0, &mut seen)
+            .is_err());
+    }
+
+    // Verify the HMAC authentication
+    #[test]
+    fn hmac_signature() {
+        let settings = settings();
+        let body = br#"{"eventId":20,"checkScene":true,"broadcast":true}"#;
+        let name = Some("game_master".to_string());
+        let signature = sign("hidden", "1000", body);
+        let mut seen = SignatureCache::default();
+
+        // Accept a valid signature only once
+        assert!(settings
+            .authenticate(name.clone(), None, Some(signature.clone()), Some("1000".into()), body, 1010, &mut seen)
+            .is_ok());
+        assert!(matches!(
+            settings.authenticate(name.clone(), None, Some(signature.to_uppercase()), Some("1000".into()), body, 1020, &mut seen),
+            Err(TriggerRejection::Unauthorized(_))
+        ));
+
+        // Reject an expired timestamp, a modified body, and a wrong secret
+        assert!(settings
+            .authenticate(name.clone(), None, Some(signature.clone()), Some("1000".into()), body, 2000, &mut seen)
+            .is_err());
+        assert!(settings
+            .authenticate(name.clone(), None, Some(signature), Some("1000".into()), b"{}", 1000, &mut seen)
+            .is_err());
+        let signature = sign("guess", "1000", body);
+        assert!(settings
+            .authenticate(name, None, Some(signature), Some("1000".into()), body, 1000, &mut seen)
+            .is_err());
+    }
+
+    // Verify the allow-lists of each caller
+    #[test]
+    fn allow_list() {
+        let settings = settings();
+        let name = Some("box_office".to_string());
+        let key = Some("tickets".to_string());
+        let seen = Mutex::new(SignatureCache::default());
+
+        // Allow a listed event
+        let body = br#"{"eventId":10,"checkScene":true,"broadcast":true}"#;
+        let request: ProcessEvent = settings
+            .authorize(name.clone(), key.clone(), None, None, body, &seen)
+            .unwrap();
+        assert_eq!(request.event_id, 10);
+
+        // Refuse an unlisted event or status
+        let body = br#"{"eventId":20,"checkScene":true,"broadcast":true}"#;
+        assert!(matches!(
+            settings.authorize::<ProcessEvent>(name.clone(), key.clone(), None, None, body, &seen),
+            Err(TriggerRejection::Forbidden(_))
+        ));
+        let body = br#"{"statusId":30,"stateId":31}"#;
+        assert!(matches!(
+            settings.authorize::<StatusChange>(name, key, None, None, body, &seen),
+            Err(TriggerRejection::Forbidden(_))
+        ));
+    }
+}
@@ -44,6 +44,11 @@ pub struct BroadcastEvent {
     id: u32,
     data: Option<u32>,
 }
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockSpeed {
+    speed: f64,
+}
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConfigFile { 
@@ -73,6 +78,11 @@ pub struct Edit {
 }
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct EnableSchedule {
+    is_enabled: bool,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ErrorLog {
     filename: String,
 }
@@ -85,6 +95,12 @@ pub struct EventChange {
 }
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct FireEvent {
+    event_id: ItemId,
+    start_time: NaiveDateTime,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GameLog {
     filename: String,
 }
@@ -115,13 +131,50 @@ pub struct GetType {
 }
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct HoldEvent {
+    event_id: ItemId,
+    start_time: NaiveDateTime,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProcessEvent {
-    event_id: u32,
+    pub event_id: u32,
     check_scene: bool,
     broadcast: bool,
 }
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct QueryLog {
+    #[serde(default)]
+    start: Option<NaiveDateTime>,
+    #[serde(default)]
+    end: Option<NaiveDateTime>,
+    #[serde(default)]
+    log_types: Vec<LogType>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Redo {
+    #[serde(default)]
+    fire_events: bool,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseEvent {
+    event_id: ItemId,
+    start_time: NaiveDateTime,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreGame {
+    restore: bool,
+}
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SaveConfig {
     filename: String,
 }
@@ -138,9 +191,15 @@ pub struct SceneChange {
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StatusChange {
-    status_id: u32,
+    pub status_id: u32,
     state_id: u32,
 }
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Undo {
+    #[serde(default)]
+    fire_events: bool,
+}
 
 // Implement FromStr for helper data types
 impl FromStr for GetEvent {
@@ -216,6 +275,13 @@ impl From<BroadcastEvent> for UserRequest {
         }
     }
 }
+impl From<ClockSpeed> for UserRequest {
+    fn from(clock_speed: ClockSpeed) -> Self {
+        UserRequest::ClockSpeed {
+            speed: clock_speed.speed,
+        }
+    }
+}
 impl From<ConfigFile> for UserRequest {
     fn from(config_file: ConfigFile) -> Self {
         UserRequest::ConfigFile {
@@ -260,6 +326,13 @@ impl From<Edit> for UserRequest {
         }
     }
 }
+impl From<EnableSchedule> for UserRequest {
+    fn from(enable_schedule: EnableSchedule) -> Self {
+        UserRequest::EnableSchedule {
+            is_enabled: enable_schedule.is_enabled,
+        }
+    }
+}
 impl From<ErrorLog> for UserRequest {
     fn from(error_log: ErrorLog) -> Self {
         UserRequest::ErrorLog {
@@ -269,10 +342,26 @@ impl From<ErrorLog> for UserRequest {
 }
 impl From<EventChange> for UserRequest {
     fn from(event_change: EventChange) -> Self {
+        // Cancel the event if there is no new delay
+        let change = match event_change.new_delay {
+            Some(delay) => EventAdjustment::Delay(delay),
+            None => EventAdjustment::Cancel,
+        };
+
+        // Return the request
         UserRequest::EventChange {
             event_id: event_change.event_id,
             start_time: event_change.start_time,
-            new_delay: event_change.new_delay,
+            change,
+        }
+    }
+}
+impl From<FireEvent> for UserRequest {
+    fn from(fire_event: FireEvent) -> Self {
+        UserRequest::EventChange {
+            event_id: fire_event.event_id,
+            start_time: fire_event.start_time,
+            change: EventAdjustment::FireNow,
         }
     }
 }
@@ -319,6 +408,15 @@ impl From<GetType> for UserRequest {
         }
     }
 }
+impl From<HoldEvent> for UserRequest {
+    fn from(hold_event: HoldEvent) -> Self {
+        UserRequest::EventChange {
+            event_id: hold_event.event_id,
+            start_time: hold_event.start_time,
+            change: EventAdjustment::Hold,
+        }
+    }
+}
 impl From<ProcessEvent> for UserRequest {
     fn from(process_event: ProcessEvent) -> Self {
         UserRequest::ProcessEvent {
@@ -328,6 +426,40 @@ impl From<ProcessEvent> for UserRequest {
         }
     }
 }
+impl From<QueryLog> for UserRequest {
+    fn from(query_log: QueryLog) -> Self {
+        UserRequest::QueryLog {
+            start: query_log.start,
+            end: query_log.end,
+            log_types: query_log.log_types,
+            offset: query_log.offset,
+            limit: query_log.limit,
+        }
+    }
+}
+impl From<Redo> for UserRequest {
+    fn from(redo: Redo) -> Self {
+        UserRequest::Redo {
+            fire_events: redo.fire_events,
+        }
+    }
+}
+impl From<ReleaseEvent> for UserRequest {
+    fn from(release_event: ReleaseEvent) -> Self {
+        UserRequest::EventChange {
+            event_id: release_event.event_id,
+            start_time: release_event.start_time,
+            change: EventAdjustment::Release,
+        }
+    }
+}
+impl From<RestoreGame> for UserRequest {
+    fn from(restore_game: RestoreGame) -> Self {
+        UserRequest::RestoreGame {
+            restore: restore_game.restore,
+        }
+    }
+}
 impl From<SaveConfig> for UserRequest {
     fn from(save_config: SaveConfig) -> Self {
         UserRequest::SaveConfig {
@@ -349,4 +481,11 @@ impl From<StatusChange> for UserRequest {
             state: ItemId::new_unchecked(status_change.state_id),
         }
     }
+}
+impl From<Undo> for UserRequest {
+    fn from(undo: Undo) -> Self {
+        UserRequest::Undo {
+            fire_events: undo.fire_events,
+        }
+    }
 }
\ No newline at end of file
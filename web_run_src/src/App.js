@@ -20,8 +20,11 @@ export class App extends React.PureComponent {
       notice: "",
       notifications: [],
       timelineEvents: [],
+      isPaused: false,
       scenes: [],
       fullStatus: {},
+      timers: {},
+      values: {},
       currentScene: {},
       currentItems: [],
       keyMap: {},
@@ -31,6 +34,7 @@ export class App extends React.PureComponent {
     // Bind the various functions
     this.processUpdate = this.processUpdate.bind(this);
     this.closeMinerva = this.closeMinerva.bind(this);
+    this.endGame = this.endGame.bind(this);
 
     // Save variables (not based on state)
     this.socket = null;
@@ -64,6 +68,11 @@ export class App extends React.PureComponent {
       this.setState({
         connectionActive: true,
       });
+
+      // Request the current window (and any prompt to restore a saved game)
+      fetch(`/redraw`, {
+        method: 'POST',
+      });
     });
 
     // If the socket closes
@@ -128,6 +137,24 @@ export class App extends React.PureComponent {
         }
       });
 
+    // Ask the operator whether to restore the saved game
+    } else if (data.hasOwnProperty(`restorePrompt`)) {
+      // Describe the saved game
+      const saved = data[`restorePrompt`][`saved`].replace(`T`, ` `).split(`.`)[0];
+      const scene = data[`restorePrompt`][`scene`][`description`];
+      const restore = window.confirm(`A saved game from ${saved} (${scene}) was found. Restore the saved game?\n\nSelect Cancel to discard the saved game and start the scene from the beginning.`);
+
+      // Send the decision to the server
+      fetch(`/restoreGame`, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({
+          restore: restore,
+        }),
+      });
+
     // Update the available scenes and full status in the window
     } else if (data.hasOwnProperty(`updateConfig`)) {
       this.setState({
@@ -149,13 +176,36 @@ export class App extends React.PureComponent {
         // Update the particular status
         let newStatus = {...prevState.fullStatus};
         newStatus[`${data['updateStatus']['statusId']}`] = data[`updateStatus`][`newState`];
+
+        // Update the value of the status, if it is a variable
+        let newValues = {...prevState.values};
+        if (data[`updateStatus`][`value`] !== null) {
+          newValues[`${data['updateStatus']['statusId']['id']}`] = data[`updateStatus`][`value`];
+        }
         
         // Update the full status
         return {
           fullStatus: newStatus,
+          values: newValues,
         };
       });
     
+    // Update the remaining time of a particular timer
+    } else if (data.hasOwnProperty(`updateTimer`)) {
+      this.setState((prevState) => {
+        // Update the particular timer
+        let newTimers = {...prevState.timers};
+        newTimers[`${data['updateTimer']['statusId']['id']}`] = {
+          remaining: data[`updateTimer`][`remaining`],
+          isRunning: data[`updateTimer`][`isRunning`],
+        };
+
+        // Update the timers
+        return {
+          timers: newTimers,
+        };
+      });
+
     // Update the current notifications
     } else if (data.hasOwnProperty(`updateNotifications`)) {
       this.setState({
@@ -170,6 +220,7 @@ export class App extends React.PureComponent {
     } else if (data.hasOwnProperty(`updateTimeline`)) {
       this.setState({
         timelineEvents: data[`updateTimeline`][`events`],
+        isPaused: data[`updateTimeline`][`isPaused`],
       });
 
       // FIXME print to commandline
@@ -194,13 +245,23 @@ export class App extends React.PureComponent {
     });
   }
 
+  // Function to end the current game and clear the backup
+  async endGame() {
+    fetch(`/endGame`, {
+      method: 'POST',
+      headers: {
+          'Content-Type': 'application/json',
+      },
+    }); // FIXME ignore errors
+  }
+
   // Render the complete application
   render() {
     return (
       <>
         <link id="userStyles" rel="stylesheet" href={`/getStyles/${this.state.randomCss}.css`} />
         <div className="app">
-          <HeaderMenu closeMinerva={this.closeMinerva}/>
+          <HeaderMenu closeMinerva={this.closeMinerva} endGame={this.endGame}/>
           <ViewArea currentScene={this.state.currentScene} currentItems={this.state.currentItems} />
           <FooterMenu notice={this.state.notice} />
         </div>
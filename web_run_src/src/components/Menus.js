@@ -94,6 +94,7 @@ export class HeaderMenu extends React.PureComponent {
           <ConfirmButton buttonClass="menuButton" onClick={() => {switchPort(64637);}} buttonText="Edit Mode" />
         </div>
         <div className="headerRight">
+          <ConfirmButton buttonClass="menuButton" onClick={() => {this.props.endGame();}} buttonText="End Game" />
           <ConfirmButton buttonClass="menuButton" onClick={() => {this.props.closeMinerva();}} buttonText="Quit Minerva" />
           <img src={logoWide} className="logo" alt="logo" />
         </div>